@@ -90,7 +90,33 @@ extension WidgetTesterExtensions on WidgetTester {
     await bus.close();
     return message;
   }
+
+  /// Waits for the runner to answer whether to run the next step in interactive mode,
+  /// returning false to skip it. Once aborted, every following step fails right away.
+  Future<bool> pauseBeforeStep(String step, {bool skippable = true}) async {
+    if (_interactiveAborted) {
+      throw TestFailure('Aborted from interactive mode');
+    }
+
+    final action = await waitForInputViaDBus('{DBUS_STEP_PAUSE_PREFIX}$step');
+    switch (action) {
+      case 'abort':
+        _interactiveAborted = true;
+        throw TestFailure('Aborted from interactive mode');
+      case 'skip':
+        if (skippable) {
+          stdout.writeln('Skipping step $step');
+          return false;
+        }
+        stdout.writeln('Step $step declares a value used later, running it');
+        return true;
+      default:
+        return true;
+    }
+  }
 }
+
+bool _interactiveAborted = false;
 "#;
 
         fs::write(
@@ -101,6 +127,10 @@ extension WidgetTesterExtensions on WidgetTester {
                 .replace(
                     "{DBUS_METHOD_CONTINUE}",
                     DbusUtils::get_dbus_method_continue(),
+                )
+                .replace(
+                    "{DBUS_STEP_PAUSE_PREFIX}",
+                    DbusUtils::get_dbus_step_pause_prefix(),
                 ),
         )?;
         info!("Generated dbus.dart");
@@ -17,14 +17,14 @@ impl GenFlutter {
 
         // Import each generated test file
         for feature in features {
-            let import_name = feature.name.to_lowercase().replace(' ', "_") + "_test.dart";
-            let alias = feature.name.to_lowercase().replace([' ', '-'], "_");
+            let import_name = feature.dart_file_name();
+            let alias = feature.dart_alias();
             content.push_str(&format!("import '{import_name}' as {alias};\n"));
         }
 
         content.push_str("\nvoid main() {\n");
         for feature in features {
-            let alias = feature.name.to_lowercase().replace([' ', '-'], "_");
+            let alias = feature.dart_alias();
             content.push_str(&format!("  {alias}.main();\n"));
         }
         content.push_str("}\n");
@@ -27,6 +27,7 @@ import 'dart:ui';
 import 'dart:typed_data';
 
 import 'package:flutter/material.dart';
+import 'package:flutter/services.dart';
 import 'package:flutter_test/flutter_test.dart';
 import 'package:integration_test/integration_test.dart';
 import 'package:path/path.dart' as p;
@@ -37,9 +38,14 @@ import 'package:{project_name}/main.dart' as app;
 
 const updateScreenshots = bool.fromEnvironment('UPDATE_SCREENSHOTS');
 
+/// Pointer pressed by a `pointer: { down: ... }` step, extensions can't hold state.
+TestGesture? _activeGesture;
+
 /// Custom extensions for WidgetTester and Finders used by generated tests.
 extension WidgetTesterExtensions on WidgetTester {
   Future<void> initializeTest(String state) async {
+    // Not released when the previous test failed while pressing it
+    _activeGesture = null;
     {state_set_command}
 
     IntegrationTestWidgetsFlutterBinding.ensureInitialized().framePolicy =
@@ -96,6 +102,17 @@ extension WidgetTesterExtensions on WidgetTester {
     );
   }
 
+  /// Waits for the app network requests to finish.
+  ///
+  /// The app must show a widget keyed [marker] while requests are in flight,
+  /// steps can call their own helper when the app exposes idleness another way.
+  Future<void> waitForNetworkIdle({
+    Duration timeout = const Duration(seconds: 10),
+    String marker = 'network_busy',
+  }) async {
+    await pumpUntilGone(find.byKey(Key(marker)), timeout: timeout);
+  }
+
   Future<void> pumpUntilProgressCompleted(
     Finder finder, {
     Duration timeout = const Duration(seconds: 10),
@@ -156,6 +173,7 @@ extension WidgetTesterExtensions on WidgetTester {
     String name, {
     bool negate = false,
     bool update = false,
+    double threshold = 0.003,
   }) async {
     // --- Paths ---
     final String projectRoot = Directory.current.path;
@@ -167,16 +185,7 @@ extension WidgetTesterExtensions on WidgetTester {
     );
     final String imagePath = p.join(folderPath, '$name.png');
 
-    // --- Capture current screen ---
-    final el = find.byWidgetPredicate((w) => w is Screenshot);
-    final widget = el.first.evaluate().first.widget as Screenshot;
-    final res = await widget.controller.capture(
-      delay: const Duration(milliseconds: 10),
-    );
-
-    if (res == null) {
-      throw Exception('Failed to capture screenshot');
-    }
+    final res = await _takeScreenshot();
 
     final File imageFile = File(imagePath);
     await Directory(folderPath).create(recursive: true);
@@ -218,8 +227,8 @@ extension WidgetTesterExtensions on WidgetTester {
         diffRatio = 1.0 - diffRatio;
       }
 
-      // 0.5% threshold
-      if (diffRatio > 0.005) {
+      // 0.3% threshold unless overridden by the step
+      if (diffRatio > threshold) {
         final String failedFolderPath = p.join(
           projectRoot,
           'integration_test',
@@ -239,13 +248,148 @@ extension WidgetTesterExtensions on WidgetTester {
 Screenshot comparison failed for $name, please update screenshots if the changes are expected.
 
 Please run the following command to update screenshots:
-flutter test integration_test --dart-define=UPDATE_SCREENSHOTS=true''',
+playmaster run --update-screenshots''',
         );
       }
     }
   }
 
+  /// Saves the current screen to `integration_test/screenshots/captures/[name].png`,
+  /// without comparing it against anything.
+  Future<void> captureScreenshot(String name) async {
+    final String folderPath = p.join(
+      Directory.current.path,
+      'integration_test',
+      'screenshots',
+      'captures',
+    );
+    final String imagePath = p.join(folderPath, '$name.png');
+
+    final res = await _takeScreenshot();
+    await Directory(folderPath).create(recursive: true);
+    await File(imagePath).writeAsBytes(res);
+    debugPrint('Saved capture: $imagePath');
+  }
+
+  Future<Uint8List> _takeScreenshot() async {
+    final el = find.byWidgetPredicate((w) => w is Screenshot);
+    final widget = el.first.evaluate().first.widget as Screenshot;
+    final res = await widget.controller.capture(
+      delay: const Duration(milliseconds: 10),
+    );
+
+    if (res == null) {
+      throw Exception('Failed to capture screenshot');
+    }
+
+    return res;
+  }
+
+  /// Asserts [property] (`enabled`, `text` or `checked`) of the first widget
+  /// matching [finder] equals [expected], compared as strings.
+  void assertProperty(Finder finder, String property, String expected) {
+    expect(finder, findsWidgets);
+    final actual = switch (property) {
+      'enabled' => _isEnabled(finder.first),
+      'text' => _textOf(finder.first),
+      'checked' => _isChecked(finder.first),
+      _ => throw ArgumentError.value(property, 'property', 'Unsupported property'),
+    };
+    expect('$actual', expected, reason: '$property of $finder');
+  }
+
+  /// Asserts the [Scrollable] matching [finder], within it or around it, scrolled along
+  /// [axis] (`vertical` or `horizontal`) is at least [atLeast] pixels from its start.
+  void assertScrollOffset(Finder finder, String axis, double atLeast) {
+    expect(finder, findsWidgets);
+    final direction = axis == 'horizontal' ? Axis.horizontal : Axis.vertical;
+    final scrollables = [
+      ...find.descendant(of: finder.first, matching: find.byType(Scrollable), matchRoot: true).evaluate(),
+      ...find.ancestor(of: finder.first, matching: find.byType(Scrollable)).evaluate(),
+    ]
+        .map((e) => (e as StatefulElement).state as ScrollableState)
+        .where((s) => axisDirectionToAxis(s.axisDirection) == direction);
+    if (scrollables.isEmpty) {
+      throw Exception('No $axis Scrollable found for $finder');
+    }
+
+    final offset = scrollables.first.position.pixels;
+    expect(offset, greaterThanOrEqualTo(atLeast), reason: '$axis scroll offset of $finder');
+  }
+
+  bool _isEnabled(Finder finder) {
+    final w = widget(finder);
+    return switch (w) {
+      ButtonStyleButton b => b.enabled,
+      IconButton b => b.onPressed != null,
+      TextField t => t.enabled ?? true,
+      Checkbox c => c.onChanged != null,
+      Switch s => s.onChanged != null,
+      _ => throw Exception('Unsupported widget for enabled: ${w.runtimeType}'),
+    };
+  }
+
+  String _textOf(Finder finder) {
+    final w = widget(finder);
+    if (w is Text) {
+      return w.data ?? w.textSpan?.toPlainText() ?? '';
+    }
+
+    final editable = find.descendant(
+      of: finder,
+      matching: find.byType(EditableText),
+      matchRoot: true,
+    );
+    if (any(editable)) {
+      return widget<EditableText>(editable.first).controller.text;
+    }
+
+    throw Exception('Unsupported widget for text: ${w.runtimeType}');
+  }
+
+  bool? _isChecked(Finder finder) {
+    final w = widget(finder);
+    return switch (w) {
+      Checkbox c => c.value,
+      CheckboxListTile c => c.value,
+      Switch s => s.value,
+      SwitchListTile s => s.value,
+      _ => throw Exception('Unsupported widget for checked: ${w.runtimeType}'),
+    };
+  }
+
+  /// Presses a pointer at [at], kept down for the next [movePointer] and [pointerUp].
+  Future<void> pointerDown(Offset at) async {
+    await releasePointer();
+    _activeGesture = await startGesture(at, kind: PointerDeviceKind.mouse);
+    await pump();
+  }
+
+  Future<void> pointerUp() async {
+    final gesture = _activeGesture;
+    if (gesture == null) {
+      throw Exception('pointer up without a pressed pointer');
+    }
+    _activeGesture = null;
+    await gesture.up();
+    await pumpAndSettle();
+  }
+
+  /// Releases the pointer left pressed, if any, called at the end of tests pressing one.
+  Future<void> releasePointer() async {
+    final gesture = _activeGesture;
+    _activeGesture = null;
+    await gesture?.up();
+  }
+
   Future<void> movePointer(Offset to, {bool remove = false}) async {
+    final pressed = _activeGesture;
+    if (pressed != null) {
+      await pressed.moveTo(to);
+      await pump();
+      return;
+    }
+
     final TestGesture gesture = await createGesture(
       kind: PointerDeviceKind.mouse,
     );
@@ -258,6 +402,73 @@ flutter test integration_test --dart-define=UPDATE_SCREENSHOTS=true''',
     await pumpAndSettle();
   }
 
+  /// Pinches [finder] with two pointers around its center moved by [offset].
+  /// A [scale] above 1 zooms in and below 1 zooms out, from 0.2 to 5.0.
+  Future<void> pinch(
+    Finder finder,
+    double scale, {
+    Offset offset = Offset.zero,
+    int steps = 10,
+  }) async {
+    if (scale < 0.2 || scale > 5.0) {
+      throw ArgumentError.value(scale, 'scale', 'Must be between 0.2 and 5.0');
+    }
+
+    final center = getCenter(finder.first) + offset;
+    const startDistance = 50.0;
+    final endDistance = startDistance * scale;
+
+    final first = await startGesture(center - const Offset(startDistance, 0));
+    final second = await startGesture(center + const Offset(startDistance, 0));
+    for (var i = 1; i <= steps; i++) {
+      final distance = startDistance + (endDistance - startDistance) * i / steps;
+      await first.moveTo(center - Offset(distance, 0));
+      await second.moveTo(center + Offset(distance, 0));
+      await pump(const Duration(milliseconds: 16));
+    }
+    await first.up();
+    await second.up();
+    await pumpAndSettle();
+  }
+
+  /// Presses the center of [finder] for [hold] before releasing, unlike [longPress] which
+  /// always releases right after the long press timeout.
+  Future<void> longPressFor(Finder finder, Duration hold) async {
+    final gesture = await startGesture(getCenter(finder.first), kind: PointerDeviceKind.mouse);
+    await pump(hold);
+    await gesture.up();
+    await pumpAndSettle();
+  }
+
+  /// Drags the center of [from] onto the center of [to] in small moves, so drag targets and
+  /// reorderable lists follow the pointer, after holding it for [hold].
+  Future<void> dragBetween(Finder from, Finder to, {Duration hold = Duration.zero, int steps = 20}) async {
+    final start = getCenter(from.first);
+    final end = getCenter(to.first);
+    final gesture = await startGesture(start, kind: PointerDeviceKind.mouse);
+    await pump(hold);
+    for (var i = 1; i <= steps; i++) {
+      await gesture.moveTo(Offset.lerp(start, end, i / steps)!);
+      await pump(const Duration(milliseconds: 16));
+    }
+    await gesture.up();
+    await pumpAndSettle();
+  }
+
+  /// Drags [finder] towards [direction] (`up`, `down`, `left` or `right`) by [amount] of its
+  /// size along that direction, so the distance follows the screen size.
+  Future<void> swipe(Finder finder, String direction, double amount) async {
+    final size = getSize(finder.first);
+    final offset = switch (direction) {
+      'up' => Offset(0, -size.height * amount),
+      'down' => Offset(0, size.height * amount),
+      'left' => Offset(-size.width * amount, 0),
+      'right' => Offset(size.width * amount, 0),
+      _ => throw ArgumentError.value(direction, 'direction', 'Must be up, down, left or right'),
+    };
+    await drag(finder.first, offset);
+  }
+
   Future<void> tapAt(Offset offset) async {
     final gesture = await startGesture(offset);
     await gesture.up();
@@ -280,10 +491,25 @@ flutter test integration_test --dart-define=UPDATE_SCREENSHOTS=true''',
     Finder finder,
     String text, {
     PointerDeviceKind kind = PointerDeviceKind.mouse,
+    bool append = false,
+    bool submit = false,
   }) async {
     await pumpAndSettle();
     await tap(finder, kind: kind);
-    await enterText(finder, text);
+    final current = append ? _textOf(finder) : '';
+    await enterText(finder, '$current$text');
+    // The text is entered through the test text input, which doesn't submit on key events
+    if (submit) {
+      await testTextInput.receiveAction(TextInputAction.done);
+    }
+    await pumpAndSettle();
+  }
+
+  /// Focuses the text field matching [finder] and empties it.
+  Future<void> clearTextField(Finder finder) async {
+    await pumpAndSettle();
+    await tap(finder, kind: PointerDeviceKind.mouse);
+    await enterText(finder, '');
     await pumpAndSettle();
   }
 
@@ -336,6 +562,19 @@ extension FinderExtensions on CommonFinders {
       return false;
     }, description: 'Widget with ValueKey prefix "$prefix"');
   }
+
+  /// Finds a [Text] or text field whose text matches [pattern] anywhere, e.g. 'Loading \\d+%'.
+  Finder textMatching(String pattern) {
+    final regex = RegExp(pattern);
+    return byWidgetPredicate((w) {
+      if (w is Text) {
+        final text = w.data ?? w.textSpan?.toPlainText();
+        return text != null && regex.hasMatch(text);
+      }
+      if (w is EditableText) return regex.hasMatch(w.controller.text);
+      return false;
+    }, description: 'Text matching /$pattern/');
+  }
 }
 "#;
 
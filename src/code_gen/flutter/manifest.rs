@@ -0,0 +1,105 @@
+use std::{fs, path::Path};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{
+    code_gen::{flutter::GenFlutter, r#gen::CodeGen},
+    hooks::iface::HookContext,
+    models::gen_state::GenState,
+    utils::errors::{EmptyResult, ResultWithError},
+};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Summary of the generated files, for tooling and CI cache keys.
+#[derive(Debug, Serialize)]
+struct Manifest {
+    playmaster_version: String,
+    /// SHA-256 of every generated file, changes whenever the generated output does
+    content_hash: String,
+    /// SHA-256 of the inputs the files were generated from, to tell whether they are stale
+    source_hash: String,
+    features: Vec<ManifestFeature>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestFeature {
+    name: String,
+    file: String,
+    tests: Vec<ManifestTest>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestTest {
+    name: String,
+    /// Number of steps as written in the YAML file, before `use_step` expansion
+    steps: usize,
+}
+
+impl GenFlutter {
+    /// Writes `manifest.json`, must run last so the hash covers the final formatted output.
+    pub fn generate_manifest(&self, ctx: &HookContext<'_, GenState>) -> EmptyResult {
+        let features = ctx
+            .read_state()?
+            .features
+            .iter()
+            .map(|feature| ManifestFeature {
+                name: feature.name.clone(),
+                file: feature.dart_file_name(),
+                tests: feature
+                    .tests
+                    .iter()
+                    .map(|test| ManifestTest {
+                        name: test.name.clone(),
+                        steps: test.steps.len(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let manifest = Manifest {
+            playmaster_version: env!("CARGO_PKG_VERSION").to_owned(),
+            content_hash: self.hash_generated_files()?,
+            source_hash: CodeGen::source_hash(ctx.args, ctx.config)?,
+            features,
+        };
+
+        fs::write(
+            self.out_dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+        info!("Generated {}", MANIFEST_FILE);
+        Ok(())
+    }
+
+    /// Source hash recorded by the last generation into `out_dir`, if any.
+    pub fn read_source_hash(out_dir: &Path) -> Option<String> {
+        let content = fs::read_to_string(out_dir.join(MANIFEST_FILE)).ok()?;
+        let manifest = serde_json::from_str::<serde_json::Value>(&content).ok()?;
+        manifest["source_hash"].as_str().map(str::to_owned)
+    }
+
+    fn hash_generated_files(&self) -> ResultWithError<String> {
+        let mut paths = fs::read_dir(&self.out_dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file() && !path.ends_with(MANIFEST_FILE))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut hasher = Sha256::new();
+        for path in paths {
+            if let Some(name) = path.file_name() {
+                hasher.update(name.to_string_lossy().as_bytes());
+            }
+            hasher.update(fs::read(&path)?);
+        }
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+}
@@ -4,24 +4,73 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use regex::Regex;
+
 use crate::{
     code_gen::gen_iface::CodeGenTrait,
     hooks::iface::HookContext,
     models::{
         args::AppArgs,
         config::{Config, ProjectType},
-        feature_test::{self, FeatureTest, SimpleStep, Step, WaitFor},
+        feature_test::{self, FeatureTest, IfStep, SimpleStep, Step, WaitFor},
         gen_state::GenState,
     },
     utils::{
         dir::DirUtils,
         errors::{EmptyResult, ResultWithError},
+        flutter::SETUP_FAILURE_PREFIX,
+        variables::LOOP_INDEX_VAR,
     },
 };
 
+lazy_static::lazy_static! {
+    static ref DART_IMPORT_RE: Regex = Regex::new(
+        r#"^import\s+('[^']+'|"[^"]+")(\s+(deferred\s+)?as\s+\w+)?(\s+(show|hide)\s+[\w\s,]+)?\s*;$"#
+    )
+    .expect("Failed to compile DART_IMPORT_RE");
+    static ref DART_DOLLAR_RE: Regex =
+        Regex::new(r"\$\{[A-Za-z_]\w*(\.[A-Za-z_]\w*)*\}|\$").expect("Failed to compile DART_DOLLAR_RE");
+}
+
+/// Fraction of pixels allowed to differ in a screenshot comparison when the step sets none.
+const DEFAULT_SCREENSHOT_THRESHOLD: f64 = 0.003;
+
+/// Keys a `key` step can press, named like the `LogicalKeyboardKey` constants.
+const LOGICAL_KEYS: &[&str] = &[
+    "escape",
+    "enter",
+    "numpadEnter",
+    "tab",
+    "space",
+    "backspace",
+    "delete",
+    "insert",
+    "arrowUp",
+    "arrowDown",
+    "arrowLeft",
+    "arrowRight",
+    "home",
+    "end",
+    "pageUp",
+    "pageDown",
+    "f1",
+    "f2",
+    "f3",
+    "f4",
+    "f5",
+    "f6",
+    "f7",
+    "f8",
+    "f9",
+    "f10",
+    "f11",
+    "f12",
+];
+
 mod dbus;
 mod entrypoint;
 mod helper;
+mod manifest;
 mod test_driver;
 mod utils;
 mod vars;
@@ -54,6 +103,7 @@ impl CodeGenTrait for GenFlutter {
         self.generate_test_driver()?;
         self.run_dart_format();
         self.run_dart_fix();
+        self.generate_manifest(ctx)?;
 
         Ok(())
     }
@@ -62,7 +112,7 @@ impl CodeGenTrait for GenFlutter {
 impl GenFlutter {
     pub fn from_exec_dir(args: AppArgs, config: Config) -> ResultWithError<Self> {
         let cwd = DirUtils::curr_dir()?;
-        let out_dir = cwd.join("integration_test/generated");
+        let out_dir = cwd.join(&args.output_dir);
         fs::create_dir_all(&out_dir)?;
 
         Ok(Self {
@@ -74,22 +124,60 @@ impl GenFlutter {
 }
 
 impl FeatureTest {
+    /// Name of the generated test file without extension, also used as the screenshot folder name.
+    pub fn dart_test_name(&self) -> String {
+        self.name.to_lowercase().replace(' ', "_") + "_test"
+    }
+
+    /// Name of the generated Dart test file, e.g. `login_test.dart`.
+    pub fn dart_file_name(&self) -> String {
+        self.dart_test_name() + ".dart"
+    }
+
+    /// Import alias of the feature in `all_tests.dart`, two features sharing it would collide.
+    pub fn dart_alias(&self) -> String {
+        self.name.to_lowercase().replace([' ', '-'], "_")
+    }
+
     pub fn generate_dart(&self, ctx: &HookContext<'_, GenState>, out_dir: &Path) -> EmptyResult {
-        let normalized_name = self.name.to_lowercase().replace(' ', "_") + "_test";
-        let file_name = normalized_name.clone() + ".dart";
-        let file_path = out_dir.join(&file_name);
+        self.check_loop_index()
+            .map_err(|e| format!("{}: {}", self.source_path.display(), e))?;
+
+        let normalized_name = self.dart_test_name();
+        let file_path = out_dir.join(self.dart_file_name());
         let mut out = String::new();
-        let has_before_each = self.before_each.is_some();
+        let has_before_each = self
+            .before_each
+            .as_ref()
+            .is_some_and(|before_each| !before_each.steps.is_empty());
 
         // Header
         out.push_str("// GENERATED FILE - DO NOT EDIT\n");
         out.push_str("import 'dart:ui';\n");
         out.push_str("import 'package:flutter_test/flutter_test.dart';\n");
-        out.push_str("import 'package:flutter/material.dart';\n");
+        // The `state` vars class would clash with the Flutter `State` class
+        if ctx.vars.data.iter().any(|v| v.file_name == "state") {
+            out.push_str("import 'package:flutter/material.dart' hide State;\n");
+        } else {
+            out.push_str("import 'package:flutter/material.dart';\n");
+        }
+        out.push_str("import 'package:flutter/services.dart';\n");
         out.push_str("import 'package:integration_test/integration_test.dart';\n");
         out.push_str("import 'dbus.dart';\n");
         out.push_str("import 'helpers.dart';\n");
-        out.push_str("import 'vars.dart';\n\n");
+        out.push_str("import 'vars.dart';\n");
+        for import in &self.imports {
+            let import = import.trim();
+            if !DART_IMPORT_RE.is_match(import) {
+                return Err(format!(
+                    "Invalid import in feature '{}': {}, expected a Dart import such as: import 'package:app/utils.dart';",
+                    self.name, import
+                )
+                .into());
+            }
+            out.push_str(&format!("{}\n", import));
+        }
+        out.push('\n');
         out.push_str("void main() {\n");
         out.push_str("  IntegrationTestWidgetsFlutterBinding.ensureInitialized();\n\n");
 
@@ -123,30 +211,96 @@ beforeEach(WidgetTester tester) async {{
 
         out.push_str(&format!("  group('{} -', () {{\n", self.name));
 
-        // Test cases
-        for test in &self.tests {
+        // Once per feature setup/teardown
+        if let Some(before_all) = self.before_all.as_ref()
+            && !before_all.steps.is_empty()
+        {
+            out.push_str("    setUpAll(() async {\n");
+            for step in &before_all.steps {
+                out.push_str(&step.to_dart_code_without_tester(&self.step_definitions)?);
+            }
+            out.push_str("    });\n\n");
+        }
+
+        if let Some(after_all) = self.after_all.as_ref()
+            && !after_all.steps.is_empty()
+        {
+            out.push_str("    tearDownAll(() async {\n");
+            for step in &after_all.steps {
+                out.push_str(&step.to_dart_code_without_tester(&self.step_definitions)?);
+            }
+            out.push_str("    });\n\n");
+        }
+
+        // Test cases, repeated when hunting flaky tests
+        let repeat = match ctx.args.filter() {
+            Some(filter) if !self.matches_filter(filter) => 1,
+            _ => ctx.args.repeat(),
+        };
+        for (test, iteration) in self
+            .tests
+            .iter()
+            .flat_map(|test| (1..=repeat).map(move |i| (test, i)))
+        {
+            if let Some(reason) = test.skip_reason() {
+                out.push_str(&format!("    // Skipped: {}\n", reason));
+            } else if test.is_skipped() {
+                out.push_str("    // Skipped\n");
+            }
             out.push_str(&format!(
                 "    testWidgets('{}', (tester) async {{\n",
-                test.name
+                FeatureTest::repeated_test_name(&test.name, iteration, repeat)
             ));
             out.push_str(&format!(
                 "      await tester.initializeTest('{}');",
                 ctx.vars.replace_var_usage(&test.state),
             ));
             if has_before_each {
-                out.push_str("      await beforeEach(tester);\n");
+                // Reported apart from the test's own failures, see `FlutterUtils::is_setup_failure`
+                out.push_str(&format!(
+                    r#"
+      try {{
+        await beforeEach(tester);
+      }} catch (e, stack) {{
+        Error.throwWithStackTrace(TestFailure('{} $e'), stack);
+      }}
+"#,
+                    SETUP_FAILURE_PREFIX
+                ));
             } else {
                 out.push('\n');
             }
             out.push('\n');
 
-            for step in &test.steps {
+            for (index, step) in test.steps.iter().enumerate() {
                 out.push_str("      //\n");
-                out.push_str(&step.to_dart_code(ctx, &self.step_definitions, &normalized_name));
+                if ctx.args.is_interactive() {
+                    out.push_str(&step.to_interactive_dart_code(
+                        ctx,
+                        &self.step_definitions,
+                        &normalized_name,
+                        &format!("{}/{}", index + 1, test.steps.len()),
+                    ));
+                } else {
+                    out.push_str(&step.to_dart_code(ctx, &self.step_definitions, &normalized_name));
+                }
                 out.push('\n');
             }
 
-            out.push_str("    });\n\n");
+            if test
+                .steps
+                .iter()
+                .any(|step| step.has_pointer_down(&self.step_definitions))
+            {
+                out.push_str("      await tester.releasePointer();\n");
+            }
+
+            // `testWidgets` only takes a flag, the reason is kept in the comment above
+            if test.is_skipped() {
+                out.push_str("    }, skip: true);\n\n");
+            } else {
+                out.push_str("    });\n\n");
+            }
         }
 
         out.push_str("  });\n}\n");
@@ -164,102 +318,218 @@ impl Step {
         file_name: &str,
     ) -> String {
         match self {
-            Step::Use { use_step } => {
+            Step::Use { use_step, with } => {
                 if let Some(steps) = step_definitions.get(use_step) {
                     let mut code = String::new();
                     for step in steps {
-                        code.push_str(&step.to_dart_code(ctx, step_definitions, file_name));
+                        // Substituted in the YAML text, so the steps are expanded in place and
+                        // the variables they declare stay visible to the next ones
+                        match step.with_args(with) {
+                            Ok(step) => {
+                                code.push_str(&step.to_dart_code(ctx, step_definitions, file_name))
+                            }
+                            Err(err) => code.push_str(&format!(
+                                "      // Failed to pass the arguments of '{}': {}\n",
+                                use_step, err
+                            )),
+                        }
                     }
                     code
                 } else {
                     format!("      // Step definition '{}' not found.\n", use_step)
                 }
             }
-            Step::Simple(SimpleStep::Settle) => "      await tester.pumpAndSettle();\n".to_owned(),
+            Step::Simple(SimpleStep::Settle) => Self::pump_and_settle(ctx, None),
+            Step::Settle { settle } => Self::pump_and_settle(ctx, settle.timeout_millis),
             Step::NotFound {
                 not_found,
                 timeout_millis,
             } => format!(
                 "      await tester.waitUntilGone({}, timeout: {});\n",
                 Self::find_by(ctx, not_found),
-                Self::duration(*timeout_millis, 10000)
+                Self::duration(
+                    *timeout_millis,
+                    ctx.config.defaults.not_found_timeout_millis
+                )
             ),
             Step::WaitFor { wait_for } => match wait_for {
                 WaitFor::Key {
                     key,
                     timeout_millis,
                     settle,
+                    settle_timeout_millis,
                 } => format!(
                     "      await tester.pumpUntilFound(find.byKey(Key('{}')), timeout: {});\n{}",
-                    ctx.vars.replace_var_usage(key),
-                    Self::duration(*timeout_millis, 5000),
-                    Self::settle(*settle),
+                    Self::dart_literal(ctx, key),
+                    Self::duration(*timeout_millis, ctx.config.defaults.wait_timeout_millis),
+                    Self::settle(ctx, *settle, *settle_timeout_millis),
                 ),
                 WaitFor::Text {
                     text,
                     timeout_millis,
                     settle,
+                    settle_timeout_millis,
                 } => format!(
-                    "      await tester.pumpUntilFound(find.text('{}'), timeout: {});\n{}",
-                    ctx.vars.replace_var_usage(text),
-                    Self::duration(*timeout_millis, 5000),
-                    Self::settle(*settle),
+                    "      await tester.pumpUntilFound({}, timeout: {});\n{}",
+                    Self::find_text(ctx, text),
+                    Self::duration(*timeout_millis, ctx.config.defaults.wait_timeout_millis),
+                    Self::settle(ctx, *settle, *settle_timeout_millis),
                 ),
-                WaitFor::Delay { delay, settle } => format!(
+                WaitFor::Delay {
+                    delay,
+                    settle,
+                    settle_timeout_millis,
+                } => format!(
                     "      await tester.pump(Duration(milliseconds: {}));\n{}",
                     delay,
-                    Self::settle(*settle),
+                    Self::settle(ctx, *settle, *settle_timeout_millis),
                 ),
                 WaitFor::Progress {
                     progress,
                     timeout_millis,
                     settle,
+                    settle_timeout_millis,
                 } => match progress {
                     feature_test::ProgressWidgetType::Linear => format!(
                         "      await tester.pumpUntilProgressCompleted(find.byType(LinearProgressIndicator), timeout: {});\n{}",
-                        Self::duration(*timeout_millis, 30000),
-                        Self::settle(*settle),
+                        Self::duration(
+                            *timeout_millis,
+                            ctx.config.defaults.progress_timeout_millis
+                        ),
+                        Self::settle(ctx, *settle, *settle_timeout_millis),
                     ),
                     feature_test::ProgressWidgetType::Radial => format!(
                         "      await tester.pumpUntilProgressCompleted(find.byType(CircularProgressIndicator), timeout: {});\n{}",
-                        Self::duration(*timeout_millis, 30000),
-                        Self::settle(*settle),
+                        Self::duration(
+                            *timeout_millis,
+                            ctx.config.defaults.progress_timeout_millis
+                        ),
+                        Self::settle(ctx, *settle, *settle_timeout_millis),
                     ),
                 },
+                WaitFor::NetworkIdle {
+                    network_idle,
+                    timeout_millis,
+                    settle,
+                    settle_timeout_millis,
+                } => {
+                    let timeout =
+                        Self::duration(*timeout_millis, ctx.config.defaults.wait_timeout_millis);
+                    let wait = match (&network_idle.helper, &network_idle.marker) {
+                        (Some(helper), _) => format!("{}(tester, {})", helper, timeout),
+                        (None, Some(marker)) => format!(
+                            "tester.waitForNetworkIdle(timeout: {}, marker: '{}')",
+                            timeout,
+                            ctx.vars.replace_var_usage(marker)
+                        ),
+                        (None, None) => format!("tester.waitForNetworkIdle(timeout: {})", timeout),
+                    };
+                    format!(
+                        "      await {};\n{}",
+                        wait,
+                        Self::settle(ctx, *settle, *settle_timeout_millis)
+                    )
+                }
             },
-            Step::Tap { tap } => match tap {
-                feature_test::TapFindBy::Coords { x, y } => format!(
-                    "      await tester.pumpAndSettle();      await tester.tapAt(Offset({}, {}));\n",
-                    x, y
+            Step::Tap {
+                tap,
+                settle_timeout_millis,
+            } => {
+                let settle = Self::pump_and_settle(ctx, *settle_timeout_millis);
+                match tap {
+                    feature_test::TapFindBy::Coords { x, y } => {
+                        format!("{settle}      await tester.tapAt(Offset({}, {}));\n", x, y)
+                    }
+                    _ => format!(
+                        "{settle}      await tester.tap({}, kind: PointerDeviceKind.mouse);\n{settle}",
+                        Self::find_by(ctx, &tap.to_find_by().expect("Should convert to find_by"))
+                    ),
+                }
+            }
+            Step::Type { r#type } => {
+                let mut modifiers = String::new();
+                if r#type.append {
+                    modifiers.push_str(", append: true");
+                }
+                if r#type.submit {
+                    modifiers.push_str(", submit: true");
+                }
+                format!(
+                    "      await tester.type({}, '{}'{});\n",
+                    Self::find_by(ctx, &r#type.by),
+                    Self::dart_literal(ctx, &r#type.value),
+                    modifiers
+                )
+            }
+            Step::Key { key } => match LOGICAL_KEYS.iter().find(|k| k.eq_ignore_ascii_case(key)) {
+                Some(key) => format!(
+                    "      await tester.sendKeyEvent(LogicalKeyboardKey.{});\n{}",
+                    key,
+                    Self::pump_and_settle(ctx, None)
                 ),
-                _ => format!(
-                    "      await tester.pumpAndSettle();\n      await tester.tap({}, kind: PointerDeviceKind.mouse);\n      await tester.pumpAndSettle();\n",
-                    Self::find_by(ctx, &tap.to_find_by().expect("Should convert to find_by"))
+                None => format!(
+                    "      // Unknown key '{}', expected one of: {}\n",
+                    key,
+                    LOGICAL_KEYS.join(", ")
                 ),
             },
-            Step::Type { r#type } => format!(
-                "      await tester.type({}, '{}');\n",
-                Self::find_by(ctx, &r#type.by),
-                ctx.vars.replace_var_usage(&r#type.value)
+            Step::Clear { clear } => format!(
+                "      await tester.clearTextField({});\n",
+                Self::find_by(ctx, clear)
             ),
             Step::Match { r#match } => match &r#match.target {
                 feature_test::MatchTarget::Key { key } => {
                     format!(
                         "      expect(find.byKey(Key('{}')), findsOneWidget);\n",
-                        ctx.vars.replace_var_usage(key)
+                        Self::dart_literal(ctx, key)
                     )
                 }
                 feature_test::MatchTarget::Text { text } => {
                     format!(
                         "      expect(find.text('{}'), findsOneWidget);\n",
-                        ctx.vars.replace_var_usage(text)
+                        Self::dart_literal(ctx, text)
+                    )
+                }
+                feature_test::MatchTarget::TextContains { contains } => {
+                    format!(
+                        "      expect(find.textContaining('{}'), findsOneWidget);\n",
+                        Self::dart_literal(ctx, contains)
+                    )
+                }
+                feature_test::MatchTarget::Icon { icon } => {
+                    format!(
+                        "      expect(find.byIcon(Icons.{}), findsOneWidget);\n",
+                        icon
+                    )
+                }
+                feature_test::MatchTarget::Tooltip { tooltip } => {
+                    format!(
+                        "      expect(find.byTooltip('{}'), findsOneWidget);\n",
+                        Self::dart_literal(ctx, tooltip)
+                    )
+                }
+                feature_test::MatchTarget::Count { by, count } => {
+                    let matcher = match count {
+                        0 => "findsNothing".to_owned(),
+                        count => format!("findsNWidgets({})", count),
+                    };
+                    format!("      expect({}, {});\n", Self::find_by(ctx, by), matcher)
+                }
+                feature_test::MatchTarget::NotExists { not_exists } => {
+                    format!(
+                        "      expect({}, findsNothing);\n",
+                        Self::find_by(ctx, not_exists)
                     )
                 }
-                feature_test::MatchTarget::Screenshot { screenshot } => {
+                feature_test::MatchTarget::Screenshot {
+                    screenshot,
+                    threshold,
+                } => {
                     format!(
-                        "      await tester.compareScreenshot('{}', '{}');\n",
+                        "      await tester.compareScreenshot('{}', '{}'{});\n",
                         file_name,
-                        ctx.vars.replace_var_usage(screenshot)
+                        ctx.vars.replace_var_usage(screenshot),
+                        Self::screenshot_threshold(*threshold)
                     )
                 }
             },
@@ -267,35 +537,204 @@ impl Step {
                 feature_test::MatchTarget::Key { key } => {
                     format!(
                         "      expect(find.byKey(Key('{}')), findsNothing);\n",
-                        ctx.vars.replace_var_usage(key)
+                        Self::dart_literal(ctx, key)
                     )
                 }
                 feature_test::MatchTarget::Text { text } => {
                     format!(
                         "      expect(find.text('{}'), findsNothing);\n",
-                        ctx.vars.replace_var_usage(text)
+                        Self::dart_literal(ctx, text)
                     )
                 }
-                feature_test::MatchTarget::Screenshot { screenshot } => {
+                feature_test::MatchTarget::TextContains { contains } => {
+                    format!(
+                        "      expect(find.textContaining('{}'), findsNothing);\n",
+                        Self::dart_literal(ctx, contains)
+                    )
+                }
+                feature_test::MatchTarget::Icon { icon } => {
+                    format!("      expect(find.byIcon(Icons.{}), findsNothing);\n", icon)
+                }
+                feature_test::MatchTarget::Tooltip { tooltip } => {
+                    format!(
+                        "      expect(find.byTooltip('{}'), findsNothing);\n",
+                        Self::dart_literal(ctx, tooltip)
+                    )
+                }
+                feature_test::MatchTarget::Count { by, count } => {
+                    let matcher = match count {
+                        0 => "findsWidgets".to_owned(),
+                        count => format!("isNot(findsNWidgets({}))", count),
+                    };
+                    format!("      expect({}, {});\n", Self::find_by(ctx, by), matcher)
+                }
+                feature_test::MatchTarget::NotExists { not_exists } => {
                     format!(
-                        "      await tester.compareScreenshot('{}', '{}', negate: true);\n",
+                        "      expect({}, findsWidgets);\n",
+                        Self::find_by(ctx, not_exists)
+                    )
+                }
+                feature_test::MatchTarget::Screenshot {
+                    screenshot,
+                    threshold,
+                } => {
+                    format!(
+                        "      await tester.compareScreenshot('{}', '{}', negate: true{});\n",
                         file_name,
-                        ctx.vars.replace_var_usage(screenshot)
+                        ctx.vars.replace_var_usage(screenshot),
+                        Self::screenshot_threshold(*threshold)
                     )
                 }
             },
+            Step::MatchProperty { match_property } => format!(
+                "      tester.assertProperty({}, '{}', '{}');\n",
+                Self::find_by(ctx, &match_property.by),
+                match_property.property.as_str(),
+                Self::dart_literal(ctx, &match_property.expected)
+            ),
+            Step::MatchScroll { match_scroll } => format!(
+                "      tester.assertScrollOffset({}, '{}', {:?});\n",
+                Self::find_by(ctx, &match_scroll.of),
+                match_scroll.axis.as_str(),
+                match_scroll.at_least
+            ),
             Step::Scroll { scroll } => format!(
                 "      await tester.drag({}, const Offset({}, {}));\n",
                 Self::find_by(ctx, &scroll.by),
                 scroll.delta.x,
                 scroll.delta.y
             ),
+            Step::DragTo { drag_to } => format!(
+                "      await tester.dragBetween({}, {}, hold: const {});\n",
+                Self::find_by(ctx, &drag_to.from),
+                Self::find_by(ctx, &drag_to.to),
+                Self::duration(drag_to.hold_millis, 0)
+            ),
+            Step::ScrollUntilVisible {
+                scroll_until_visible,
+            } => {
+                // Flutter reads the axis from a `Scrollable` widget, while the finder usually
+                // matches a list view around it. Pages often hold several, take the first one
+                let scrollable = match &scroll_until_visible.scrollable {
+                    Some(scrollable) => format!(
+                        "find.descendant(of: {}, matching: find.byType(Scrollable), matchRoot: true).first",
+                        Self::find_by(ctx, scrollable)
+                    ),
+                    None => "find.byType(Scrollable).first".to_owned(),
+                };
+                format!(
+                    "      await tester.scrollUntilVisible({}, {:?}, scrollable: {});\n",
+                    Self::find_by(ctx, &scroll_until_visible.target),
+                    scroll_until_visible.delta.unwrap_or(100.0),
+                    scrollable
+                )
+            }
+            Step::Swipe { swipe } => format!(
+                "      await tester.swipe({}, '{}', {:?});\n",
+                Self::find_by(ctx, &swipe.by),
+                swipe.direction.as_str(),
+                swipe.amount.unwrap_or(0.5)
+            ),
             Step::Pointer { pointer } => match pointer {
                 feature_test::PointerAction::Move { to, remove } => format!(
                     "      await tester.movePointer(Offset({}, {}), remove: {});\n",
                     to.x, to.y, remove,
                 ),
+                feature_test::PointerAction::Down { down } => format!(
+                    "      await tester.pointerDown(Offset({}, {}));\n",
+                    down.x, down.y,
+                ),
+                feature_test::PointerAction::Up { .. } => {
+                    "      await tester.pointerUp();\n".to_owned()
+                }
             },
+            Step::DoubleTap { double_tap } => {
+                let settle = Self::pump_and_settle(ctx, None);
+                let finder = Self::find_by(ctx, double_tap);
+                // The second tap must come within `kDoubleTapTimeout` (300ms) of the first
+                format!(
+                    "{settle}      await tester.tap({finder}, kind: PointerDeviceKind.mouse);\n      await tester.pump(const Duration(milliseconds: 50));\n      await tester.tap({finder}, kind: PointerDeviceKind.mouse);\n{settle}"
+                )
+            }
+            Step::LongPress {
+                long_press,
+                hold_millis,
+            } => {
+                let settle = Self::pump_and_settle(ctx, None);
+                format!(
+                    "{settle}      await tester.longPressFor({}, const {});\n",
+                    Self::find_by(ctx, long_press),
+                    Self::duration(*hold_millis, 500)
+                )
+            }
+            Step::Hover { hover } => format!(
+                "      await tester.movePointer(tester.getCenter({}));\n",
+                Self::find_by(ctx, hover),
+            ),
+            Step::If {
+                if_present: exists,
+                then,
+                r#else,
+            }
+            | Step::IfExists {
+                r#if:
+                    IfStep {
+                        exists,
+                        then,
+                        r#else,
+                    },
+            } => {
+                let mut code = Self::pump_and_settle(ctx, None);
+                code.push_str(&format!(
+                    "      if (tester.any({})) {{\n",
+                    Self::find_by(ctx, exists)
+                ));
+                code.push_str(&Self::nested(ctx, then, step_definitions, file_name));
+                if let Some(r#else) = r#else {
+                    code.push_str("      } else {\n");
+                    code.push_str(&Self::nested(ctx, r#else, step_definitions, file_name));
+                }
+                code.push_str("      }\n");
+                code
+            }
+            // A loop that never runs would only hide the nested steps from the reader
+            Step::Repeat { repeat } if repeat.times == 0 => {
+                format!(
+                    "      // Skipped repeat of {} step(s) run 0 times\n",
+                    repeat.steps.len()
+                )
+            }
+            Step::Repeat { repeat } => {
+                let mut code = format!(
+                    "      for (var {0} = 0; {0} < {1}; {0}++) {{\n",
+                    LOOP_INDEX_VAR, repeat.times
+                );
+                code.push_str(&Self::nested(
+                    ctx,
+                    &repeat.steps,
+                    step_definitions,
+                    file_name,
+                ));
+                code.push_str("      }\n");
+                code
+            }
+            Step::Capture { capture } => format!(
+                "      await tester.captureScreenshot('{}');\n",
+                ctx.vars.replace_var_usage(capture)
+            ),
+            Step::Pinch { pinch } => {
+                let center = pinch
+                    .center
+                    .as_ref()
+                    .map(|c| format!(", offset: const Offset({}, {})", c.x, c.y))
+                    .unwrap_or_default();
+                format!(
+                    "      await tester.pinch({}, {:?}{});\n",
+                    Self::find_by(ctx, &pinch.by),
+                    pinch.scale,
+                    center
+                )
+            }
             Step::UserInput { user_input } => format!(
                 "      String {} = await tester.waitForInputViaDBus('{}');\n",
                 ctx.vars.replace_var_usage(&user_input.name),
@@ -304,35 +743,1024 @@ impl Step {
         }
     }
 
+    /// Generates code pausing before the step in interactive mode, running it unless skipped.
+    fn to_interactive_dart_code(
+        &self,
+        ctx: &HookContext<'_, GenState>,
+        step_definitions: &HashMap<String, Vec<Step>>,
+        file_name: &str,
+        position: &str,
+    ) -> String {
+        // Parentheses would end the DBus input name early, quotes and `$` the Dart string
+        let description = self
+            .label()
+            .chars()
+            .filter(|c| !matches!(c, '(' | ')' | '\'' | '$' | '\\' | '\n'))
+            .take(100)
+            .collect::<String>();
+        let label = format!("{} {}", position, description);
+
+        // User inputs declare a variable used by the next steps, so they can't be skipped
+        if self.declares_input(step_definitions) {
+            return format!(
+                "      await tester.pauseBeforeStep('{}', skippable: false);\n{}",
+                label,
+                self.to_dart_code(ctx, step_definitions, file_name)
+            );
+        }
+
+        format!(
+            "      if (await tester.pauseBeforeStep('{}')) {{\n{}      }}\n",
+            label,
+            Self::nested(ctx, std::slice::from_ref(self), step_definitions, file_name)
+        )
+    }
+
+    /// Generates code for steps running outside of a widget test (`setUpAll`/`tearDownAll`),
+    /// where no `tester` is available.
+    pub fn to_dart_code_without_tester(
+        &self,
+        step_definitions: &HashMap<String, Vec<Step>>,
+    ) -> ResultWithError<String> {
+        match self {
+            Step::Use { use_step, .. } => {
+                let steps = step_definitions
+                    .get(use_step)
+                    .ok_or_else(|| format!("Step definition '{}' not found", use_step))?;
+                let mut code = String::new();
+                for step in steps {
+                    code.push_str(&step.to_dart_code_without_tester(step_definitions)?);
+                }
+                Ok(code)
+            }
+            Step::WaitFor {
+                wait_for:
+                    WaitFor::Delay {
+                        delay,
+                        settle: None | Some(false),
+                        ..
+                    },
+            } => Ok(format!(
+                "      await Future.delayed(const Duration(milliseconds: {}));\n",
+                delay
+            )),
+            _ => Err(format!(
+                "Step '{}' requires a widget tester and is not allowed in before_all/after_all",
+                self.label()
+            )
+            .into()),
+        }
+    }
+
+    /// Generates the code of steps nested in a block, indented one level deeper.
+    fn nested(
+        ctx: &HookContext<'_, GenState>,
+        steps: &[Step],
+        step_definitions: &HashMap<String, Vec<Step>>,
+        file_name: &str,
+    ) -> String {
+        steps
+            .iter()
+            .map(|step| step.to_dart_code(ctx, step_definitions, file_name))
+            .collect::<String>()
+            .lines()
+            .map(|line| {
+                if line.is_empty() {
+                    "\n".to_owned()
+                } else {
+                    format!("  {}\n", line)
+                }
+            })
+            .collect()
+    }
+
     fn find_by(ctx: &HookContext<'_, GenState>, by: &feature_test::FindBy) -> String {
         match by {
             feature_test::FindBy::Key { key } => {
-                format!("find.byKey(Key('{}'))", ctx.vars.replace_var_usage(key))
-            }
-            feature_test::FindBy::Text { text } => {
-                format!("find.text('{}')", ctx.vars.replace_var_usage(text))
+                format!("find.byKey(Key('{}'))", Self::dart_literal(ctx, key))
             }
+            feature_test::FindBy::Text { text } => Self::find_text(ctx, text),
             feature_test::FindBy::Placeholder { placeholder } => {
                 format!(
                     "find.byPlaceholder('{}')",
-                    ctx.vars.replace_var_usage(placeholder)
+                    Self::dart_literal(ctx, placeholder)
                 )
             }
             feature_test::FindBy::Type { r#type } => {
                 format!("find.byType({})", ctx.vars.replace_var_usage(r#type))
             }
+            // Material icon names are Dart identifiers, passed through unchanged
+            feature_test::FindBy::Icon { icon } => format!("find.byIcon(Icons.{})", icon),
+            feature_test::FindBy::Tooltip { tooltip } => {
+                format!("find.byTooltip('{}')", Self::dart_literal(ctx, tooltip))
+            }
+            feature_test::FindBy::Descendant { matching, of } => format!(
+                "find.descendant(of: {}, matching: {})",
+                Self::find_by(ctx, of),
+                Self::find_by(ctx, matching)
+            ),
+            feature_test::FindBy::Ancestor { matching, of } => format!(
+                "find.ancestor(of: {}, matching: {})",
+                Self::find_by(ctx, of),
+                Self::find_by(ctx, matching)
+            ),
+            feature_test::FindBy::Nth { index, of } => {
+                format!("{}.at({})", Self::find_by(ctx, of), index)
+            }
         }
     }
 
+    /// Screenshot diff threshold argument, the step's own or the default one.
+    fn screenshot_threshold(threshold: Option<f64>) -> String {
+        format!(
+            ", threshold: {:?}",
+            threshold.unwrap_or(DEFAULT_SCREENSHOT_THRESHOLD)
+        )
+    }
+
+    /// Finds a text, or text matching a regex when wrapped in slashes, e.g. `/Loading \d+%/`.
+    fn find_text(ctx: &HookContext<'_, GenState>, text: &str) -> String {
+        match text
+            .strip_prefix('/')
+            .and_then(|text| text.strip_suffix('/'))
+        {
+            Some(pattern) if !pattern.is_empty() => {
+                // Regex escapes and anchors must reach the RegExp as written
+                format!("find.textMatching('{}')", Self::dart_literal(ctx, pattern))
+            }
+            _ => format!("find.text('{}')", Self::dart_literal(ctx, text)),
+        }
+    }
+
+    /// Escapes a value for a single-quoted Dart string, keeping the vars interpolated,
+    /// e.g. so the `$` of `Total: $42` isn't read as an interpolation but `${Common.key}` is.
+    fn dart_literal(ctx: &HookContext<'_, GenState>, value: &str) -> String {
+        let escaped = value.replace('\\', "\\\\").replace('\'', "\\'");
+        let escaped =
+            DART_DOLLAR_RE.replace_all(&escaped, |caps: &regex::Captures| match &caps[0] {
+                "$" => "\\$".to_owned(),
+                var => var.to_owned(),
+            });
+        ctx.vars.replace_var_usage(&escaped)
+    }
+
     fn duration(duration: Option<u32>, default_ms: u32) -> String {
         format!("Duration(milliseconds: {})", duration.unwrap_or(default_ms))
     }
 
-    fn settle(settle: bool) -> String {
-        if settle {
-            "      await tester.pumpAndSettle();\n".to_owned()
+    fn settle(
+        ctx: &HookContext<'_, GenState>,
+        settle: Option<bool>,
+        timeout_millis: Option<u32>,
+    ) -> String {
+        if settle.unwrap_or(ctx.config.defaults.settle) {
+            Self::pump_and_settle(ctx, timeout_millis)
         } else {
             "".to_owned()
         }
     }
+
+    /// Without a timeout, `pumpAndSettle` waits up to 10 minutes for never-ending animations.
+    /// The timeout is its third positional argument, the first one being the pump interval.
+    fn pump_and_settle(ctx: &HookContext<'_, GenState>, timeout_millis: Option<u32>) -> String {
+        match timeout_millis.or(ctx.config.defaults.settle_timeout_millis) {
+            Some(timeout) => format!(
+                "      await tester.pumpAndSettle(const Duration(milliseconds: 100), EnginePhase.sendSemanticsUpdate, const Duration(milliseconds: {}));\n",
+                timeout
+            ),
+            None => "      await tester.pumpAndSettle();\n".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use clap::Parser as _;
+
+    use super::*;
+    use crate::models::vars::Vars;
+
+    /// Runs `f` with a generation context of default args, config and no vars files.
+    fn with_ctx<R>(f: impl FnOnce(&HookContext<'_, GenState>) -> R) -> R {
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        };
+        with_config_ctx("project_type: flutter", &vars, f)
+    }
+
+    fn with_config_ctx<R>(
+        config: &str,
+        vars: &Vars,
+        f: impl FnOnce(&HookContext<'_, GenState>) -> R,
+    ) -> R {
+        let args = AppArgs::parse_from(["playmaster", "gen"]);
+        let config: Config = serde_yaml::from_str(config).expect("Should parse config");
+        let ctx = HookContext {
+            args: &args,
+            config: &config,
+            vars,
+            state: Arc::new(RwLock::new(GenState::default())),
+        };
+        f(&ctx)
+    }
+
+    /// Dart code of the steps, given as a YAML list, with the given step definitions.
+    fn steps_code_with_defs(steps: &str, definitions: &str) -> String {
+        let steps: Vec<Step> = serde_yaml::from_str(steps).expect("Should parse steps");
+        let definitions: HashMap<String, Vec<Step>> =
+            serde_yaml::from_str(definitions).expect("Should parse step definitions");
+        with_ctx(|ctx| {
+            steps
+                .iter()
+                .map(|step| step.to_dart_code(ctx, &definitions, "feature_test"))
+                .collect()
+        })
+    }
+
+    fn steps_code(steps: &str) -> String {
+        steps_code_with_defs(steps, "{}")
+    }
+
+    /// Generated Dart file of the feature, given as YAML.
+    fn feature_code(feature: &str) -> String {
+        let feature: FeatureTest = serde_yaml::from_str(feature).expect("Should parse feature");
+        let out_dir = tempfile::tempdir().expect("Should create temp dir");
+        with_ctx(|ctx| feature.generate_dart(ctx, out_dir.path()))
+            .expect("Should generate feature");
+        fs::read_to_string(out_dir.path().join(feature.dart_file_name()))
+            .expect("Should read generated feature")
+    }
+
+    #[test]
+    fn interactive_steps_pause_with_a_readable_label() {
+        let definitions: HashMap<String, Vec<Step>> =
+            serde_yaml::from_str("ask_code:\n  - user_input: { name: code }\n").unwrap();
+        let interactive = |yaml: &str| {
+            let step: Step = serde_yaml::from_str(yaml).expect("Should parse step");
+            with_ctx(|ctx| step.to_interactive_dart_code(ctx, &definitions, "feature_test", "2/5"))
+        };
+
+        let tap = interactive("tap: { text: Log in }");
+        assert!(
+            tap.starts_with("      if (await tester.pauseBeforeStep('2/5 tap text: Log in')) {\n"),
+            "{tap}"
+        );
+
+        let nested_input = interactive("if_present: { key: mfa }\nthen:\n  - use_step: ask_code\n");
+        assert!(
+            nested_input.starts_with(
+                "      await tester.pauseBeforeStep('2/5 if_present key: mfa', skippable: false);\n"
+            ),
+            "{nested_input}"
+        );
+    }
+
+    #[test]
+    fn repeat_wraps_its_steps_in_a_loop() {
+        let code = steps_code_with_defs(
+            r#"
+- repeat:
+    times: 3
+    steps:
+      - tap: { text: "Load more" }
+      - use_step: check_item
+"#,
+            "check_item:\n  - wait_for: { key: \"item_{{ loop.index }}\" }\n",
+        );
+
+        assert_eq!(
+            code,
+            "      for (var loopIndex = 0; loopIndex < 3; loopIndex++) {\n\
+             \x20       await tester.pumpAndSettle();\n\
+             \x20       await tester.tap(find.text('Load more'), kind: PointerDeviceKind.mouse);\n\
+             \x20       await tester.pumpAndSettle();\n\
+             \x20       await tester.pumpUntilFound(find.byKey(Key('item_${loopIndex}')), timeout: Duration(milliseconds: 5000));\n\
+             \x20     }\n"
+        );
+    }
+
+    #[test]
+    fn repeat_accepts_count_and_leaves_out_zero_runs() {
+        let with_count = steps_code("- repeat:\n    count: 2\n    steps: [settle]\n");
+        assert!(
+            with_count.starts_with("      for (var loopIndex = 0; loopIndex < 2; loopIndex++) {\n")
+        );
+
+        let never = steps_code(
+            "- repeat:\n    times: 0\n    steps:\n      - tap: { text: \"Load more\" }\n      - settle\n",
+        );
+        assert_eq!(never, "      // Skipped repeat of 2 step(s) run 0 times\n");
+    }
+
+    #[test]
+    fn skipped_tests_are_generated_with_the_skip_flag() {
+        let code = feature_code(
+            r#"
+name: Login
+tests:
+  - name: With reason
+    skip: "Flaky"
+    steps: []
+  - name: Flagged
+    skip: true
+    steps: []
+  - name: Not skipped
+    skip: false
+    steps: []
+  - name: Focused
+    skip: "Flaky"
+    only: true
+    steps: []
+"#,
+        );
+
+        assert!(code.contains("    // Skipped: Flaky\n    testWidgets('With reason'"));
+        assert!(code.contains("    // Skipped\n    testWidgets('Flagged'"));
+        assert_eq!(code.matches("}, skip: true);").count(), 2);
+        assert!(!code.contains("// Skipped: false"));
+        assert!(code.contains("testWidgets('Not skipped'"));
+        assert!(code.contains("testWidgets('Focused'"));
+    }
+
+    const LOGIN_DEFINITION: &str = r#"
+login:
+  - tap: { text: "Sign In" }
+"#;
+
+    #[test]
+    fn if_present_guards_the_branches_behind_the_widget_check() {
+        let code = steps_code_with_defs(
+            r#"
+- if_present: { text: "Accept cookies" }
+  then:
+    - use_step: login
+  else:
+    - use_step: login
+"#,
+            LOGIN_DEFINITION,
+        );
+
+        assert_eq!(
+            code,
+            "      await tester.pumpAndSettle();\n\
+             \x20     if (tester.any(find.text('Accept cookies'))) {\n\
+             \x20       await tester.pumpAndSettle();\n\
+             \x20       await tester.tap(find.text('Sign In'), kind: PointerDeviceKind.mouse);\n\
+             \x20       await tester.pumpAndSettle();\n\
+             \x20     } else {\n\
+             \x20       await tester.pumpAndSettle();\n\
+             \x20       await tester.tap(find.text('Sign In'), kind: PointerDeviceKind.mouse);\n\
+             \x20       await tester.pumpAndSettle();\n\
+             \x20     }\n"
+        );
+    }
+
+    #[test]
+    fn if_exists_generates_the_same_code_as_if_present() {
+        let if_exists = steps_code_with_defs(
+            r#"
+- if:
+    exists: { key: "cookie_banner" }
+    then:
+      - use_step: login
+"#,
+            LOGIN_DEFINITION,
+        );
+        let if_present = steps_code_with_defs(
+            r#"
+- if_present: { key: "cookie_banner" }
+  then:
+    - use_step: login
+"#,
+            LOGIN_DEFINITION,
+        );
+
+        assert!(if_exists.contains("      if (tester.any(find.byKey(Key('cookie_banner')))) {\n"));
+        assert!(!if_exists.contains("} else {"));
+        assert_eq!(if_exists, if_present);
+    }
+
+    #[test]
+    fn use_step_arguments_are_substituted_in_the_definition() {
+        let code = steps_code_with_defs(
+            r#"
+- use_step: login
+  with:
+    email: "admin@example.com"
+    password: "{{vars.common.adminPassword}}"
+"#,
+            r#"
+login:
+  - type:
+      by: { placeholder: "Email" }
+      value: "{{ email }}"
+  - type:
+      by: { placeholder: "Password" }
+      value: "{{password}}"
+  - match:
+      text: "Welcome {{ email }}, {{ name }}"
+"#,
+        );
+
+        assert_eq!(
+            code,
+            "      await tester.type(find.byPlaceholder('Email'), 'admin@example.com');\n\
+             \x20     await tester.type(find.byPlaceholder('Password'), '${Common.adminPassword}');\n\
+             \x20     expect(find.text('Welcome admin@example.com, ${name}'), findsOneWidget);\n"
+        );
+    }
+
+    #[test]
+    fn use_step_arguments_keep_declared_inputs_in_scope() {
+        let code = steps_code_with_defs(
+            r#"
+- use_step: read_code
+  with:
+    label: "MFA"
+- type:
+    by: { placeholder: "Code" }
+    value: "{{ code }}"
+"#,
+            r#"
+read_code:
+  - user_input:
+      name: "code"
+  - match:
+      text: "{{ label }}"
+"#,
+        );
+
+        assert!(!code.contains("{\n"), "{}", code);
+        assert!(code.contains("expect(find.text('MFA'), findsOneWidget);"));
+    }
+
+    #[test]
+    fn use_step_arguments_keep_every_step_kind() {
+        let steps = r#"
+- wait_for: { key: "home", timeout_millis: 5s, settle: false }
+- wait_for: { delay: 250ms }
+- wait_for: { network_idle: { marker: "busy" } }
+- not_found: { text: "Loading" }
+- tap: { text: "Sign In" }
+- tap: { x: 10, y: 20 }
+- tap: { index: 1, of: { type: "ElevatedButton" } }
+- double_tap: { key: "row" }
+- long_press: { tooltip: "More" }
+  hold_millis: 800
+- type: { by: { placeholder: "Email" }, value: "a@b.c", append: true }
+- key: escape
+- clear: { key: "email" }
+- match: { text: "Welcome" }
+- match: { by: { type: "Card" }, count: 3 }
+- match: { screenshot: "home", threshold: 0.01 }
+- not_match: { not_exists: { icon: "close" } }
+- match_property: { by: { key: "submit" }, property: enabled, expected: "true" }
+- scroll: { by: { type: "ListView" }, delta: { x: 0, y: -300 } }
+- drag_to: { from: { key: "a" }, to: { key: "b" } }
+- scroll_until_visible: { target: { text: "Item 40" } }
+- swipe: { by: { key: "card" }, direction: left }
+- pinch: { by: { key: "map" }, scale: 2.0 }
+- hover: { text: "Help" }
+- capture: "home"
+- if_present: { text: "Accept cookies" }
+  then: [settle]
+- if: { exists: { text: "Banner" }, then: [settle], else: [settle] }
+- repeat: { times: 2, steps: [{ tap: { text: "Add {{ loop.index }}" } }] }
+- settle: { timeout_millis: 2s }
+- settle
+"#;
+        let direct = steps_code(steps);
+        let definitions = format!("all:{}", steps.replace("\n", "\n  "));
+        let with_args = steps_code_with_defs(
+            "- use_step: all\n  with: { unused: \"value\" }\n",
+            &definitions,
+        );
+
+        assert_eq!(with_args, direct);
+    }
+
+    #[test]
+    fn match_count_asserts_the_number_of_widgets() {
+        assert_eq!(
+            steps_code("- match: { count: 0, by: { key: \"cart_row\" } }"),
+            "      expect(find.byKey(Key('cart_row')), findsNothing);\n"
+        );
+        assert_eq!(
+            steps_code("- match: { count: 1, by: { text: \"Total\" } }"),
+            "      expect(find.text('Total'), findsNWidgets(1));\n"
+        );
+        assert_eq!(
+            steps_code("- match: { count: 3, by: { type: \"ListTile\" } }"),
+            "      expect(find.byType(ListTile), findsNWidgets(3));\n"
+        );
+    }
+
+    #[test]
+    fn not_match_count_negates_the_number_of_widgets() {
+        assert_eq!(
+            steps_code("- not_match: { count: 0, by: { key: \"cart_row\" } }"),
+            "      expect(find.byKey(Key('cart_row')), findsWidgets);\n"
+        );
+        assert_eq!(
+            steps_code("- not_match: { count: 3, by: { key: \"cart_row\" } }"),
+            "      expect(find.byKey(Key('cart_row')), isNot(findsNWidgets(3)));\n"
+        );
+    }
+
+    #[test]
+    fn nth_finder_selects_the_match_at_the_index() {
+        assert_eq!(
+            steps_code("- match: { count: 1, by: { index: 0, of: { type: \"ElevatedButton\" } } }"),
+            "      expect(find.byType(ElevatedButton).at(0), findsNWidgets(1));\n"
+        );
+    }
+
+    #[test]
+    fn nth_finder_wraps_nested_finders() {
+        let code = steps_code(
+            r#"
+- tap:
+    index: 2
+    of:
+      matching: { text: "Add" }
+      descendant_of: { key: "cart_item_{{ loop.index }}" }
+"#,
+        );
+
+        assert_eq!(
+            code,
+            "      await tester.pumpAndSettle();\n\
+             \x20     await tester.tap(find.descendant(of: find.byKey(Key('cart_item_${loopIndex}')), matching: find.text('Add')).at(2), kind: PointerDeviceKind.mouse);\n\
+             \x20     await tester.pumpAndSettle();\n"
+        );
+    }
+
+    #[test]
+    fn tooltip_finder_is_found_by_tooltip() {
+        let steps: Vec<Step> = serde_yaml::from_str("- tap: { tooltip: \"Delete\" }").unwrap();
+        assert!(
+            matches!(&steps[0], Step::Tap { tap: feature_test::TapFindBy::Tooltip { tooltip }, .. } if tooltip == "Delete"),
+            "{:?}",
+            steps[0]
+        );
+
+        assert_eq!(
+            steps_code("- tap: { tooltip: \"Delete\" }"),
+            "      await tester.pumpAndSettle();\n\
+             \x20     await tester.tap(find.byTooltip('Delete'), kind: PointerDeviceKind.mouse);\n\
+             \x20     await tester.pumpAndSettle();\n"
+        );
+        assert_eq!(
+            steps_code("- match: { tooltip: \"Delete {{ loop.index }}\" }"),
+            "      expect(find.byTooltip('Delete ${loopIndex}'), findsOneWidget);\n"
+        );
+        assert_eq!(
+            steps_code("- not_found: { tooltip: \"Delete\" }"),
+            "      await tester.waitUntilGone(find.byTooltip('Delete'), timeout: Duration(milliseconds: 10000));\n"
+        );
+    }
+
+    #[test]
+    fn icon_finder_is_found_by_material_icon() {
+        assert_eq!(
+            steps_code("- tap: { icon: close }"),
+            "      await tester.pumpAndSettle();\n\
+             \x20     await tester.tap(find.byIcon(Icons.close), kind: PointerDeviceKind.mouse);\n\
+             \x20     await tester.pumpAndSettle();\n"
+        );
+        assert_eq!(
+            steps_code("- match: { icon: close }"),
+            "      expect(find.byIcon(Icons.close), findsOneWidget);\n"
+        );
+        assert_eq!(
+            steps_code("- match: { count: 2, by: { icon: star_outline } }"),
+            "      expect(find.byIcon(Icons.star_outline), findsNWidgets(2));\n"
+        );
+    }
+
+    #[test]
+    fn not_exists_asserts_the_widget_is_absent_right_away() {
+        let code = steps_code(
+            r#"
+- match: { not_exists: { key: "spinner" } }
+- match: { not_exists: { text: "Logged in as {{ loop.index }}" } }
+- match: { not_exists: { icon: logout } }
+- match: { not_exists: { placeholder: "Password" } }
+- not_match: { not_exists: { key: "spinner" } }
+"#,
+        );
+
+        assert_eq!(
+            code,
+            "      expect(find.byKey(Key('spinner')), findsNothing);\n\
+             \x20     expect(find.text('Logged in as ${loopIndex}'), findsNothing);\n\
+             \x20     expect(find.byIcon(Icons.logout), findsNothing);\n\
+             \x20     expect(find.byPlaceholder('Password'), findsNothing);\n\
+             \x20     expect(find.byKey(Key('spinner')), findsWidgets);\n"
+        );
+    }
+
+    #[test]
+    fn screenshot_threshold_is_passed_to_the_comparison() {
+        assert_eq!(
+            steps_code("- match: { screenshot: \"home\", threshold: 0.01 }"),
+            "      await tester.compareScreenshot('feature_test', 'home', threshold: 0.01);\n"
+        );
+        assert_eq!(
+            steps_code("- not_match: { screenshot: \"home\", threshold: 0.01 }"),
+            "      await tester.compareScreenshot('feature_test', 'home', negate: true, threshold: 0.01);\n"
+        );
+    }
+
+    #[test]
+    fn screenshot_without_threshold_passes_the_default() {
+        assert_eq!(
+            steps_code("- match: { screenshot: \"home\" }"),
+            "      await tester.compareScreenshot('feature_test', 'home', threshold: 0.003);\n"
+        );
+        assert_eq!(
+            steps_code("- not_match: { screenshot: \"home\" }"),
+            "      await tester.compareScreenshot('feature_test', 'home', negate: true, threshold: 0.003);\n"
+        );
+    }
+
+    #[test]
+    fn double_tap_taps_twice_in_quick_succession() {
+        assert_eq!(
+            steps_code("- double_tap: { key: \"row_{{ loop.index }}\" }"),
+            "      await tester.pumpAndSettle();\n\
+             \x20     await tester.tap(find.byKey(Key('row_${loopIndex}')), kind: PointerDeviceKind.mouse);\n\
+             \x20     await tester.pump(const Duration(milliseconds: 50));\n\
+             \x20     await tester.tap(find.byKey(Key('row_${loopIndex}')), kind: PointerDeviceKind.mouse);\n\
+             \x20     await tester.pumpAndSettle();\n"
+        );
+    }
+
+    #[test]
+    fn long_press_holds_for_the_given_duration() {
+        assert_eq!(
+            steps_code("- long_press: { text: \"Item {{ loop.index }}\" }"),
+            "      await tester.pumpAndSettle();\n\
+             \x20     await tester.longPressFor(find.text('Item ${loopIndex}'), const Duration(milliseconds: 500));\n"
+        );
+        assert_eq!(
+            steps_code("- long_press: { key: \"item\" }\n  hold_millis: 1s"),
+            "      await tester.pumpAndSettle();\n\
+             \x20     await tester.longPressFor(find.byKey(Key('item')), const Duration(milliseconds: 1000));\n"
+        );
+    }
+
+    #[test]
+    fn swipe_moves_by_a_fraction_of_the_widget() {
+        assert_eq!(
+            steps_code("- swipe: { by: { key: \"card\" }, direction: left }"),
+            "      await tester.swipe(find.byKey(Key('card')), 'left', 0.5);\n"
+        );
+        assert_eq!(
+            steps_code("- swipe: { by: { type: \"ListView\" }, direction: up, amount: 0.8 }"),
+            "      await tester.swipe(find.byType(ListView), 'up', 0.8);\n"
+        );
+    }
+
+    #[test]
+    fn match_contains_escapes_the_text() {
+        assert_eq!(
+            steps_code("- match: { contains: \"Total: $42\" }"),
+            "      expect(find.textContaining('Total: \\$42'), findsOneWidget);\n"
+        );
+        assert_eq!(
+            steps_code("- not_match: { contains: \"It's ${Common.name}\" }"),
+            "      expect(find.textContaining('It\\'s ${Common.name}'), findsNothing);\n"
+        );
+    }
+
+    #[test]
+    fn string_finders_escape_quotes_and_dollars() {
+        assert_eq!(
+            steps_code("- match: { key: \"user's_row\" }"),
+            "      expect(find.byKey(Key('user\\'s_row')), findsOneWidget);\n"
+        );
+        assert_eq!(
+            steps_code("- match: { text: \"Pay $5\" }"),
+            "      expect(find.text('Pay \\$5'), findsOneWidget);\n"
+        );
+        assert_eq!(
+            steps_code("- not_match: { tooltip: \"Don't\" }"),
+            "      expect(find.byTooltip('Don\\'t'), findsNothing);\n"
+        );
+        assert_eq!(
+            steps_code("- clear: { placeholder: \"C:\\\\path\" }"),
+            "      await tester.clearTextField(find.byPlaceholder('C:\\\\path'));\n"
+        );
+        assert_eq!(
+            steps_code("- type: { by: { key: \"email\" }, value: \"${Common.validEmail}\" }"),
+            "      await tester.type(find.byKey(Key('email')), '${Common.validEmail}');\n"
+        );
+    }
+
+    #[test]
+    fn clear_step_empties_the_text_field() {
+        assert_eq!(
+            steps_code("- clear: { key: \"email\" }"),
+            "      await tester.clearTextField(find.byKey(Key('email')));\n"
+        );
+    }
+
+    #[test]
+    fn type_step_submits_only_when_asked() {
+        assert_eq!(
+            steps_code("- type: { by: { key: \"q\" }, value: \"shoes\", submit: true }"),
+            "      await tester.type(find.byKey(Key('q')), 'shoes', submit: true);\n"
+        );
+        assert_eq!(
+            steps_code("- type: { by: { key: \"q\" }, value: \"shoes\" }"),
+            "      await tester.type(find.byKey(Key('q')), 'shoes');\n"
+        );
+    }
+
+    #[test]
+    fn key_step_sends_the_logical_key() {
+        assert_eq!(
+            steps_code("- key: Escape"),
+            "      await tester.sendKeyEvent(LogicalKeyboardKey.escape);\n\
+             \x20     await tester.pumpAndSettle();\n"
+        );
+        assert_eq!(
+            steps_code("- key: nope"),
+            format!(
+                "      // Unknown key 'nope', expected one of: {}\n",
+                LOGICAL_KEYS.join(", ")
+            )
+        );
+    }
+
+    #[test]
+    fn scroll_until_visible_scrolls_the_first_scrollable() {
+        assert_eq!(
+            steps_code("- scroll_until_visible: { target: { text: \"Item 40\" } }"),
+            "      await tester.scrollUntilVisible(find.text('Item 40'), 100.0, scrollable: find.byType(Scrollable).first);\n"
+        );
+        assert_eq!(
+            steps_code(
+                "- scroll_until_visible: { target: { key: \"row_40\" }, scrollable: { key: \"list\" }, delta: -50 }"
+            ),
+            "      await tester.scrollUntilVisible(find.byKey(Key('row_40')), -50.0, scrollable: find.descendant(of: find.byKey(Key('list')), matching: find.byType(Scrollable), matchRoot: true).first);\n"
+        );
+    }
+
+    #[test]
+    fn drag_to_drags_between_the_widgets() {
+        assert_eq!(
+            steps_code("- drag_to: { from: { key: \"a\" }, to: { key: \"b\" } }"),
+            "      await tester.dragBetween(find.byKey(Key('a')), find.byKey(Key('b')), hold: const Duration(milliseconds: 0));\n"
+        );
+        assert_eq!(
+            steps_code(
+                "- drag_to: { from: { key: \"a\" }, to: { text: \"Trash\" }, hold_millis: 500ms }"
+            ),
+            "      await tester.dragBetween(find.byKey(Key('a')), find.text('Trash'), hold: const Duration(milliseconds: 500));\n"
+        );
+    }
+
+    #[test]
+    fn state_output_var_is_interpolated_in_the_feature() {
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        }
+        .with_state_output("token-123");
+        let feature: FeatureTest = serde_yaml::from_str(
+            r#"
+name: Session
+tests:
+  - name: Uses the seeded token
+    steps:
+      - type: { by: { key: "token" }, value: "{{ vars.state.output }}" }
+"#,
+        )
+        .expect("Should parse feature");
+        let out_dir = tempfile::tempdir().expect("Should create temp dir");
+        let generator = GenFlutter {
+            args: AppArgs::parse_from(["playmaster", "gen"]),
+            config: serde_yaml::from_str("project_type: flutter").expect("Should parse config"),
+            out_dir: out_dir.path().to_path_buf(),
+        };
+
+        with_config_ctx("project_type: flutter", &vars, |ctx| {
+            feature.generate_dart(ctx, out_dir.path())?;
+            generator.generate_vars(ctx)
+        })
+        .expect("Should generate feature");
+
+        let code = fs::read_to_string(out_dir.path().join(feature.dart_file_name())).unwrap();
+        assert!(code.contains("import 'package:flutter/material.dart' hide State;\n"));
+        assert!(
+            code.contains("await tester.type(find.byKey(Key('token')), '${State.output}');\n"),
+            "{}",
+            code
+        );
+        let vars_code = fs::read_to_string(out_dir.path().join("vars.dart")).unwrap();
+        assert!(vars_code.contains("class State {\n  static const output = 'token-123';\n}\n"));
+    }
+
+    #[test]
+    fn configured_defaults_apply_to_steps_without_their_own() {
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        };
+        let config = "project_type: flutter\ndefaults: { wait_timeout_millis: 9000, not_found_timeout_millis: 3000, settle: true }";
+        let steps: Vec<Step> = serde_yaml::from_str(
+            r#"
+- wait_for: { key: "home" }
+- wait_for: { key: "home", timeout_millis: 1s, settle: false }
+- not_found: { text: "Loading" }
+"#,
+        )
+        .unwrap();
+
+        let code: String = with_config_ctx(config, &vars, |ctx| {
+            steps
+                .iter()
+                .map(|step| step.to_dart_code(ctx, &HashMap::new(), "feature_test"))
+                .collect()
+        });
+
+        assert_eq!(
+            code,
+            "      await tester.pumpUntilFound(find.byKey(Key('home')), timeout: Duration(milliseconds: 9000));\n\
+             \x20     await tester.pumpAndSettle();\n\
+             \x20     await tester.pumpUntilFound(find.byKey(Key('home')), timeout: Duration(milliseconds: 1000));\n\
+             \x20     await tester.waitUntilGone(find.text('Loading'), timeout: Duration(milliseconds: 3000));\n"
+        );
+    }
+
+    #[test]
+    fn pointer_steps_press_move_and_release() {
+        assert_eq!(
+            steps_code(
+                "- pointer: { down: { x: 10, y: 20 } }\n\
+                 - pointer: { to: { x: 30, y: 40 }, remove: false }\n\
+                 - pointer: { up: {} }"
+            ),
+            "      await tester.pointerDown(Offset(10, 20));\n\
+             \x20     await tester.movePointer(Offset(30, 40), remove: false);\n\
+             \x20     await tester.pointerUp();\n"
+        );
+    }
+
+    #[test]
+    fn pressed_pointer_is_released_at_the_end_of_the_test() {
+        let code = feature_code(
+            r#"
+name: Draw
+tests:
+  - name: Line
+    steps:
+      - pointer: { down: { x: 10, y: 20 } }
+      - pointer: { to: { x: 30, y: 40 }, remove: false }
+  - name: Tap
+    steps:
+      - tap: { key: ok }
+"#,
+        );
+
+        assert!(
+            code.contains(
+                "      await tester.movePointer(Offset(30, 40), remove: false);\n\n\
+                 \x20     await tester.releasePointer();\n\
+                 \x20   });\n"
+            ),
+            "{}",
+            code
+        );
+        assert_eq!(code.matches("releasePointer").count(), 1, "{}", code);
+    }
+
+    #[test]
+    fn scoped_finders_nest() {
+        let code = steps_code(
+            r#"
+- match:
+    count: 1
+    by:
+      matching: { type: "ElevatedButton" }
+      descendant_of:
+        matching: { type: "Card" }
+        ancestor_of: { text: "Premium plan" }
+"#,
+        );
+
+        assert_eq!(
+            code,
+            "      expect(find.descendant(of: find.ancestor(of: find.text('Premium plan'), matching: find.byType(Card)), matching: find.byType(ElevatedButton)), findsNWidgets(1));\n"
+        );
+    }
+
+    #[test]
+    fn slash_wrapped_text_waits_on_a_regex() {
+        assert_eq!(
+            steps_code(r#"- wait_for: { text: "/^Loading \\d+%$/", timeout_millis: 2s }"#),
+            "      await tester.pumpUntilFound(find.textMatching('^Loading \\\\d+%\\$'), timeout: Duration(milliseconds: 2000));\n"
+        );
+        assert_eq!(
+            steps_code(r#"- not_found: { text: "/Loading \\d+%/" }"#),
+            "      await tester.waitUntilGone(find.textMatching('Loading \\\\d+%'), timeout: Duration(milliseconds: 10000));\n"
+        );
+        assert_eq!(
+            steps_code(r#"- not_found: { text: "/" }"#),
+            "      await tester.waitUntilGone(find.text('/'), timeout: Duration(milliseconds: 10000));\n"
+        );
+    }
+
+    #[test]
+    fn tester_steps_in_before_all_are_named_by_their_label() {
+        let step: Step = serde_yaml::from_str("tap: { text: Log in }").expect("Should parse step");
+        let err = step
+            .to_dart_code_without_tester(&HashMap::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Step 'tap text: Log in' requires a widget tester and is not allowed in before_all/after_all"
+        );
+    }
+
+    #[test]
+    fn loop_index_is_rejected_outside_repeat() {
+        let generate = |feature: &str| {
+            let feature: FeatureTest = serde_yaml::from_str(feature).expect("Should parse feature");
+            let out_dir = tempfile::tempdir().expect("Should create temp dir");
+            with_ctx(|ctx| feature.generate_dart(ctx, out_dir.path())).map_err(|e| e.to_string())
+        };
+
+        let err = generate(
+            r#"
+name: Cart
+tests:
+  - name: Remove
+    steps:
+      - tap: { text: "Remove {{ loop.index }}" }
+"#,
+        )
+        .unwrap_err();
+        assert!(
+            err.ends_with("Step 'tap text: Remove {{ loop.index }}' uses {{ loop.index }} outside of a repeat step"),
+            "{err}"
+        );
+
+        // Through a definition or the widget of an `if`, in any block
+        let err = generate(
+            r#"
+name: Cart
+step_definitions:
+  remove:
+    - tap: { key: "remove_{{ loop.index }}" }
+before_each:
+  steps:
+    - use_step: remove
+tests: []
+"#,
+        )
+        .unwrap_err();
+        assert!(
+            err.contains("Step 'tap key: remove_{{ loop.index }}'"),
+            "{err}"
+        );
+        let err = generate(
+            r#"
+name: Cart
+tests:
+  - name: Remove
+    steps:
+      - if_present: { key: "row_{{ loop.index }}" }
+        then: [settle]
+"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("outside of a repeat step"), "{err}");
+
+        // Fine in a repeat, directly or through a definition
+        generate(
+            r#"
+name: Cart
+step_definitions:
+  remove:
+    - if_present: { key: "row_{{ loop.index }}" }
+      then:
+        - tap: { key: "remove_{{ loop.index }}" }
+tests:
+  - name: Remove
+    steps:
+      - repeat:
+          times: 2
+          steps:
+            - tap: { text: "Remove {{ loop.index }}" }
+            - use_step: remove
+"#,
+        )
+        .expect("Should generate feature");
+    }
 }
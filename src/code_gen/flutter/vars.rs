@@ -16,7 +16,7 @@ impl GenFlutter {
 
         let mut dart = String::new();
         dart.push_str("// GENERATED FILE - DO NOT EDIT\n");
-        dart.push_str("// This file contains vars from all *.vars.yaml files in feature_test/\n\n");
+        dart.push_str("// This file contains vars from all *.vars.yaml files in the test dirs\n\n");
 
         for yaml in vars_data {
             let name = &yaml.file_name;
@@ -1,22 +1,37 @@
 use std::{
+    collections::HashMap,
     fs,
+    path::Path,
     sync::{Arc, RwLock},
 };
 
-use tracing::info;
+use sha2::{Digest as _, Sha256};
+use tracing::{error, info, warn};
 
 use crate::{
     code_gen::{flutter::GenFlutter, gen_iface::CodeGenTrait},
     hooks::iface::HookContext,
     models::{
-        args::AppArgs, config::Config, feature_test::FeatureTest, gen_state::GenState, vars::Vars,
+        args::AppArgs, config::Config, feature_test::FeatureTest, gen_state::GenState,
+        timings::Timings, vars::Vars,
     },
     utils::{
         dir::DirUtils,
         errors::{EmptyResult, ResultWithError},
+        string::StringUtils,
+        variables::VariablesUtils,
     },
 };
 
+/// Whether the generated tests can be run as they are.
+#[derive(Debug, PartialEq)]
+pub enum GeneratedStatus {
+    Missing,
+    /// Generated from other feature files, config or generation flags
+    Stale,
+    UpToDate,
+}
+
 /// Main controller to run the code generation logic.
 pub struct CodeGen {
     args: AppArgs,
@@ -26,29 +41,131 @@ pub struct CodeGen {
 
 impl CodeGen {
     pub fn new(args: AppArgs, config: Config, vars: Vars) -> Self {
+        // Placeholder keeping the tests compiling until a run captures the actual output
+        let vars = if config.state_set.capture_output && !vars.has_state_output() {
+            vars.with_state_output("")
+        } else {
+            vars
+        };
         Self { args, config, vars }
     }
 
     pub fn execute(&self) -> EmptyResult {
+        let features = FeatureTest::all_from_curr_dir(
+            &self.config.test_dirs,
+            self.args.version_check,
+            self.args.validate_schema,
+        )?;
+        self.execute_with_features(features)
+    }
+
+    /// Generates the given features instead of the ones found in the current directory.
+    pub fn execute_with_features(&self, features: Vec<FeatureTest>) -> EmptyResult {
         info!(
             "Code generation started with config for project type: {:?}",
             self.config.project_type
         );
 
-        self.generate_code()?;
+        self.generate_code(features)?;
 
         info!("Code generation finished");
 
         Ok(())
     }
 
-    fn generate_code(&self) -> EmptyResult {
-        let features = FeatureTest::all_from_curr_dir()?;
+    /// SHA-256 of what the generated tests depend on: the YAML files of the test dirs, the config,
+    /// the flags baked into the tests and the PlayMaster version.
+    pub fn source_hash(args: &AppArgs, config: &Config) -> ResultWithError<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(&config.content_hash);
+
+        let repeat = args.repeat();
+        hasher.update(format!(
+            "repeat={};filter={:?};shard={:?};interactive={};vars={:?};tags={:?}",
+            repeat,
+            args.filter().filter(|_| repeat > 1),
+            args.shard().map(|shard| shard.to_string()),
+            args.is_interactive(),
+            args.vars,
+            args.tags(),
+        ));
+        // The shards are balanced with the timings, which change after every run
+        if args.shard().is_some()
+            && let Some(timings) = args.timings_file()
+        {
+            hasher.update(fs::read(timings).unwrap_or_default());
+        }
+
+        let cwd = DirUtils::curr_dir()?;
+        let mut paths = vec![];
+        for test_dir in &config.test_dirs {
+            let mut dirs = vec![cwd.join(test_dir)];
+            while let Some(dir) = dirs.pop() {
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    continue;
+                };
+                for entry in entries {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        dirs.push(path);
+                    } else if path
+                        .extension()
+                        .is_some_and(|ext| ext == "yaml" || ext == "yml")
+                    {
+                        paths.push(path);
+                    }
+                }
+            }
+        }
+        paths.sort();
+        for path in paths {
+            hasher.update(
+                path.strip_prefix(&cwd)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .as_bytes(),
+            );
+            hasher.update(fs::read(&path)?);
+        }
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+
+    /// Checks the tests were generated, from the current feature files and flags.
+    pub fn generated_status(args: &AppArgs, config: &Config) -> ResultWithError<GeneratedStatus> {
+        let out_dir = DirUtils::curr_dir()?.join(&args.output_dir);
+        if !out_dir.join("all_tests.dart").exists() {
+            return Ok(GeneratedStatus::Missing);
+        }
+
+        match GenFlutter::read_source_hash(&out_dir) {
+            Some(hash) if hash == Self::source_hash(args, config)? => Ok(GeneratedStatus::UpToDate),
+            _ => Ok(GeneratedStatus::Stale),
+        }
+    }
+
+    fn generate_code(&self, features: Vec<FeatureTest>) -> EmptyResult {
         if features.is_empty() {
             info!("No feature test files found. Nothing to generate.");
             return Ok(());
         }
 
+        Self::check_duplicate_names(&features)?;
+        let features = FeatureTest::apply_only(features);
+        let features = FeatureTest::apply_tags(features, self.args.tags())?;
+        let features = match self.args.shard() {
+            Some(shard) => {
+                let timings = self.args.timings_file().map(Timings::load).transpose()?;
+                shard.apply(features, timings.as_ref())
+            }
+            None => features,
+        };
+
         let state = GenState { features };
         let state = Arc::new(RwLock::new(state));
         let ctx = HookContext {
@@ -59,16 +176,126 @@ impl CodeGen {
         };
 
         let cwd = DirUtils::curr_dir()?;
-        let out_dir = cwd.join("integration_test/generated");
+        let out_dir = cwd.join(&self.args.output_dir);
 
         _ = fs::remove_dir_all(&out_dir);
-        fs::create_dir_all(out_dir)?;
+        fs::create_dir_all(&out_dir)?;
 
         let generators = self.get_generators()?;
         for generator in generators {
             generator.run(&ctx)?;
         }
 
+        if self.args.warn_unused_vars {
+            self.report_unused_vars(&out_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Warns about the global vars neither resolved from `{{ vars.<file>.<key> }}` nor
+    /// referenced as `Class.key` in the generated tests.
+    fn report_unused_vars(&self, out_dir: &Path) -> EmptyResult {
+        let unused = self.unused_vars(out_dir)?;
+        if unused.is_empty() {
+            info!("All vars are referenced");
+            return Ok(());
+        }
+
+        for var in unused {
+            warn!("Unused var: {}", var);
+        }
+
+        Ok(())
+    }
+
+    /// Sorted `<file>.<key> (<path>)` of the global vars the generated tests don't use.
+    fn unused_vars(&self, out_dir: &Path) -> ResultWithError<Vec<String>> {
+        let used = VariablesUtils::used_vars();
+
+        let mut generated = String::new();
+        for entry in fs::read_dir(out_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "dart")
+                && path.file_name().is_some_and(|name| name != "vars.dart")
+            {
+                generated.push_str(&fs::read_to_string(&path)?);
+            }
+        }
+
+        let mut unused = vec![];
+        for var in &self.vars.data {
+            let class_name = StringUtils::to_pascal_case(&var.file_name);
+            for key in var.content.0.keys() {
+                if !used.contains(&format!("vars.{}.{}", var.file_name, key))
+                    && !generated.contains(&format!("{}.{}", class_name, key))
+                {
+                    unused.push(format!(
+                        "{}.{} ({})",
+                        var.file_name,
+                        key,
+                        var.path.display()
+                    ));
+                }
+            }
+        }
+
+        unused.sort();
+        Ok(unused)
+    }
+
+    /// Generated files, aliases and result matching all rely on feature and test names,
+    /// so duplicates would silently shadow each other.
+    fn check_duplicate_names(features: &[FeatureTest]) -> EmptyResult {
+        let mut errors = Vec::new();
+
+        let mut features_by_name: HashMap<String, Vec<&FeatureTest>> = HashMap::new();
+        for feature in features {
+            features_by_name
+                .entry(feature.dart_alias())
+                .or_default()
+                .push(feature);
+        }
+        let mut duplicates = features_by_name
+            .values()
+            .filter(|same| same.len() > 1)
+            .collect::<Vec<_>>();
+        duplicates.sort_by_key(|same| same[0].name.clone());
+        for same in duplicates {
+            let files = same
+                .iter()
+                .map(|f| f.source_path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            errors.push(format!(
+                "Duplicate feature name '{}' in: {}",
+                same[0].name, files
+            ));
+        }
+
+        for feature in features {
+            let mut seen = HashMap::new();
+            for test in &feature.tests {
+                let count = seen.entry(test.name.as_str()).or_insert(0);
+                *count += 1;
+                if *count == 2 {
+                    errors.push(format!(
+                        "Duplicate test name '{}' in feature '{}' ({})",
+                        test.name,
+                        feature.name,
+                        feature.source_path.display()
+                    ));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            for err in &errors {
+                error!("{}", err);
+            }
+            return Err(errors.join("\n").into());
+        }
+
         Ok(())
     }
 
@@ -94,3 +321,53 @@ impl CodeGen {
         Ok(generators)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::Parser as _;
+
+    use super::*;
+    use crate::{models::vars::VarsData, utils::dir::YamlResult};
+
+    #[test]
+    fn vars_neither_resolved_nor_referenced_are_unused() {
+        let out_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            out_dir.path().join("login_test.dart"),
+            "await tester.type(find.byKey(Key('email')), '${Unusedcheck.email}');\n",
+        )
+        .unwrap();
+        fs::write(
+            out_dir.path().join("vars.dart"),
+            "class Unusedcheck {\n  static const stale = 'x';\n}\n",
+        )
+        .unwrap();
+        let vars = Vars {
+            data: vec![YamlResult {
+                file_name: "unusedcheck".to_owned(),
+                path: PathBuf::from("unusedcheck.vars.yaml"),
+                content: VarsData(HashMap::from(
+                    ["email", "token", "stale", "retired"]
+                        .map(|key| (key.to_owned(), "x".to_owned())),
+                )),
+            }],
+            all_vars: HashMap::from([("vars.unusedcheck.token".to_owned(), "x".to_owned())]),
+        };
+        assert_eq!(vars.replace_var("{{ vars.unusedcheck.token }}", None), "x");
+        let code_gen = CodeGen::new(
+            AppArgs::parse_from(["playmaster", "gen"]),
+            serde_yaml::from_str("project_type: flutter").unwrap(),
+            vars,
+        );
+
+        assert_eq!(
+            code_gen.unused_vars(out_dir.path()).unwrap(),
+            [
+                "unusedcheck.retired (unusedcheck.vars.yaml)",
+                "unusedcheck.stale (unusedcheck.vars.yaml)"
+            ]
+        );
+    }
+}
@@ -1,8 +1,12 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+};
 
 use tracing::{error, info, warn};
 
 use crate::{
+    code_gen::r#gen::{CodeGen, GeneratedStatus},
     code_run,
     gmail::{client::GmailClient, imap_client::ImapGmailClient},
     hooks::{
@@ -10,16 +14,20 @@ use crate::{
         iface::{HookContext, HookListExt as _, HookType},
     },
     models::{
-        app_state::AppState,
+        app_state::{AppState, ErrorPhase, LAST_RESULTS_FILE, Results},
         args::{AppArgs, Command},
-        config::{Config, WebhookType},
+        config::{Config, SinkConfig, WebhookType},
         feature_test::FeatureTest,
+        timings::Timings,
         vars::Vars,
     },
+    sinks::{iface::ResultSink, webhook::WebhookSink},
     utils::{
         command::CommandUtils,
-        errors::{EmptyResult, OptionResultTrait},
+        errors::{EmptyResult, OptionResultTrait, ResultTrait as _, ResultWithError},
         execution::ExecutionUtils,
+        setup_marker::SetupMarker,
+        timing::TimingSpan,
     },
 };
 
@@ -28,13 +36,16 @@ pub struct CodeRun {
     args: AppArgs,
     config: Config,
     vars: Vars,
-    hooks: Vec<Box<dyn hooks::iface::Hook>>,
+    hooks: Vec<Arc<dyn hooks::iface::Hook>>,
     state: Arc<RwLock<AppState>>,
 }
 
 impl CodeRun {
     pub fn new(args: AppArgs, config: Config, vars: Vars) -> Self {
         let hooks = Self::load_hooks(&config);
+        if let Err(err) = CommandUtils::set_shell(config.shell.clone()) {
+            error!("Failed to set shell: {}", err);
+        }
         let state = Arc::new(RwLock::new(AppState::default()));
         Self {
             args,
@@ -45,47 +56,95 @@ impl CodeRun {
         }
     }
 
-    fn load_hooks(config: &Config) -> Vec<Box<dyn hooks::iface::Hook>> {
-        let mut hooks: Vec<Box<dyn hooks::iface::Hook>> = vec![
-            Box::new(hooks::check_dependency::HookCheckDependency::new()),
-            Box::new(hooks::connect::HookConnect::new()),
-            Box::new(hooks::setup_state::HookSetupState::new()),
+    fn load_hooks(config: &Config) -> Vec<Arc<dyn hooks::iface::Hook>> {
+        let mut hooks: Vec<Arc<dyn hooks::iface::Hook>> = vec![
+            Arc::new(hooks::check_dependency::HookCheckDependency::new()),
+            Arc::new(hooks::connect::HookConnect::new()),
+            Arc::new(hooks::setup_state::HookSetupState::new()),
+            Arc::new(hooks::remote_precheck::HookRemotePrecheck::new()),
         ];
 
+        // Result sinks publish before the custom finished hooks, which can read the logs URL
+        let sinks = Self::load_sinks(config);
+        if !sinks.is_empty() {
+            hooks.push(Arc::new(hooks::results::HookResults::new(sinks)));
+        }
+
         hooks.extend(config.hooks.iter().map(|hook| {
-            Box::new(hooks::custom::HookCustom::new(hook.clone())) as Box<dyn hooks::iface::Hook>
+            Arc::new(hooks::custom::HookCustom::new(hook.clone())) as Arc<dyn hooks::iface::Hook>
         }));
 
-        // Add result webhooks at the end
+        // After the custom hooks, which may start the services the command relies on
+        hooks.push(Arc::new(hooks::state_capture::HookStateCapture::new()));
+
+        hooks
+    }
+
+    fn load_sinks(config: &Config) -> Vec<Box<dyn ResultSink>> {
+        let mut sinks: Vec<Box<dyn ResultSink>> = vec![];
+
         for webhook_config in &config.webhooks {
             match webhook_config.webhook_type {
                 WebhookType::Results => {
-                    hooks.push(Box::new(hooks::results::HookResults::new(
+                    sinks.push(Box::new(WebhookSink::new(
                         webhook_config.clone(),
+                        config.proxy.clone(),
                     )));
                 }
             }
         }
 
-        hooks
+        for sink_config in &config.results.sinks {
+            match sink_config {
+                SinkConfig::Webhook(webhook_config) => {
+                    sinks.push(Box::new(WebhookSink::new(
+                        webhook_config.clone(),
+                        config.proxy.clone(),
+                    )));
+                }
+            }
+        }
+
+        sinks
     }
 
-    fn run_hooks_of_type(
+    async fn run_hooks_of_type(
         &self,
-        ctx: &HookContext<'_, AppState>,
         hook_type: hooks::iface::HookType,
         has_error: bool,
     ) -> EmptyResult {
         let hooks_to_run = self.hooks.hooks_of_type(hook_type);
         for hook in hooks_to_run {
             if hook.continue_on_error() || !has_error {
-                hook.run(ctx)?;
+                self.run_blocking(hook).await?;
             }
         }
         Ok(())
     }
 
-    pub async fn execute(&self) -> EmptyResult {
+    /// Runs a hook on the blocking thread pool, as hooks block on commands, HTTP clients and S3
+    /// uploads, which must not happen on the async runtime, whether multi or current thread.
+    async fn run_blocking(&self, hook: Arc<dyn hooks::iface::Hook>) -> EmptyResult {
+        let args = self.args.clone();
+        let config = self.config.clone();
+        let vars = self.vars.clone();
+        let state = Arc::clone(&self.state);
+
+        tokio::task::spawn_blocking(move || {
+            hook.run(&HookContext {
+                args: &args,
+                config: &config,
+                vars: &vars,
+                state,
+            })
+        })
+        .await
+        .auto_err("Hook panicked")?
+    }
+
+    /// Runs the hooks and the tests, returning the results even when some tests failed.
+    /// Errors are returned when the run couldn't complete, e.g. a pre-hook or the build failed.
+    pub async fn execute(&self) -> ResultWithError<Results> {
         let ctx = HookContext {
             args: &self.args,
             config: &self.config,
@@ -93,11 +152,15 @@ impl CodeRun {
             state: Arc::clone(&self.state),
         };
 
+        if self.args.is_report_only() {
+            return self.report_only(&ctx, Path::new(LAST_RESULTS_FILE)).await;
+        }
+
         // Validate Gmail/IMAP credentials if Gmail is enabled
         if self.config.gmail.enabled {
             // Prefer IMAP (simpler App Password auth) over OAuth
             if let Some(imap_config) = &self.config.gmail.credentials.imap {
-                let app_password = self.vars.replace_var(&imap_config.app_password, None);
+                let app_password = imap_config.resolve_app_password(&self.vars);
                 let imap_client = ImapGmailClient::new(imap_config.email.clone(), app_password);
 
                 if let Err(err) = imap_client.validate_credentials() {
@@ -110,10 +173,15 @@ impl CodeRun {
                 }
             } else if let Some(creds) = &self.config.gmail.credentials.s3 {
                 // Fall back to OAuth if no IMAP config
-                let gmail_client =
-                    GmailClient::new(Some(creds.bucket.clone()), Some(creds.key_prefix.clone()));
+                let gmail_client = GmailClient::new(Some(creds.clone()));
 
-                if let Err(err) = gmail_client.ensure_authenticated().await {
+                // Authenticating opens a browser flow waiting for the user
+                let res = if self.args.non_interactive {
+                    gmail_client.validate_credentials().await
+                } else {
+                    gmail_client.ensure_authenticated().await
+                };
+                if let Err(err) = res {
                     warn!(
                         "Gmail OAuth authentication failed: {}. Tests requiring Gmail MFA will fail.",
                         err
@@ -122,19 +190,24 @@ impl CodeRun {
             }
         }
 
-        let features = match FeatureTest::all_from_curr_dir() {
+        let generate_span = TimingSpan::start(&ctx, "generate");
+        let generated = self.generate_if_stale();
+        drop(generate_span);
+        if let Err(err) = generated {
+            self.abort_before_run(&ctx, format!("Failed to generate the tests: {}", err))
+                .await?;
+            return Err("Failed to generate the tests".into());
+        }
+
+        let features = match FeatureTest::all_from_curr_dir(
+            &self.config.test_dirs,
+            self.args.version_check,
+            self.args.validate_schema,
+        ) {
             Ok(features) => features,
             Err(err) => {
-                let err = format!("Failed to load feature tests: {}", err);
-                error!("{}", err);
-                ctx.add_results_error(err)?;
-
-                if let Err(err) = self.run_hooks_of_type(&ctx, HookType::Finished, true) {
-                    let err = format!("Post-hook {:?} failed: {}", HookType::Finished, err);
-                    error!("{}", err);
-                    ctx.add_results_error(err)?;
-                }
-
+                self.abort_before_run(&ctx, format!("Failed to load feature tests: {}", err))
+                    .await?;
                 return Err("Failed to load feature tests".into());
             }
         };
@@ -148,11 +221,12 @@ impl CodeRun {
         info!("Running pre-execution hooks");
 
         for hook_type in hooks::iface::HookType::pre_hooks() {
-            if let Err(err) = self.run_hooks_of_type(&ctx, hook_type, has_error) {
+            let _span = TimingSpan::start(&ctx, hook_type.error_phase().as_str());
+            if let Err(err) = self.run_hooks_of_type(hook_type, has_error).await {
                 let err = format!("Pre-hook error {:?} failed: {}", hook_type, err);
                 error!("{}", err);
                 has_error = true;
-                ctx.add_results_error(err)?;
+                ctx.add_results_error(hook_type.error_phase(), err)?;
 
                 if !ExecutionUtils::is_running() {
                     break;
@@ -160,6 +234,14 @@ impl CodeRun {
             }
         }
 
+        if !has_error
+            && ExecutionUtils::is_running()
+            && !ctx.read_state()?.setup_complete
+            && let Err(err) = self.mark_setup_complete(&ctx)
+        {
+            warn!("Failed to mark setup as complete: {}", err);
+        }
+
         let res = if !has_error && ExecutionUtils::is_running() {
             self.run_tests(&ctx, features).await
         } else {
@@ -172,13 +254,21 @@ impl CodeRun {
             error!("Failed to terminate running commands: {}", err);
         }
 
+        // Kept before the finished hooks add their own errors, for `--report-only` to resend
+        if !self.args.is_dry_run()
+            && let Err(err) = ctx.get_results()?.save(Path::new(LAST_RESULTS_FILE))
+        {
+            warn!("Failed to save the results: {}", err);
+        }
+
         info!("Running post-execution hooks");
         for hook_type in hooks::iface::HookType::post_hooks() {
-            if let Err(err) = self.run_hooks_of_type(&ctx, hook_type, has_error) {
+            let _span = TimingSpan::start(&ctx, hook_type.error_phase().as_str());
+            if let Err(err) = self.run_hooks_of_type(hook_type, has_error).await {
                 let err = format!("Post-hook {:?} failed: {}", hook_type, err);
                 error!("{}", err);
                 has_error = true;
-                ctx.add_results_error(err)?;
+                ctx.add_results_error(hook_type.error_phase(), err)?;
             }
         }
 
@@ -187,8 +277,44 @@ impl CodeRun {
         }
 
         info!("Execution finished");
+        TimingSpan::print_breakdown(&ctx.read_state()?.results.phase_timings);
 
-        res
+        res?;
+        ctx.get_results()
+    }
+
+    /// Publishes the results saved at `path` to the result sinks again, without running anything.
+    async fn report_only(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        path: &Path,
+    ) -> ResultWithError<Results> {
+        let sinks = Self::load_sinks(&self.config);
+        if sinks.is_empty() {
+            return Err("No result sinks configured, nothing to report to".into());
+        }
+
+        let results = Results::load(path)?;
+        info!(
+            "Reporting the results of the run started at {}: {} passed, {} failed, {} skipped",
+            results.start_time, results.passed, results.failed, results.skipped
+        );
+        ctx.write_state()?.results = results.clone();
+
+        self.run_blocking(Arc::new(hooks::results::HookResults::new(sinks)))
+            .await?;
+        Ok(results)
+    }
+
+    /// Records that setup succeeded with the current config, for `--once` to skip it next time.
+    fn mark_setup_complete(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
+        if self.args.is_dry_run() {
+            return Ok(());
+        }
+
+        let root_dir = ctx.get_root_dir()?;
+        let remote = ctx.read_state()?.remote.clone();
+        SetupMarker::write(remote.as_ref(), &root_dir, &self.config.content_hash)
     }
 
     async fn run_tests(
@@ -214,6 +340,235 @@ impl CodeRun {
                 .as_str(),
             )?;
 
-        runner.run(ctx, &features).await
+        let features = match self.select_features(ctx, features) {
+            Ok(features) if features.is_empty() => return Ok(()),
+            Ok(features) => features,
+            Err(err) => {
+                ctx.add_results_error(ErrorPhase::Prepare, err.to_string())?;
+                return Err(err);
+            }
+        };
+
+        let res = runner.run(ctx, &features).await;
+
+        if let Some(path) = self.args.timings_file()
+            && !self.args.is_dry_run()
+            && let Err(err) = Self::save_timings(ctx, path)
+        {
+            warn!("Failed to save test timings: {}", err);
+        }
+
+        res
+    }
+
+    /// Keeps the features of the shard and matching the filter, empty when the shard has no tests.
+    fn select_features(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        features: Vec<FeatureTest>,
+    ) -> ResultWithError<Vec<FeatureTest>> {
+        let features = FeatureTest::apply_tags(features, self.args.tags())?;
+        let features = match self.args.shard() {
+            Some(shard) => {
+                ctx.write_state()?.results.shard = Some(shard.to_string());
+                let timings = self.args.timings_file().map(Timings::load).transpose()?;
+                let features = shard.apply(features, timings.as_ref());
+                if features.is_empty() {
+                    info!("Shard {} has no tests, nothing to run", shard);
+                    return Ok(features);
+                }
+                features
+            }
+            None => features,
+        };
+        let features = self.filter_features(features)?;
+
+        self.check_generated_output()?;
+        Ok(features)
+    }
+
+    /// Fails early when the generated tests are missing or stale, instead of building them
+    /// into a cryptic Dart error, or generates them with `--auto-gen`.
+    fn check_generated_output(&self) -> EmptyResult {
+        let problem = match CodeGen::generated_status(&self.args, &self.config)? {
+            GeneratedStatus::UpToDate => return Ok(()),
+            GeneratedStatus::Missing => "are missing",
+            GeneratedStatus::Stale => "are out of date with the feature files",
+        };
+
+        Err(format!(
+            "Generated tests in {} {}, run 'playmaster gen' first or drop --no-gen",
+            self.args.output_dir.display(),
+            problem
+        )
+        .into())
+    }
+
+    /// Generates the tests unless they are up to date, so edited YAML files never run stale
+    /// tests. Skipped with `--no-gen`.
+    fn generate_if_stale(&self) -> EmptyResult {
+        if self.args.is_no_gen() {
+            info!("Test generation skipped (--no-gen)");
+            return Ok(());
+        }
+
+        match CodeGen::generated_status(&self.args, &self.config)? {
+            GeneratedStatus::UpToDate => {
+                info!("Generated tests are up to date");
+                return Ok(());
+            }
+            GeneratedStatus::Missing => info!("Generated tests are missing, generating them"),
+            GeneratedStatus::Stale => info!(
+                "Feature files, config or flags changed since the last generation, generating the tests"
+            ),
+        }
+
+        let vars =
+            Vars::all_from_curr_dir(&self.config.test_dirs)?.with_overrides(&self.args.vars)?;
+        CodeGen::new(self.args.clone(), self.config.clone(), vars).execute()
+    }
+
+    /// Records an error preventing the run, and still runs the finished hooks to report it.
+    async fn abort_before_run(&self, ctx: &HookContext<'_, AppState>, err: String) -> EmptyResult {
+        error!("{}", err);
+        ctx.add_results_error(ErrorPhase::Prepare, err)?;
+
+        if let Err(err) = self.run_hooks_of_type(HookType::Finished, true).await {
+            let err = format!("Post-hook {:?} failed: {}", HookType::Finished, err);
+            error!("{}", err);
+            ctx.add_results_error(HookType::Finished.error_phase(), err)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the durations of this run to the timings file, read back by the next sharded runs.
+    fn save_timings(ctx: &HookContext<'_, AppState>, path: &Path) -> EmptyResult {
+        let durations = ctx.read_state()?.results.durations_ms.clone();
+        if durations.is_empty() {
+            return Ok(());
+        }
+
+        let mut timings = Timings::load(path)?;
+        for (test_name, duration_ms) in durations {
+            timings.record(&test_name, duration_ms);
+        }
+        timings.save(path)
+    }
+
+    fn filter_features(&self, features: Vec<FeatureTest>) -> ResultWithError<Vec<FeatureTest>> {
+        let Some(filter) = self.args.filter() else {
+            return Ok(features);
+        };
+
+        let features = features
+            .into_iter()
+            .filter(|f| f.matches_filter(filter))
+            .collect::<Vec<_>>();
+
+        if features.is_empty() {
+            return Err(format!("No feature matches filter '{}'", filter).into());
+        }
+
+        info!(
+            "Filter '{}' matched features: {}",
+            filter,
+            features
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        Ok(features)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead as _, BufReader, Read as _, Write as _},
+        net::TcpListener,
+        thread,
+    };
+
+    use clap::Parser as _;
+
+    use super::*;
+
+    /// Webhook answering one request, returning the JSON body it received.
+    fn webhook_server() -> (String, thread::JoinHandle<serde_json::Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            serde_json::from_slice(&body).unwrap()
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn report_only_publishes_the_saved_results_on_a_current_thread_runtime() {
+        let (url, server) = webhook_server();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("last_results.json");
+        Results {
+            total: 4,
+            passed: 3,
+            failed: 1,
+            ..Default::default()
+        }
+        .save(&path)
+        .unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            "project_type: flutter\nresults: {{ sinks: [{{ type: webhook, url: \"{url}\" }}] }}"
+        ))
+        .unwrap();
+        let code_run = CodeRun::new(
+            AppArgs::parse_from(["playmaster", "run", "--report-only"]),
+            config,
+            Vars {
+                data: vec![],
+                all_vars: Default::default(),
+            },
+        );
+        let ctx = HookContext {
+            args: &code_run.args,
+            config: &code_run.config,
+            vars: &code_run.vars,
+            state: Arc::clone(&code_run.state),
+        };
+
+        let results = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(code_run.report_only(&ctx, &path))
+            .unwrap();
+
+        assert_eq!((results.passed, results.failed), (3, 1));
+        let payload = server.join().unwrap();
+        assert_eq!(payload["results"]["passed"], 3);
+        assert_eq!(payload["results"]["failed"], 1);
+        assert_eq!(payload["results"]["total"], 4);
     }
 }
@@ -1,27 +1,31 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs,
     io::{BufRead as _, BufReader},
     path::{Path, PathBuf},
     pin::Pin,
     process::{Child, Command, Stdio},
     str::FromStr,
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 
 use indicatif::ProgressBar;
 use regex::Regex;
 use scopeguard::defer;
+use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
 use tempfile::NamedTempFile;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
+    code_gen::flutter::GenFlutter,
     code_run::run_iface::CodeRunTrait,
     gmail::{client::GmailClient, imap_client::ImapGmailClient},
     hooks::iface::HookContext,
     models::{
-        app_state::{AppState, RemoteInfo},
+        app_state::{AppState, ErrorPhase, RemoteInfo, RemoteLineIterator},
+        args,
         config::ProjectType,
         feature_test::{FeatureTest, UserInputGmail},
     },
@@ -31,13 +35,106 @@ use crate::{
         dbus::DbusUtils,
         errors::{EmptyResult, ResultTrait, ResultWithError},
         flutter::FlutterUtils,
+        logger::LoggerUtils,
         os::OsUtils,
+        shlex::ShlexUtils,
+        string::StringUtils,
+        timing::TimingSpan,
+        variables::VariablesUtils,
     },
 };
 
+const LINUX_DEVICE: &str = "linux";
+const BUILD_MODE: &str = "debug";
+/// Syncs of a directory before giving up on a remote copy that differs from the local one
+const SYNC_ATTEMPTS: u32 = 2;
+/// Red `[Flutter Error Log]` prefix for forwarded stderr lines
+const STDERR_TAG: &str = "\x1b[31m[Flutter Error Log]\x1b[0m";
+const DEFAULT_BUILD_COMMAND: &str =
+    "flutter pub get && flutter build linux --{{mode}} --target={{target}}";
+/// Written next to the Linux bundle, to tell whether `--no-build` would drive stale tests
+const BUNDLE_INFO_FILE: &str = "playmaster_bundle.json";
+
 #[allow(dead_code)]
+#[derive(Default)]
 pub struct RunFlutter;
 
+/// Options resolved once per run which shape the build and `flutter drive` commands.
+struct DriveOptions {
+    /// Dart entrypoint to build and drive, relative to the Flutter project
+    target: String,
+    /// Flutter device id passed to `flutter drive -d`
+    device: String,
+    /// Custom build command from the config, supporting `{{target}}` and `{{mode}}` placeholders
+    build_command: Option<String>,
+    /// Directory of the generated tests, relative to the Flutter project
+    output_dir: PathBuf,
+    /// Reuse the existing bundle instead of building the app
+    no_build: bool,
+    /// Env vars of the `flutter drive` process, the `run_env` config overridden by `--env`
+    env: Vec<(String, String)>,
+}
+
+impl DriveOptions {
+    /// Whether the tests run on the Linux desktop using the prebuilt Linux bundle.
+    fn is_linux_desktop(&self) -> bool {
+        self.device == LINUX_DEVICE
+    }
+}
+
+/// Tests compiled into the Linux bundle, which `flutter drive --use-application-binary` runs
+/// instead of the current generated ones.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct BundleInfo {
+    target: String,
+    /// `source_hash` of the generated tests' manifest at build time
+    source_hash: Option<String>,
+}
+
+impl BundleInfo {
+    /// Why a bundle built with `self` can't run the `current` tests, if it can't.
+    fn mismatch(&self, current: &BundleInfo) -> Option<String> {
+        if self.target != current.target {
+            return Some(format!(
+                "it was built for {} and this run drives {}",
+                self.target, current.target
+            ));
+        }
+        if self.source_hash != current.source_hash {
+            return Some("the tests were generated again since it was built".to_owned());
+        }
+        None
+    }
+}
+
+/// Running totals of a `flutter drive` progress line, e.g. `+3 ~1 -2` in `00:42 +3 ~1 -2: Test`.
+#[derive(Debug, Default, PartialEq)]
+struct ProgressCounters {
+    passed: i16,
+    /// Counted with `~`
+    skipped: i16,
+    failed: i16,
+}
+
+impl ProgressCounters {
+    fn parse(counters: &str) -> Self {
+        let mut parsed = Self::default();
+        for counter in counters.split_whitespace() {
+            let Some(kind) = counter.chars().next() else {
+                continue;
+            };
+            let count = counter[kind.len_utf8()..].parse::<i16>().unwrap_or(0);
+            match kind {
+                '+' => parsed.passed = count,
+                '~' => parsed.skipped = count,
+                '-' => parsed.failed = count,
+                _ => {}
+            }
+        }
+        parsed
+    }
+}
+
 impl CodeRunTrait for RunFlutter {
     fn get_type(&self) -> ProjectType {
         ProjectType::Flutter
@@ -59,17 +156,44 @@ impl CodeRunTrait for RunFlutter {
                 utils::dir::DirUtils::curr_dir()?
             };
 
-            self.prepare_env(remote, &exec_dir, &root_dir)?;
+            if self.should_update_screenshots(ctx) {
+                warn!(
+                    "⚠️ --update-screenshots is set, screenshot baselines will be OVERWRITTEN instead of compared"
+                );
+            }
+
+            let opts = match self.get_drive_options(ctx, remote, &root_dir, features) {
+                Ok(opts) => opts,
+                Err(err) => {
+                    ctx.add_results_error(ErrorPhase::Prepare, err.to_string())?;
+                    return Err(err);
+                }
+            };
+
+            if ctx.args.is_dry_run() {
+                return self.print_dry_run(ctx, remote, &exec_dir, &root_dir, &opts);
+            }
 
-            if let Some(remote) = remote {
+            if let Err(err) = self.prepare_env(ctx, remote, &exec_dir, &root_dir, &opts) {
+                ctx.add_results_error(ErrorPhase::Build, err.to_string())?;
+                return Err(err);
+            }
+
+            let drive_span = TimingSpan::start(ctx, "drive");
+            let res = if let Some(remote) = remote {
                 info!("Running Flutter tests remotely");
-                self.execute_remote(ctx, remote, &exec_dir, &root_dir, features)
+                self.execute_remote(ctx, remote, &exec_dir, &root_dir, &opts, features)
                     .await
             } else {
                 info!("Running Flutter tests locally\n");
-                self.execute_local(ctx, &exec_dir, &root_dir, features)
+                self.execute_local(ctx, &exec_dir, &root_dir, &opts, features)
                     .await
+            };
+            drop(drive_span);
+            if let Err(err) = &res {
+                ctx.add_results_error(ErrorPhase::Run, err.to_string())?;
             }
+            res
         })
     }
 }
@@ -79,33 +203,319 @@ impl RunFlutter {
         Self {}
     }
 
+    /// Returns the Dart entrypoint to build and drive, which is the single feature's own
+    /// test file when `--filter` selects exactly one feature and `all_tests.dart` otherwise.
+    fn get_target(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        features: &[FeatureTest],
+    ) -> ResultWithError<String> {
+        let output_dir = &ctx.args.output_dir;
+        let all_tests = output_dir.join("all_tests.dart").display().to_string();
+
+        let args::Command::Run {
+            filter: Some(filter),
+            ..
+        } = &ctx.args.command
+        else {
+            return Ok(all_tests);
+        };
+
+        let [feature] = features else {
+            warn!(
+                "Filter '{}' matched {} features, running all_tests.dart",
+                filter,
+                features.len()
+            );
+            return Ok(all_tests);
+        };
+
+        let target = output_dir
+            .join(feature.dart_file_name())
+            .display()
+            .to_string();
+        if !utils::dir::DirUtils::curr_dir()?.join(&target).exists() {
+            return Err(format!(
+                "Generated test file {} not found, please run `playmaster gen` first",
+                target
+            )
+            .into());
+        }
+
+        Ok(target)
+    }
+
+    /// Options of the build and drive commands, with the device checked to be available.
+    fn get_drive_options(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        remote: Option<&RemoteInfo>,
+        root_dir: &str,
+        features: &[FeatureTest],
+    ) -> ResultWithError<DriveOptions> {
+        let opts = DriveOptions {
+            target: self.get_target(ctx, features)?,
+            device: self.get_device(ctx),
+            build_command: ctx.config.build_command.clone(),
+            output_dir: ctx.args.output_dir.clone(),
+            no_build: ctx.args.is_no_build(),
+            env: self.get_run_env(ctx)?,
+        };
+        info!(
+            "Using test target: {}, device: {}",
+            opts.target, opts.device
+        );
+
+        if ctx.args.is_dry_run() {
+            info!(
+                "Dry run: would check that Flutter device '{}' is available",
+                opts.device
+            );
+        } else {
+            self.validate_device(remote, root_dir, &opts.device)?;
+        }
+        Ok(opts)
+    }
+
+    fn get_run_env(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+    ) -> ResultWithError<Vec<(String, String)>> {
+        let mut env = ctx.config.run_env.clone();
+        if let Some(key) = env.keys().find(|k| !args::AppArgs::is_env_name(k)) {
+            return Err(format!(
+                "Invalid run_env name '{}', expected letters, digits and _",
+                key
+            )
+            .into());
+        }
+        env.extend(ctx.args.run_env().iter().cloned());
+
+        Ok(env.into_iter().collect())
+    }
+
+    /// `KEY=value ` assignments prepended to the drive command, with the values redacted for logs.
+    fn get_env_prefix(&self, opts: &DriveOptions, redacted: bool) -> ResultWithError<String> {
+        let mut prefix = String::new();
+        for (key, value) in &opts.env {
+            let value = if redacted {
+                StringUtils::redact(value)
+            } else {
+                ShlexUtils::quote(value)?
+            };
+            prefix.push_str(&format!("{key}={value} "));
+        }
+        Ok(prefix)
+    }
+
+    fn get_device(&self, ctx: &HookContext<'_, AppState>) -> String {
+        if let args::Command::Run {
+            device: Some(device),
+            ..
+        } = &ctx.args.command
+        {
+            return device.clone();
+        }
+
+        ctx.config
+            .device
+            .clone()
+            .unwrap_or_else(|| LINUX_DEVICE.to_owned())
+    }
+
+    /// Ensures the device is reported by `flutter devices` on the machine running the tests.
+    fn validate_device(
+        &self,
+        remote: Option<&RemoteInfo>,
+        root_dir: &str,
+        device: &str,
+    ) -> EmptyResult {
+        info!("Checking that Flutter device '{}' is available...", device);
+
+        let res = CommandUtils::run_command_str("flutter devices --machine", remote, root_dir)?;
+        if res.status != 0 {
+            return Err(format!("Failed to list Flutter devices: {}", res.stderr).into());
+        }
+
+        // Skip anything printed before the JSON output, e.g. flutter upgrade banners
+        let json = res
+            .stdout
+            .find('[')
+            .map(|pos| &res.stdout[pos..])
+            .unwrap_or_default();
+        let devices: Vec<serde_json::Value> =
+            serde_json::from_str(json).auto_err("Invalid output from flutter devices")?;
+        let ids = devices
+            .iter()
+            .filter_map(|d| d.get("id").and_then(|id| id.as_str()))
+            .collect::<Vec<_>>();
+
+        if !ids.contains(&device) {
+            return Err(format!(
+                "Flutter device '{}' not found, available devices: {}",
+                device,
+                ids.join(", ")
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
     fn prepare_env(
         &self,
+        ctx: &HookContext<'_, AppState>,
         remote: Option<&RemoteInfo>,
         exec_dir: &Path,
         root_dir: &str,
+        opts: &DriveOptions,
     ) -> EmptyResult {
-        self.build()?;
+        let build_span = TimingSpan::start(ctx, "build");
+        if opts.no_build {
+            // An unchanged bundle is not transferred again by rsync below
+            self.check_existing_bundle(opts)?;
+        } else {
+            self.build(opts)?;
+        }
+        drop(build_span);
 
         if let Some(remote) = remote {
-            self.sync_build(remote, root_dir, exec_dir)?;
-            self.sync_tests(remote, root_dir, exec_dir)?;
-            self.sync_driver(remote, root_dir, exec_dir)?;
-            self.sync_linux(remote, root_dir, exec_dir)?;
+            for (desc, local_dir, remote_dir) in self.get_sync_dirs(exec_dir, opts)? {
+                info!("Syncing {} to remote...", desc);
+                let _span = TimingSpan::start(ctx, format!("sync {}", desc));
+                self.sync_dir_verified(
+                    remote,
+                    root_dir,
+                    local_dir.to_string_lossy().as_ref(),
+                    remote_dir.to_string_lossy().as_ref(),
+                )?;
+            }
+            let _span = TimingSpan::start(ctx, "sync pubspec");
             self.sync_pubspec(remote, root_dir, exec_dir)?;
         }
 
         Ok(())
     }
 
-    fn build(&self) -> EmptyResult {
+    /// Syncs a directory to the remote and compares the file sizes on both sides, syncing
+    /// again once when they differ, e.g. after a transfer cut by a flaky network.
+    fn sync_dir_verified(
+        &self,
+        remote: &RemoteInfo,
+        root_dir: &str,
+        local_dir: &str,
+        remote_dir: &str,
+    ) -> EmptyResult {
+        let mut attempt = 1;
+        loop {
+            CommandUtils::sync_dir_to_remote(remote, root_dir, local_dir, remote_dir)?;
+            let diffs = CommandUtils::diff_synced_dir(remote, root_dir, local_dir, remote_dir)?;
+            if diffs.is_empty() {
+                return Ok(());
+            }
+
+            if attempt >= SYNC_ATTEMPTS {
+                return Err(format!(
+                    "Remote copy of {} does not match after {} syncs: {}",
+                    local_dir,
+                    attempt,
+                    diffs
+                        .iter()
+                        .take(10)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+                .into());
+            }
+            warn!(
+                "Remote copy of {} does not match ({} file(s) differ), syncing again",
+                local_dir,
+                diffs.len()
+            );
+            attempt += 1;
+        }
+    }
+
+    /// Prints the commands and sync operations a run would perform without executing them.
+    fn print_dry_run(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        remote: Option<&RemoteInfo>,
+        exec_dir: &Path,
+        root_dir: &str,
+        opts: &DriveOptions,
+    ) -> EmptyResult {
+        info!("🔎 Dry run, nothing will be executed");
+        if opts.no_build {
+            info!("Build skipped, reusing the existing bundle");
+        } else {
+            info!(
+                "Build command (in {}): {}",
+                utils::dir::DirUtils::curr_dir()?.display(),
+                self.get_build_command(opts)
+            );
+        }
+
+        if let Some(remote) = remote {
+            let ssh_target = format!("{}@{}:{}", remote.user, remote.host, remote.port);
+            for (desc, local_dir, remote_dir) in self.get_sync_dirs(exec_dir, opts)? {
+                info!(
+                    "Sync {}: {} -> {}{}",
+                    desc,
+                    local_dir.display(),
+                    ssh_target,
+                    remote_dir.display()
+                );
+            }
+            info!(
+                "Copy cleaned pubspec.yaml -> {}{}",
+                ssh_target,
+                exec_dir.join("pubspec.yaml").display()
+            );
+            info!(
+                "Remote drive command: {}",
+                self.get_remote_command_str(ctx, exec_dir, root_dir, opts, true)?
+            );
+        } else {
+            info!(
+                "Drive command (in {}): DISPLAY={} {}{} -c {:?}",
+                exec_dir.display(),
+                OsUtils::get_display(),
+                self.get_env_prefix(opts, true)?,
+                CommandUtils::shell_program(),
+                self.get_flutter_drive_command_str(ctx, root_dir, opts, "")?
+            );
+        }
+
+        Ok(())
+    }
+
+    fn get_build_command(&self, opts: &DriveOptions) -> String {
+        let build_command = match &opts.build_command {
+            Some(build_command) => build_command.as_str(),
+            None if opts.is_linux_desktop() => DEFAULT_BUILD_COMMAND,
+            // Other devices are built by `flutter drive` itself
+            None => "flutter pub get",
+        };
+
+        let placeholders = HashMap::from([
+            ("target".to_owned(), opts.target.clone()),
+            ("mode".to_owned(), BUILD_MODE.to_owned()),
+        ]);
+        VariablesUtils::replace_vars(build_command, &placeholders, None)
+    }
+
+    fn build(&self, opts: &DriveOptions) -> EmptyResult {
         info!("Building Flutter app...");
+        let cmd = self.get_build_command(opts);
+        debug!("Build command: {}", cmd);
 
         let mut command = Command::new("bash");
         command
             .current_dir(utils::dir::DirUtils::curr_dir()?)
             .arg("-c")
-            .arg("flutter pub get && flutter build linux --debug --target=integration_test/generated/all_tests.dart")
+            .arg(cmd)
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit());
         let status = command.status()?;
@@ -114,81 +524,123 @@ impl RunFlutter {
             return Err("Flutter build failed".into());
         }
 
-        Ok(())
-    }
-
-    fn sync_build(&self, remote: &RemoteInfo, root_dir: &str, exec_dir: &Path) -> EmptyResult {
-        info!("Syncing build to remote...");
-
-        let local_flutter_dir = utils::dir::DirUtils::curr_dir()?
-            .join("build")
-            .join("linux")
-            .join("x64")
-            .join("debug")
-            .join("bundle");
-        let remote_flutter_dir = exec_dir
-            .join("build")
-            .join("linux")
-            .join("x64")
-            .join("debug")
-            .join("bundle");
-
-        utils::command::CommandUtils::sync_dir_to_remote(
-            remote,
-            root_dir,
-            local_flutter_dir.to_string_lossy().as_ref(),
-            remote_flutter_dir.to_string_lossy().as_ref(),
-        )?;
+        if opts.is_linux_desktop() {
+            let info = self.get_current_bundle_info(opts)?;
+            fs::write(self.get_bundle_info_path()?, serde_json::to_string(&info)?)?;
+        }
 
         Ok(())
     }
 
-    fn sync_tests(&self, remote: &RemoteInfo, root_dir: &str, exec_dir: &Path) -> EmptyResult {
-        info!("Syncing integration tests to remote...");
+    /// Makes sure there is a bundle to drive against when `--no-build` is set.
+    fn check_existing_bundle(&self, opts: &DriveOptions) -> EmptyResult {
+        // Other devices are built by `flutter drive` itself
+        if !opts.is_linux_desktop() {
+            info!(
+                "Skipping build, flutter drive builds the app for {}",
+                opts.device
+            );
+            return Ok(());
+        }
 
-        let local_flutter_dir = utils::dir::DirUtils::curr_dir()?.join("integration_test");
-        let remote_flutter_dir = exec_dir.join("integration_test");
+        let bundle_dir = utils::dir::DirUtils::curr_dir()?.join(self.get_bundle_dir());
+        if !bundle_dir.is_dir() {
+            return Err(format!(
+                "No existing bundle found at {}, run once without --no-build to build it",
+                bundle_dir.display()
+            )
+            .into());
+        }
 
-        utils::command::CommandUtils::sync_dir_to_remote(
-            remote,
-            root_dir,
-            local_flutter_dir.to_string_lossy().as_ref(),
-            remote_flutter_dir.to_string_lossy().as_ref(),
-        )?;
+        // The tests are compiled into the bundle, which would otherwise silently run old ones
+        let current = self.get_current_bundle_info(opts)?;
+        match fs::read_to_string(self.get_bundle_info_path()?)
+            .ok()
+            .and_then(|content| serde_json::from_str::<BundleInfo>(&content).ok())
+        {
+            Some(built) => {
+                if let Some(mismatch) = built.mismatch(&current) {
+                    return Err(format!(
+                        "The bundle at {} can't run the current tests, {}. Run without --no-build to rebuild it",
+                        bundle_dir.display(),
+                        mismatch
+                    )
+                    .into());
+                }
+            }
+            None => warn!(
+                "⚠️ No {} next to the bundle, it may run tests older than the generated ones. Run once without --no-build to record them",
+                BUNDLE_INFO_FILE
+            ),
+        }
 
+        info!(
+            "Skipping build, reusing the bundle at {}",
+            bundle_dir.display()
+        );
         Ok(())
     }
 
-    fn sync_driver(&self, remote: &RemoteInfo, root_dir: &str, exec_dir: &Path) -> EmptyResult {
-        info!("Syncing test_driver to remote...");
-
-        let local_flutter_dir = utils::dir::DirUtils::curr_dir()?.join("test_driver");
-        let remote_flutter_dir = exec_dir.join("test_driver");
+    /// Target and generated tests the bundle is built with by this run.
+    fn get_current_bundle_info(&self, opts: &DriveOptions) -> ResultWithError<BundleInfo> {
+        let out_dir = utils::dir::DirUtils::curr_dir()?.join(&opts.output_dir);
+        Ok(BundleInfo {
+            target: opts.target.clone(),
+            source_hash: GenFlutter::read_source_hash(&out_dir),
+        })
+    }
 
-        utils::command::CommandUtils::sync_dir_to_remote(
-            remote,
-            root_dir,
-            local_flutter_dir.to_string_lossy().as_ref(),
-            remote_flutter_dir.to_string_lossy().as_ref(),
-        )?;
+    /// Next to the bundle rather than in it, as the bundle is synced to the remote as is.
+    fn get_bundle_info_path(&self) -> ResultWithError<PathBuf> {
+        Ok(utils::dir::DirUtils::curr_dir()?
+            .join(self.get_bundle_dir())
+            .with_file_name(BUNDLE_INFO_FILE))
+    }
 
-        Ok(())
+    fn get_bundle_dir(&self) -> PathBuf {
+        PathBuf::from("build")
+            .join("linux")
+            .join("x64")
+            .join(BUILD_MODE)
+            .join("bundle")
     }
 
-    fn sync_linux(&self, remote: &RemoteInfo, root_dir: &str, exec_dir: &Path) -> EmptyResult {
-        info!("Syncing linux to remote...");
+    /// Directories synced to the remote before running, as `(description, local, remote)`.
+    fn get_sync_dirs(
+        &self,
+        exec_dir: &Path,
+        opts: &DriveOptions,
+    ) -> ResultWithError<Vec<(&'static str, PathBuf, PathBuf)>> {
+        let curr_dir = utils::dir::DirUtils::curr_dir()?;
+        let mut dirs = vec![];
+
+        if opts.is_linux_desktop() {
+            let bundle_dir = self.get_bundle_dir();
+            dirs.push((
+                "build",
+                curr_dir.join(&bundle_dir),
+                exec_dir.join(&bundle_dir),
+            ));
+        }
 
-        let local_flutter_dir = utils::dir::DirUtils::curr_dir()?.join("linux");
-        let remote_flutter_dir = exec_dir.join("linux");
+        for (desc, dir) in [
+            ("integration tests", "integration_test"),
+            ("test_driver", "test_driver"),
+            ("linux", "linux"),
+        ] {
+            dirs.push((desc, curr_dir.join(dir), exec_dir.join(dir)));
+        }
 
-        utils::command::CommandUtils::sync_dir_to_remote(
-            remote,
-            root_dir,
-            local_flutter_dir.to_string_lossy().as_ref(),
-            remote_flutter_dir.to_string_lossy().as_ref(),
-        )?;
+        // The default output dir is synced with the integration tests
+        if !opts.output_dir.starts_with("integration_test") {
+            dirs.push((
+                "generated tests",
+                curr_dir.join(&opts.output_dir),
+                exec_dir.join(&opts.output_dir),
+            ));
+        }
 
-        Ok(())
+        Ok(dirs)
     }
 
     fn cleaned_pubspec(&self, content: &str) -> ResultWithError<String> {
@@ -259,10 +711,16 @@ impl RunFlutter {
         ctx: &HookContext<'_, AppState>,
         exec_dir: &PathBuf,
         root_dir: &str,
+        opts: &DriveOptions,
         features: &[FeatureTest],
     ) -> EmptyResult {
-        let child = self.spawn_flutter_command(exec_dir, root_dir)?;
-        self.process_output(ctx, child, features).await
+        let child = self.spawn_flutter_command(ctx, exec_dir, root_dir, opts)?;
+        let pgid = child.id();
+        CommandUtils::track_process_group("flutter drive", pgid)?;
+
+        let res = self.process_output(ctx, child, features).await;
+        CommandUtils::untrack_process_group(pgid)?;
+        res
     }
 
     async fn execute_remote(
@@ -271,44 +729,139 @@ impl RunFlutter {
         remote: &RemoteInfo,
         exec_dir: &Path,
         root_dir: &str,
+        opts: &DriveOptions,
         features: &[FeatureTest],
     ) -> EmptyResult {
         info!("Executing tests remotely via SSH...\n");
 
-        let cmd = format!(
-            "cd {} && {}",
-            exec_dir.display(),
-            self.get_flutter_drive_command_str(root_dir)?,
+        let cmd = self.get_remote_command_str(ctx, exec_dir, root_dir, opts, false)?;
+        info!(
+            "Remote command: {}\n",
+            self.get_remote_command_str(ctx, exec_dir, root_dir, opts, true)?
         );
-        info!("Remote command: {}\n", cmd);
 
-        let output = remote.exec_remote_stream(&cmd)?;
-        self.process_remote_output(ctx, output, features).await
+        let pid_file = CommandUtils::remote_pid_file(root_dir);
+        CommandUtils::track_remote_cmd("flutter drive", &pid_file, remote.clone())?;
+
+        let output =
+            remote.exec_remote_stream(&CommandUtils::with_remote_pid_file(&cmd, &pid_file))?;
+        let res = self.process_remote_output(ctx, output, features).await;
+        CommandUtils::untrack_remote_cmd(&pid_file)?;
+
+        // Captures are kept even when tests fail, as they are mostly useful to debug failures
+        if let Err(err) = self.pull_captures(remote, exec_dir, root_dir) {
+            error!("Failed to pull screenshot captures from remote: {}", err);
+        }
+
+        res
     }
 
-    fn spawn_flutter_command(&self, exec_dir: &PathBuf, root_dir: &str) -> ResultWithError<Child> {
-        let mut command = Command::new("sh");
-        command
+    /// Copies the screenshots saved by `capture` steps on the remote back to the local project.
+    fn pull_captures(&self, remote: &RemoteInfo, exec_dir: &Path, root_dir: &str) -> EmptyResult {
+        let captures_dir = PathBuf::from("integration_test")
+            .join("screenshots")
+            .join("captures");
+        let remote_dir = exec_dir.join(&captures_dir);
+        let remote_dir = remote_dir.to_string_lossy();
+
+        let exists = utils::command::CommandUtils::run_command_str(
+            &format!("test -d {}", remote_dir),
+            Some(remote),
+            root_dir,
+        )?;
+        if exists.status != 0 {
+            return Ok(());
+        }
+
+        let local_dir = utils::dir::DirUtils::curr_dir()?.join(&captures_dir);
+        info!("Pulling screenshot captures to {}...", local_dir.display());
+        utils::command::CommandUtils::sync_dir_from_remote(
+            remote,
+            &remote_dir,
+            local_dir.to_string_lossy().as_ref(),
+        )
+    }
+
+    fn spawn_flutter_command(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        exec_dir: &PathBuf,
+        root_dir: &str,
+        opts: &DriveOptions,
+    ) -> ResultWithError<Child> {
+        let drive_cmd = self.get_flutter_drive_command_str(ctx, root_dir, opts, "")?;
+        let mut command = Command::new(CommandUtils::shell_program());
+        CommandUtils::in_own_process_group(&mut command)
             .current_dir(exec_dir)
-            .args(["-c", &self.get_flutter_drive_command_str(root_dir)?])
+            .args(["-c", &drive_cmd])
             .env("DISPLAY", OsUtils::get_display())
+            .envs(opts.env.iter().map(|(key, value)| (key, value)))
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        info!("Spawning local command: {:?}\n", command);
+        // Not the command's Debug output, which would show the env values
+        info!(
+            "Spawning local command: DISPLAY={} {}{} -c {:?}\n",
+            OsUtils::get_display(),
+            self.get_env_prefix(opts, true)?,
+            CommandUtils::shell_program(),
+            drive_cmd
+        );
 
         Ok(command.spawn()?)
     }
 
-    fn get_flutter_drive_command_str(&self, root_dir: &str) -> ResultWithError<String> {
-        let binary_name = FlutterUtils::get_name()?;
+    fn get_remote_command_str(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        exec_dir: &Path,
+        root_dir: &str,
+        opts: &DriveOptions,
+        redacted: bool,
+    ) -> ResultWithError<String> {
+        let env_prefix = self.get_env_prefix(opts, redacted)?;
+        Ok(format!(
+            "cd {} && {}",
+            exec_dir.display(),
+            self.get_flutter_drive_command_str(ctx, root_dir, opts, &env_prefix)?,
+        ))
+    }
 
-        let binary = format!("build/linux/x64/debug/bundle/{binary_name}");
-        let binary_arg = format!("--use-application-binary={binary}");
-        let args = format!(
-            "--driver=test_driver/integration_test.dart --target=integration_test/generated/all_tests.dart {binary_arg} --no-headless -d linux"
+    fn get_flutter_drive_command_str(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        root_dir: &str,
+        opts: &DriveOptions,
+        env_prefix: &str,
+    ) -> ResultWithError<String> {
+        let mut args = format!(
+            "--driver=test_driver/integration_test.dart --target={}",
+            opts.target
         );
 
-        CommandUtils::with_env_source(root_dir, &format!("flutter drive {args}"))
+        // Only the Linux desktop uses the prebuilt bundle
+        if opts.is_linux_desktop() {
+            let binary_name = FlutterUtils::get_name()?;
+            let binary = format!("build/linux/x64/{BUILD_MODE}/bundle/{binary_name}");
+            args.push_str(&format!(" --use-application-binary={binary} --no-headless"));
+        }
+
+        args.push_str(&format!(" -d {}", opts.device));
+
+        if self.should_update_screenshots(ctx) {
+            args.push_str(" --dart-define=UPDATE_SCREENSHOTS=true");
+        }
+
+        CommandUtils::with_env_source(root_dir, &format!("{env_prefix}flutter drive {args}"))
+    }
+
+    fn should_update_screenshots(&self, ctx: &HookContext<'_, AppState>) -> bool {
+        matches!(
+            ctx.args.command,
+            args::Command::Run {
+                update_screenshots: true,
+                ..
+            }
+        )
     }
 
     async fn process_output(
@@ -320,30 +873,62 @@ impl RunFlutter {
         let stdout = child.stdout.take().unwrap();
         let reader = BufReader::new(stdout);
 
+        // Forward stderr on its own thread so it shows up in real time and neither pipe
+        // buffer can fill up and block the flutter process
+        let stderr = child.stderr.take().unwrap();
+        let stderr_reader = thread::spawn(move || {
+            let mut collected = String::new();
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                if LoggerUtils::is_json() {
+                    warn!("[Flutter Error Log] {line}");
+                } else {
+                    eprintln!("{STDERR_TAG} {line}");
+                }
+                collected.push_str(&line);
+                collected.push('\n');
+            }
+            collected
+        });
+
         let res = self.process_lines(ctx, reader.lines(), features).await;
         let output = child
             .wait_with_output()
             .auto_err("Failed to wait for child process when running flutter tests")?;
+        let stderr_output = stderr_reader.join().unwrap_or_default();
         let status = output.status;
-        if res.is_ok() && !status.success() {
+        // Failed tests make flutter exit with an error too, they are reported in the results
+        if res.is_ok() && !status.success() && ctx.get_results()?.failed == 0 {
             error!(
                 "❌ Error when running tests, status:{}, output:{}, error:{}",
                 status,
                 String::from_utf8_lossy(&output.stdout),
-                String::from_utf8_lossy(&output.stderr)
+                stderr_output
             );
             return Err("Error during tests".into());
         }
         res
     }
 
-    async fn process_remote_output<I: Iterator<Item = String>>(
+    async fn process_remote_output(
         &self,
         ctx: &HookContext<'_, AppState>,
-        lines: I,
+        mut lines: RemoteLineIterator,
         features: &[FeatureTest],
     ) -> EmptyResult {
-        self.process_lines(ctx, lines.map(Ok), features).await
+        let res = self
+            .process_lines(ctx, lines.by_ref().map(Ok), features)
+            .await;
+
+        match lines.exit_code() {
+            Some(code) if res.is_ok() && code != 0 && ctx.get_results()?.failed == 0 => {
+                error!("❌ Error when running tests remotely, exit code:{}", code);
+                return Err("Error during tests".into());
+            }
+            None => warn!("Could not retrieve the exit code of the remote tests"),
+            _ => {}
+        }
+
+        res
     }
 
     async fn process_lines(
@@ -354,8 +939,10 @@ impl RunFlutter {
     ) -> EmptyResult {
         let start_time = chrono::Utc::now();
         let mut curr_test_start_timestamp = start_time.timestamp();
+        let mut curr_test_start = Instant::now();
         let mut passed = 0;
         let mut failed = 0;
+        let mut skipped = 0;
 
         let mut current_test: Option<String> = None;
         let mut test_spinner: Option<indicatif::ProgressBar> = None;
@@ -371,14 +958,33 @@ impl RunFlutter {
             line = line.trim().to_string();
             full_test_output.push_str(format!("{}\n", line).as_str());
 
-            if line.is_empty()
-                || line.contains("Some tests failed")
+            if line.is_empty() {
+                // Blank lines separate the parts of the exception blocks
+                if collecting_output {
+                    current_test_output.push('\n');
+                }
+                continue;
+            }
+
+            if line.contains("Some tests failed")
                 || line.contains("All tests passed")
                 || line.contains("VMServiceFlutterDriver")
             {
                 continue;
             }
 
+            if let Some(input_name) = DbusUtils::identify_continue_request(&line)
+                && let Some(step) = DbusUtils::identify_step_pause(&input_name)
+            {
+                if let Err(err) = self.process_step_pause(ctx, step, &mut test_spinner) {
+                    error!(
+                        "Error answering the pause before step '{}': {:?}",
+                        step, err
+                    );
+                }
+                continue;
+            }
+
             if let Some(input_name) = DbusUtils::identify_continue_request(&line)
                 && let Some(curr_test) = current_test.as_ref()
             {
@@ -406,12 +1012,16 @@ impl RunFlutter {
 
             // detect test progress lines, e.g. "00:06 +0: TestName" or "+0 -1:"
             if let Some((_, rest)) = line.split_once(' ') {
+                // The failing test's progress line, kept in its output next to the exception
                 if rest.contains("[E]") {
+                    if collecting_output {
+                        current_test_output.push_str(format!("{}\n", line).as_str());
+                    }
                     continue;
                 }
 
                 if rest.starts_with('+') && rest.contains(':') {
-                    // Example: "+0 -1: TestName"
+                    // Example: "+0 ~1 -1: TestName", where "~" counts skipped tests
                     // Extract counters and test name
                     let mut parts = rest.splitn(2, ':');
                     let counters = parts.next().unwrap_or_default().trim();
@@ -422,12 +1032,7 @@ impl RunFlutter {
                     }
                     prev_test_names.insert(test_name.to_owned());
 
-                    let counts: Vec<i16> = counters
-                        .split_whitespace()
-                        .map(|s| s.parse::<i16>().unwrap_or(0).abs())
-                        .collect();
-                    let curr_passed = counts.first().cloned().unwrap_or(0);
-                    let curr_failed = counts.get(1).cloned().unwrap_or(0);
+                    let curr = ProgressCounters::parse(counters);
 
                     // finish spinner if previous test was running
                     if let Some(ts) = test_spinner.take() {
@@ -435,10 +1040,11 @@ impl RunFlutter {
                     }
 
                     // Failed
-                    if curr_failed != failed
+                    if curr.failed != failed
                         && let Some(test_name) = current_test.as_ref()
                     {
                         failed += 1;
+                        self.record_duration(ctx, test_name, curr_test_start)?;
                         self.handle_test_failed(
                             ctx,
                             test_name,
@@ -447,11 +1053,24 @@ impl RunFlutter {
                         )?;
                     }
 
+                    // Skipped
+                    if curr.skipped != skipped
+                        && let Some(test_name) = current_test.as_ref()
+                    {
+                        skipped += 1;
+                        self.handle_test_skipped(
+                            ctx,
+                            test_name,
+                            self.find_feature_test_skip_reason(features, test_name),
+                        )?;
+                    }
+
                     // Passed
-                    if curr_passed != passed
+                    if curr.passed != passed
                         && let Some(test_name) = current_test.as_ref()
                     {
                         passed += 1;
+                        self.record_duration(ctx, test_name, curr_test_start)?;
                         self.handle_test_passed(ctx, test_name)?;
                     }
 
@@ -462,6 +1081,7 @@ impl RunFlutter {
                     }
 
                     curr_test_start_timestamp = chrono::Utc::now().timestamp();
+                    curr_test_start = Instant::now();
                     current_test = Some(test_name.to_string());
                     test_spinner = Some(utils::command::CommandUtils::display_loader(format!(
                         "Running: {}",
@@ -474,7 +1094,7 @@ impl RunFlutter {
                 }
             }
 
-            if collecting_output && !line.is_empty() {
+            if collecting_output {
                 current_test_output.push_str(format!("{}\n", line).as_str());
             }
         }
@@ -483,7 +1103,7 @@ impl RunFlutter {
             ts.finish_and_clear();
         }
 
-        let total = passed + failed;
+        let total = passed + failed + skipped;
         ctx.set_results_total(total)?;
 
         let end_time = chrono::Utc::now();
@@ -491,14 +1111,58 @@ impl RunFlutter {
 
         ctx.set_results_full_log(full_test_output)?;
 
-        println!();
+        if !LoggerUtils::is_json() {
+            println!();
+        }
         info!("🎉 All tests completed");
-        info!("✅ Passed: {passed}  ❌ Failed: {failed}  📋 Total: {total}");
+        info!("✅ Passed: {passed}  ❌ Failed: {failed}  ⏭️ Skipped: {skipped}  📋 Total: {total}");
+
+        Ok(())
+    }
+
+    /// Asks the operator whether to run the next step in interactive mode, and answers the
+    /// test over DBus.
+    fn process_step_pause(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        step: &str,
+        test_spinner: &mut Option<ProgressBar>,
+    ) -> EmptyResult {
+        defer! {
+            if let Some(spinner) = test_spinner.as_ref() {
+                spinner.enable_steady_tick(Duration::from_millis(80));
+            }
+        }
 
-        if failed > 0 {
-            return Err("Some tests failed".into());
+        if let Some(spinner) = test_spinner.as_ref() {
+            spinner.disable_steady_tick();
         }
 
+        let answer = OsUtils::ask_with_timeout(
+            &format!(
+                "\n⏸️  Next step {}\n   [Enter] run, [s] skip, [a] abort: ",
+                step
+            ),
+            None,
+        )?;
+        let action = match answer.to_lowercase().as_str() {
+            "s" | "skip" => "skip",
+            "a" | "abort" => {
+                warn!("Aborting, the remaining tests fail right away");
+                "abort"
+            }
+            _ => "run",
+        };
+
+        let remote = ctx.get_remote_info()?;
+        let root_dir = ctx.get_root_dir()?;
+        CommandUtils::run_command_str(
+            &DbusUtils::dbus_method_continue_cmd(action),
+            remote.as_ref(),
+            &root_dir,
+        )?;
+        debug!("Sent DBus continue command: {}", action);
+
         Ok(())
     }
 
@@ -593,7 +1257,12 @@ impl RunFlutter {
                 input_name
             );
 
-            OsUtils::ask(&format!("User input requested for {}:", input_name))
+            ctx.args
+                .ensure_can_prompt(
+                    &format!("User input {}", input_name),
+                    "read it from an email with the gmail option of the user_input step",
+                )
+                .and_then(|_| OsUtils::ask(&format!("User input requested for {}:", input_name)))
                 .map_err(|err| {
                     error!("Error during prompting for user input, err:{err:?}");
                     err
@@ -622,8 +1291,7 @@ impl RunFlutter {
 
         debug!("Creating IMAP Gmail client for user input retrieval");
 
-        // Replace any environment variables in the app password
-        let app_password = ctx.vars.replace_var(&imap_config.app_password, None);
+        let app_password = imap_config.resolve_app_password(ctx.vars);
 
         Some(ImapGmailClient::new(
             imap_config.email.clone(),
@@ -638,20 +1306,20 @@ impl RunFlutter {
 
         debug!("Creating Gmail client for user input retrieval");
 
-        Some(GmailClient::new(
-            ctx.config
-                .gmail
-                .credentials
-                .s3
-                .as_ref()
-                .map(|s| s.bucket.clone()),
-            ctx.config
-                .gmail
-                .credentials
-                .s3
-                .as_ref()
-                .map(|s| s.key_prefix.clone()),
-        ))
+        Some(GmailClient::new(ctx.config.gmail.credentials.s3.clone()))
+    }
+
+    /// Keeps the test duration for `--timings-file`, iterations of `--repeat` under the same name.
+    fn record_duration(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        test_name: &str,
+        start: Instant,
+    ) -> EmptyResult {
+        ctx.add_results_duration(
+            FeatureTest::strip_repeat_suffix(test_name),
+            start.elapsed().as_millis() as u64,
+        )
     }
 
     fn handle_test_passed(&self, ctx: &HookContext<'_, AppState>, test_name: &str) -> EmptyResult {
@@ -660,6 +1328,20 @@ impl RunFlutter {
         Ok(())
     }
 
+    fn handle_test_skipped(
+        &self,
+        ctx: &HookContext<'_, AppState>,
+        test_name: &str,
+        reason: Option<String>,
+    ) -> EmptyResult {
+        match reason {
+            Some(reason) => info!("⏭️ Skipped: {} ({})", test_name, reason),
+            None => info!("⏭️ Skipped: {}", test_name),
+        }
+        ctx.increment_results_skipped()?;
+        Ok(())
+    }
+
     fn handle_test_failed(
         &self,
         ctx: &HookContext<'_, AppState>,
@@ -698,6 +1380,13 @@ impl RunFlutter {
             .collect::<Vec<_>>()
             .join("\n");
 
+        if let Some(exception) = FlutterUtils::extract_exception(&cleaned_output) {
+            error!("Exception:");
+            for line in exception.lines() {
+                error!("    {}", line);
+            }
+        }
+
         match fs::write(&log_path, &cleaned_output) {
             Ok(_) => {
                 error!("📄 Test output saved to: {}", log_path.display());
@@ -713,6 +1402,11 @@ impl RunFlutter {
             }
         }
 
+        if FlutterUtils::is_setup_failure(&cleaned_output) {
+            error!("The before_each steps failed, the test steps didn't run");
+            ctx.increment_results_setup_failed()?;
+        }
+
         ctx.increment_results_failed()?;
         Ok(())
     }
@@ -725,7 +1419,7 @@ impl RunFlutter {
         features.iter().find_map(|f| {
             f.tests.iter().find_map(|t| {
                 let joined = format!("{} - {}", f.name, t.name);
-                if full_test_name == joined {
+                if FeatureTest::strip_repeat_suffix(full_test_name) == joined {
                     Some(t.description.clone())
                 } else {
                     None
@@ -734,6 +1428,23 @@ impl RunFlutter {
         })
     }
 
+    fn find_feature_test_skip_reason(
+        &self,
+        features: &[FeatureTest],
+        full_test_name: &str,
+    ) -> Option<String> {
+        features.iter().find_map(|f| {
+            f.tests.iter().find_map(|t| {
+                let joined = format!("{} - {}", f.name, t.name);
+                if FeatureTest::strip_repeat_suffix(full_test_name) == joined {
+                    t.skip_reason().map(str::to_owned)
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
     fn find_feature_test_gmail_config(
         &self,
         features: &[FeatureTest],
@@ -747,7 +1458,7 @@ impl RunFlutter {
                 f.tests.iter().find_map(|t| {
                     let joined = format!("{} - {}", f.name, t.name);
                     debug!("Comparing with test name: {}", joined);
-                    if full_test_name == joined {
+                    if FeatureTest::strip_repeat_suffix(full_test_name) == joined {
                         t.steps.iter().find_map(|s| {
                             if let crate::models::feature_test::Step::UserInput { user_input } = s {
                                 if user_input.name == user_input_name {
@@ -768,3 +1479,196 @@ impl RunFlutter {
             .flatten()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use clap::Parser as _;
+
+    use super::*;
+    use crate::models::{args::AppArgs, config::Config, vars::Vars};
+
+    fn bundle_info(target: &str, source_hash: Option<&str>) -> BundleInfo {
+        BundleInfo {
+            target: target.to_owned(),
+            source_hash: source_hash.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn progress_counters_include_the_skipped_tests() {
+        assert_eq!(
+            ProgressCounters::parse("+0 ~1"),
+            ProgressCounters {
+                passed: 0,
+                skipped: 1,
+                failed: 0,
+            }
+        );
+        assert_eq!(
+            ProgressCounters::parse("+3 ~1 -2"),
+            ProgressCounters {
+                passed: 3,
+                skipped: 1,
+                failed: 2,
+            }
+        );
+        assert_eq!(
+            ProgressCounters::parse("+5"),
+            ProgressCounters {
+                passed: 5,
+                ..Default::default()
+            }
+        );
+        // Unknown or malformed counters are ignored
+        assert_eq!(
+            ProgressCounters::parse("~x ?2"),
+            ProgressCounters::default()
+        );
+    }
+
+    #[test]
+    fn bundle_matches_the_tests_it_was_built_with() {
+        let built = bundle_info("integration_test/generated/all_tests.dart", Some("abc"));
+        let current = bundle_info("integration_test/generated/all_tests.dart", Some("abc"));
+        assert_eq!(built.mismatch(&current), None);
+    }
+
+    #[test]
+    fn bundle_built_for_another_target_mismatches() {
+        let built = bundle_info("integration_test/generated/all_tests.dart", Some("abc"));
+        let current = bundle_info("integration_test/generated/login_test.dart", Some("abc"));
+        assert!(
+            built
+                .mismatch(&current)
+                .is_some_and(|m| m.contains("login_test.dart"))
+        );
+    }
+
+    #[test]
+    fn bundle_built_from_older_tests_mismatches() {
+        let built = bundle_info("integration_test/generated/all_tests.dart", Some("abc"));
+        let current = bundle_info("integration_test/generated/all_tests.dart", Some("def"));
+        assert!(built.mismatch(&current).is_some());
+    }
+
+    /// Runs `f` with a run context of the given args and config, rooted in a temp dir whose
+    /// env file replaces `flutter` with a function printing the env vars it sees.
+    fn with_fake_flutter<R>(
+        args: &[&str],
+        config: &str,
+        f: impl FnOnce(&HookContext<'_, AppState>, &str) -> R,
+    ) -> R {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_dir_str = root_dir.path().to_string_lossy().to_string();
+        fs::write(
+            CommandUtils::env_file(&root_dir_str),
+            "flutter() { printf '%s|%s\\n' \"$API_URL\" \"$FLAG\"; }\n",
+        )
+        .unwrap();
+
+        let args = AppArgs::parse_from(args);
+        let config: Config = serde_yaml::from_str(config).unwrap();
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        };
+        let ctx = HookContext {
+            args: &args,
+            config: &config,
+            vars: &vars,
+            state: Arc::new(RwLock::new(AppState::default())),
+        };
+        f(&ctx, &root_dir_str)
+    }
+
+    fn drive_options(env: Vec<(String, String)>) -> DriveOptions {
+        DriveOptions {
+            target: "integration_test/generated/all_tests.dart".to_owned(),
+            device: "emulator-5554".to_owned(),
+            build_command: None,
+            output_dir: PathBuf::from("integration_test/generated"),
+            no_build: true,
+            env,
+        }
+    }
+
+    const RUN_ENV_CONFIG: &str = "project_type: flutter\nrun_env: { API_URL: \"https://config.example.com\", FLAG: \"it's on\" }";
+
+    #[test]
+    fn cli_env_overrides_the_run_env_config() {
+        with_fake_flutter(
+            &[
+                "playmaster",
+                "run",
+                "--env",
+                "API_URL=https://cli.example.com",
+            ],
+            RUN_ENV_CONFIG,
+            |ctx, _| {
+                let mut env = RunFlutter.get_run_env(ctx).unwrap();
+                env.sort();
+                assert_eq!(
+                    env,
+                    [
+                        ("API_URL".to_owned(), "https://cli.example.com".to_owned()),
+                        ("FLAG".to_owned(), "it's on".to_owned()),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn run_env_reaches_the_local_drive_command() {
+        with_fake_flutter(
+            &[
+                "playmaster",
+                "run",
+                "--env",
+                "API_URL=https://cli.example.com",
+            ],
+            RUN_ENV_CONFIG,
+            |ctx, root_dir| {
+                let opts = drive_options(RunFlutter.get_run_env(ctx).unwrap());
+
+                let output = RunFlutter
+                    .spawn_flutter_command(ctx, &PathBuf::from(root_dir), root_dir, &opts)
+                    .unwrap()
+                    .wait_with_output()
+                    .unwrap();
+
+                assert_eq!(
+                    String::from_utf8_lossy(&output.stdout),
+                    "https://cli.example.com|it's on\n"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn run_env_reaches_the_remote_drive_command_redacted_in_logs() {
+        with_fake_flutter(&["playmaster", "run"], RUN_ENV_CONFIG, |ctx, root_dir| {
+            let mut env = RunFlutter.get_run_env(ctx).unwrap();
+            env.sort();
+            let opts = drive_options(env);
+            let exec_dir = Path::new(root_dir);
+
+            let cmd = RunFlutter
+                .get_remote_command_str(ctx, exec_dir, root_dir, &opts, false)
+                .unwrap();
+            let output = Command::new("sh").arg("-c").arg(&cmd).output().unwrap();
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                "https://config.example.com|it's on\n"
+            );
+
+            let logged = RunFlutter
+                .get_remote_command_str(ctx, exec_dir, root_dir, &opts, true)
+                .unwrap();
+            assert!(!logged.contains("config.example.com"), "{logged}");
+            assert!(!logged.contains("it's on"), "{logged}");
+        });
+    }
+}
@@ -0,0 +1,312 @@
+use std::path::Path;
+
+use tracing::{error, info};
+
+use crate::{
+    models::{
+        args::AppArgs,
+        config::{Config, S3Config, SinkConfig},
+        feature_test::FeatureTest,
+        vars::Vars,
+    },
+    utils::{
+        command::CommandUtils, dir::DirUtils, errors::EmptyResult, logger::LoggerUtils,
+        os::OsUtils, s3::S3Utils, semver::SemverUtils,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "✅ PASS",
+            // The warning sign is narrower than it renders, pad it to keep the columns aligned
+            CheckStatus::Warn => "⚠️  WARN",
+            CheckStatus::Fail => "❌ FAIL",
+        }
+    }
+
+    /// Plain status for the JSON logs.
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "pass",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "fail",
+        }
+    }
+}
+
+struct Check {
+    name: String,
+    status: CheckStatus,
+    details: String,
+}
+
+impl Check {
+    fn new(name: &str, status: CheckStatus, details: impl Into<String>) -> Self {
+        Self {
+            name: name.to_owned(),
+            status,
+            details: details.into(),
+        }
+    }
+}
+
+/// Tools PlayMaster itself runs, as `(name, version command, required)`.
+/// Optional ones are only needed in remote mode or for user input over D-Bus.
+const TOOLS: [(&str, &str, bool); 5] = [
+    ("flutter", "flutter --version", true),
+    ("dart", "dart --version", true),
+    ("rsync", "rsync --version", false),
+    ("sshpass", "sshpass -V", false),
+    ("busctl", "busctl --version", false),
+];
+
+/// Checks the local environment PlayMaster needs and prints a pass/warn/fail table.
+pub struct Doctor {
+    args: AppArgs,
+}
+
+impl Doctor {
+    pub fn new(args: AppArgs) -> Self {
+        Self { args }
+    }
+
+    pub async fn execute(&self) -> EmptyResult {
+        info!("Checking the environment...");
+        let root_dir = DirUtils::root_dir(None)?.to_string_lossy().to_string();
+
+        let mut checks = vec![];
+        for (name, cmd, required) in TOOLS {
+            checks.push(self.check_tool(name, cmd, required, &root_dir));
+        }
+        checks.push(self.check_display());
+
+        match Config::from_curr_dir(self.args.version_check) {
+            Ok(config) => {
+                checks.push(Check::new(
+                    "config",
+                    CheckStatus::Pass,
+                    "playmaster.yaml is valid",
+                ));
+                checks.push(self.check_feature_tests(&config));
+                checks.extend(self.check_dependencies(&config, &root_dir));
+                checks.extend(self.check_cloud_credentials(&config).await);
+            }
+            Err(err) => checks.push(Check::new("config", CheckStatus::Fail, err.to_string())),
+        }
+
+        self.print_table(&checks);
+
+        let failed = checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Fail)
+            .count();
+        if failed > 0 {
+            error!("{} check(s) failed", failed);
+            return Err(format!("{} doctor check(s) failed", failed).into());
+        }
+
+        info!("All required checks passed");
+        Ok(())
+    }
+
+    fn check_tool(&self, name: &str, cmd: &str, required: bool, root_dir: &str) -> Check {
+        let missing_status = if required {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Warn
+        };
+
+        match CommandUtils::run_command_str(cmd, None, root_dir) {
+            Ok(res) if res.status == 0 => {
+                let output = if res.stdout.trim().is_empty() {
+                    res.stderr
+                } else {
+                    res.stdout
+                };
+                let version = output
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .unwrap_or_default();
+                Check::new(name, CheckStatus::Pass, version)
+            }
+            Ok(res) if res.status == 127 => Check::new(name, missing_status, "not found"),
+            Ok(res) => Check::new(
+                name,
+                missing_status,
+                format!("`{}` exited with status {}", cmd, res.status),
+            ),
+            Err(err) => Check::new(name, missing_status, err.to_string()),
+        }
+    }
+
+    fn check_display(&self) -> Check {
+        let display = OsUtils::get_display();
+        let number = display
+            .trim_start_matches(':')
+            .split('.')
+            .next()
+            .unwrap_or_default();
+        let socket = format!("/tmp/.X11-unix/X{}", number);
+
+        if Path::new(&socket).exists() {
+            Check::new(
+                "display",
+                CheckStatus::Pass,
+                format!("X server on {}", display),
+            )
+        } else {
+            Check::new(
+                "display",
+                CheckStatus::Warn,
+                format!(
+                    "No X server on {} ({} missing), needed to run the tests locally",
+                    display, socket
+                ),
+            )
+        }
+    }
+
+    fn check_feature_tests(&self, config: &Config) -> Check {
+        let features = FeatureTest::all_from_curr_dir(
+            &config.test_dirs,
+            self.args.version_check,
+            self.args.validate_schema,
+        );
+        let vars = Vars::all_from_curr_dir(&config.test_dirs);
+
+        match (features, vars) {
+            (Ok(features), Ok(vars)) => {
+                let tests = features.iter().map(|f| f.tests.len()).sum::<usize>();
+                Check::new(
+                    "feature tests",
+                    CheckStatus::Pass,
+                    format!(
+                        "{} features, {} tests, {} vars files",
+                        features.len(),
+                        tests,
+                        vars.data.len()
+                    ),
+                )
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                Check::new("feature tests", CheckStatus::Fail, err.to_string())
+            }
+        }
+    }
+
+    /// Dependencies of the config, checked locally, they are only installed when running.
+    fn check_dependencies(&self, config: &Config, root_dir: &str) -> Vec<Check> {
+        config
+            .dependencies
+            .iter()
+            .map(|dep| {
+                let name = format!("dependency {}", dep.name);
+                let output =
+                    match CommandUtils::run_command_str(&dep.version_command, None, root_dir) {
+                        Ok(res) => res.stdout.trim().to_owned(),
+                        Err(err) => return Check::new(&name, CheckStatus::Warn, err.to_string()),
+                    };
+
+                match SemverUtils::is_version_greater_or_equal(&dep.min_version, &output) {
+                    Ok(true) => Check::new(
+                        &name,
+                        CheckStatus::Pass,
+                        format!("{} ≥ {}", output, dep.min_version),
+                    ),
+                    Ok(false) if dep.install.is_some() => Check::new(
+                        &name,
+                        CheckStatus::Warn,
+                        format!(
+                            "'{}' doesn't match {}, it will be installed by `run`",
+                            output, dep.min_version
+                        ),
+                    ),
+                    Ok(false) => Check::new(
+                        &name,
+                        CheckStatus::Fail,
+                        format!(
+                            "'{}' doesn't match {} and there is no install spec",
+                            output, dep.min_version
+                        ),
+                    ),
+                    Err(err) => Check::new(&name, CheckStatus::Fail, err.to_string()),
+                }
+            })
+            .collect()
+    }
+
+    /// AWS credentials, only checked when logs are uploaded to S3 or Gmail credentials are read from it.
+    async fn check_cloud_credentials(&self, config: &Config) -> Vec<Check> {
+        let mut s3_configs: Vec<(&str, &S3Config)> = vec![];
+        for webhook in &config.webhooks {
+            if let Some(s3_config) = &webhook.s3_config {
+                s3_configs.push(("S3 logs upload", s3_config));
+            }
+        }
+        for sink in &config.results.sinks {
+            match sink {
+                SinkConfig::Webhook(webhook) => {
+                    if let Some(s3_config) = &webhook.s3_config {
+                        s3_configs.push(("S3 logs upload", s3_config));
+                    }
+                }
+            }
+        }
+        if config.gmail.enabled
+            && let Some(s3_config) = &config.gmail.credentials.s3
+        {
+            s3_configs.push(("S3 Gmail credentials", s3_config));
+        }
+
+        let mut checks = vec![];
+        for (name, s3_config) in s3_configs {
+            let check = match S3Utils::check_credentials(s3_config).await {
+                Ok(details) => Check::new(
+                    name,
+                    CheckStatus::Pass,
+                    format!("bucket {}, {}", s3_config.bucket, details),
+                ),
+                Err(err) => Check::new(name, CheckStatus::Fail, err.to_string()),
+            };
+            checks.push(check);
+        }
+        checks
+    }
+
+    fn print_table(&self, checks: &[Check]) {
+        if LoggerUtils::is_json() {
+            for check in checks {
+                info!(
+                    check = check.name.as_str(),
+                    status = check.status.as_str(),
+                    "{}",
+                    check.details
+                );
+            }
+            return;
+        }
+
+        let name_width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+
+        println!();
+        for check in checks {
+            println!(
+                "{} {:<width$}  {}",
+                check.status.label(),
+                check.name,
+                check.details,
+                width = name_width
+            );
+        }
+        println!();
+    }
+}
@@ -18,23 +18,22 @@ use tracing::{debug, error, info};
 
 use crate::{
     gmail::s3_storage::S3TokenStorage,
+    models::config::S3Config,
     utils::{
         dir::DirUtils,
         errors::{EmptyResult, ResultWithError},
+        string::StringUtils,
     },
 };
 
 pub struct GmailClient {
-    pub s3_bucket: Option<String>,
-    pub s3_key_prefix: Option<String>,
+    /// S3 location of the stored token, stored locally when not set
+    pub s3: Option<S3Config>,
 }
 
 impl GmailClient {
-    pub fn new(s3_bucket: Option<String>, s3_key_prefix: Option<String>) -> Self {
-        GmailClient {
-            s3_bucket,
-            s3_key_prefix,
-        }
+    pub fn new(s3: Option<S3Config>) -> Self {
+        GmailClient { s3 }
     }
 
     pub async fn fetch_latest_email_matching_regex(
@@ -47,10 +46,7 @@ impl GmailClient {
         poll_interval_secs: u64,
     ) -> ResultWithError<String> {
         debug!("fetch_latest_email_matching_regex called");
-        debug!(
-            "S3 bucket: {:?}, S3 key_prefix: {:?}",
-            self.s3_bucket, self.s3_key_prefix
-        );
+        debug!("S3 config: {:?}", self.s3);
 
         //  Auth
         let secret = match self.get_secret().await {
@@ -109,10 +105,7 @@ impl GmailClient {
     /// Returns Ok(()) if credentials are valid, or an error if authentication is needed.
     pub async fn validate_credentials(&self) -> EmptyResult {
         debug!("Validating Gmail credentials");
-        debug!(
-            "S3 bucket: {:?}, S3 key_prefix: {:?}",
-            self.s3_bucket, self.s3_key_prefix
-        );
+        debug!("S3 config: {:?}", self.s3);
 
         // Check if we have a stored token with a refresh token
         if let Some(storage) = self.get_storage().await {
@@ -148,23 +141,43 @@ impl GmailClient {
             }
             Err(_) => {
                 info!("Gmail credentials not found or expired, initiating authentication flow");
-                self.generate_refresh_token().await
+                let storage = self.generate_refresh_token().await?;
+                info!("Gmail token stored in {}", storage);
+                Ok(())
             }
         }
     }
 
-    pub async fn generate_refresh_token(&self) -> EmptyResult {
+    /// Authorizes the Gmail access and stores the tokens, returning where they are stored.
+    pub async fn generate_refresh_token(&self) -> ResultWithError<String> {
         let secret = self.get_secret().await?;
         let auth = self.get_flow(secret).await?;
 
         let token = auth
             .token(&["https://www.googleapis.com/auth/gmail.readonly"])
             .await?;
-        let token = token.token();
+        debug!(
+            "Access token: {}",
+            StringUtils::redact(token.token().unwrap_or_default())
+        );
 
-        info!("ACCESS TOKEN: {:?}", token);
+        self.storage_location()
+    }
+
+    /// S3 URL or local path of the stored tokens.
+    fn storage_location(&self) -> ResultWithError<String> {
+        match &self.s3 {
+            Some(s3) => Ok(format!("s3://{}/{}", s3.bucket, Self::s3_key(s3))),
+            None => Ok(Self::local_token_path()?.display().to_string()),
+        }
+    }
 
-        Ok(())
+    fn s3_key(s3: &S3Config) -> String {
+        format!("{}/credentials.json", s3.key_prefix.trim_end_matches('/'))
+    }
+
+    fn local_token_path() -> ResultWithError<PathBuf> {
+        Ok(DirUtils::config_dir()?.join("gmail_token.json"))
     }
 
     fn build_query(
@@ -255,10 +268,7 @@ impl GmailClient {
         &self,
         secret: ApplicationSecret,
     ) -> ResultWithError<Authenticator<HttpsConnector<HttpConnector>>> {
-        debug!(
-            "get_flow: Creating authenticator, S3 bucket: {:?}",
-            self.s3_bucket
-        );
+        debug!("get_flow: Creating authenticator, S3 config: {:?}", self.s3);
         if let Some(storage) = self.get_storage().await {
             debug!("get_flow: Using S3 token storage");
             Ok(
@@ -272,8 +282,7 @@ impl GmailClient {
             )
         } else {
             debug!("get_flow: Using local token storage");
-            let token_path = DirUtils::config_dir()?;
-            let token_path = token_path.join("gmail_token.json");
+            let token_path = Self::local_token_path()?;
             debug!("get_flow: Local token path: {:?}", token_path);
 
             Ok(
@@ -289,9 +298,8 @@ impl GmailClient {
     }
 
     async fn get_storage(&self) -> Option<S3TokenStorage> {
-        if let (Some(bucket), Some(prefix)) = (&self.s3_bucket, &self.s3_key_prefix) {
-            let key = format!("{}/credentials.json", prefix.trim_end_matches('/'));
-            Some(S3TokenStorage::new(bucket.clone(), key).await)
+        if let Some(s3) = &self.s3 {
+            Some(S3TokenStorage::new(s3, Self::s3_key(s3)).await)
         } else {
             None
         }
@@ -1,10 +1,11 @@
 use std::sync::Arc;
 
-use aws_config::BehaviorVersion;
 use aws_sdk_s3::primitives::ByteStream;
 use google_gmail1::yup_oauth2::storage::{TokenInfo, TokenStorage};
 use tracing::{debug, error, info};
 
+use crate::{models::config::S3Config, utils::s3::S3Utils};
+
 pub struct S3TokenStorage {
     bucket: String,
     key: String,
@@ -12,13 +13,12 @@ pub struct S3TokenStorage {
 }
 
 impl S3TokenStorage {
-    pub async fn new(bucket: impl Into<String>, key: impl Into<String>) -> Self {
-        let bucket = bucket.into();
+    pub async fn new(s3_config: &S3Config, key: impl Into<String>) -> Self {
+        let bucket = s3_config.bucket.clone();
         let key = key.into();
         debug!("S3TokenStorage::new - bucket: {}, key: {}", bucket, key);
 
-        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-        let s3 = Arc::new(aws_sdk_s3::Client::new(&config));
+        let s3 = Arc::new(S3Utils::client(s3_config).await);
 
         Self { bucket, key, s3 }
     }
@@ -1,7 +1,6 @@
 use std::io::{Read as _, Write as _};
 
-use reqwest::blocking::Client;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{
     hooks::iface::{Hook, HookContext, HookType},
@@ -17,12 +16,15 @@ use crate::{
             r#trait::SourceProvider,
         },
         errors::{EmptyResult, ResultTrait as _, ResultWithError},
+        http::HttpUtils,
+        logger::LoggerUtils,
         os::{InstallType, OsUtils},
         semver::SemverUtils,
     },
 };
 
 /// Hook to check system dependencies as specified in the configuration.
+#[derive(Default)]
 pub struct HookCheckDependency {}
 
 impl HookCheckDependency {
@@ -41,6 +43,10 @@ impl HookCheckDependency {
             return Ok(());
         }
 
+        args.ensure_can_prompt(
+            &format!("Confirmation to install {}", install.tool),
+            "pass --yes to install the missing dependencies",
+        )?;
         let res = inquire::Select::new(
             format!("Do you want to install {} now?", install.tool).as_str(),
             vec!["Yes", "No"],
@@ -82,11 +88,16 @@ impl HookCheckDependency {
                 } else {
                     return Err("Invalid Bitbucket repo format, expected org/repo".into());
                 };
-                let provider = BitbucketSourceProvider::new(org, repo_name, Some(token.to_owned()));
+                let provider = BitbucketSourceProvider::new(
+                    org,
+                    repo_name,
+                    Some(token.to_owned()),
+                    ctx.config.proxy.as_deref(),
+                )?;
                 self.download_with_provider(provider, version)
             }
             crate::models::config::InstallSource::Url { url } => {
-                self.download_url(url, remote, &root_dir, version)
+                self.download_url(url, remote, &root_dir, version, ctx.config.proxy.as_deref())
             }
         }
     }
@@ -118,6 +129,7 @@ impl HookCheckDependency {
         remote: Option<&RemoteInfo>,
         root_dir: &str,
         version: Option<String>,
+        proxy: Option<&str>,
     ) -> ResultWithError<String> {
         let mut url = url.to_string();
         if let Some(version) = version {
@@ -128,18 +140,26 @@ impl HookCheckDependency {
         let dest_path = format!("/tmp/{}", filename);
 
         if let Some(remote) = remote {
-            let curl_cmd = format!("stdbuf -eL curl -L -o {} '{}'", dest_path, url);
+            let proxy_arg = proxy
+                .map(|proxy| format!("-x '{}' ", proxy))
+                .unwrap_or_default();
+            let curl_cmd = format!("stdbuf -eL curl -L {}-o {} '{}'", proxy_arg, dest_path, url);
             CommandUtils::run_command_str(&curl_cmd, Some(remote), root_dir)?;
         } else {
-            self.download_url_local_with_progress(&url, &dest_path)?;
+            self.download_url_local_with_progress(&url, &dest_path, proxy)?;
             info!("Downloaded artifact locally to {}", dest_path);
         }
 
         Ok(dest_path)
     }
 
-    fn download_url_local_with_progress(&self, url: &str, dest_path: &str) -> EmptyResult {
-        let client = Client::new();
+    fn download_url_local_with_progress(
+        &self,
+        url: &str,
+        dest_path: &str,
+        proxy: Option<&str>,
+    ) -> EmptyResult {
+        let client = HttpUtils::client(proxy)?;
         let mut response = client.get(url).send()?;
         let total_size = response.content_length().unwrap_or(0);
 
@@ -159,7 +179,8 @@ impl HookCheckDependency {
             file.write_all(&buffer[..n])?;
             downloaded += n as u64;
 
-            if total_size > 0 {
+            // A progress line per chunk would flood the JSON logs
+            if total_size > 0 && !LoggerUtils::is_json() {
                 let progress = downloaded as f64 / total_size as f64 * 100.0;
                 print!(
                     "\rDownloading... {:.2}% ({:.1}/{:.1} MB)",
@@ -171,7 +192,10 @@ impl HookCheckDependency {
             }
         }
 
-        println!("\nDownload complete: {}", dest_path);
+        if !LoggerUtils::is_json() {
+            println!();
+        }
+        info!("Download complete: {}", dest_path);
         Ok(())
     }
 
@@ -254,7 +278,7 @@ impl HookCheckDependency {
         remote: Option<&RemoteInfo>,
         root_dir: &str,
     ) -> EmptyResult {
-        if let Some(bin_path) = &install.bin_path {
+        for bin_path in install.bin_path.iter().chain(&install.bin_paths) {
             let full_path = if bin_path.starts_with('/') || bin_path.starts_with("~") {
                 bin_path.to_string()
             } else {
@@ -264,6 +288,22 @@ impl HookCheckDependency {
 
             OsUtils::add_bin(&full_path, remote, root_dir)?;
         }
+
+        // Sorted to keep the env file stable across runs
+        let mut env: Vec<_> = install.env.iter().collect();
+        env.sort();
+        for (key, value) in env {
+            if key.is_empty()
+                || key.starts_with(|c: char| c.is_ascii_digit())
+                || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(
+                    format!("Invalid env variable name for {}: {}", install.tool, key).into(),
+                );
+            }
+            let line = format!("export {}=\"{}\"", key, value);
+            OsUtils::add_line_to_bashrc(&line, remote, root_dir)?;
+        }
         Ok(())
     }
 
@@ -298,9 +338,24 @@ impl Hook for HookCheckDependency {
     }
 
     fn run(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
+        if ctx.read_state()?.setup_complete {
+            info!("Setup already complete with this config, skipping dependency checks (--once)");
+            return Ok(());
+        }
+
         info!("Checking dependencies...");
 
         for dep in ctx.config.dependencies.iter() {
+            if ctx.args.is_dry_run() {
+                if let Err(err) = self.validate_dependency(ctx, dep, false) {
+                    warn!(
+                        "Dry run: dependency {} is not met and would be installed: {}",
+                        dep.name, err
+                    );
+                }
+                continue;
+            }
+
             let was_installed = self.validate_dependency(ctx, dep, true)?;
 
             if was_installed {
@@ -311,3 +366,58 @@ impl Hook for HookCheckDependency {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install_spec(yaml: &str) -> InstallSpec {
+        serde_yaml::from_str(yaml).expect("Should parse install spec")
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn bin_paths_and_env_are_written_once() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let root_dir = root_dir.path().to_string_lossy().to_string();
+        let install = install_spec(
+            r#"
+tool: flutter
+bin_path: flutter/bin
+bin_paths: [flutter/bin/cache/dart-sdk/bin, /opt/tools/bin]
+env: { PUB_CACHE: "~/.pub-cache", FLUTTER_ROOT: /opt/flutter }
+"#,
+        );
+        let hook = HookCheckDependency::new();
+
+        hook.setup_bin_path(&install, None, &root_dir).unwrap();
+        hook.setup_bin_path(&install, None, &root_dir).unwrap();
+
+        let env_file = std::fs::read_to_string(CommandUtils::env_file(&root_dir)).unwrap();
+        assert_eq!(
+            env_file,
+            format!(
+                "export PATH=\"{root_dir}/flutter/bin:$PATH\"\n\
+                 export PATH=\"{root_dir}/flutter/bin/cache/dart-sdk/bin:$PATH\"\n\
+                 export PATH=\"/opt/tools/bin:$PATH\"\n\
+                 export FLUTTER_ROOT=\"/opt/flutter\"\n\
+                 export PUB_CACHE=\"~/.pub-cache\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_env_names_are_rejected() {
+        let root_dir = tempfile::tempdir().unwrap();
+        let install = install_spec("tool: flutter\nbin_path: null\nenv: { 1BAD: x }\n");
+
+        let err = HookCheckDependency::new()
+            .setup_bin_path(&install, None, &root_dir.path().to_string_lossy())
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid env variable name for flutter: 1BAD"
+        );
+    }
+}
@@ -1,27 +1,31 @@
-use std::net::TcpStream;
+use std::{thread, time::Duration};
 
 use regex::Regex;
-use ssh2::Session;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
     hooks::iface::{Hook, HookContext, HookType},
     models::{
         self,
         app_state::{AppState, RemoteInfo},
-        args::AppMode,
+        args::{AppArgs, AppMode},
     },
-    utils::errors::{EmptyResult, OptionResultTrait, ResultTrait as _, ResultWithError},
+    utils::errors::{EmptyResult, OptionResultTrait, ResultWithError},
 };
 
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
 /// Hook to establish connection to remote host if needed.
+#[derive(Default)]
 pub struct HookConnect {}
 impl HookConnect {
     pub fn new() -> Self {
         HookConnect {}
     }
 
-    fn prompt_for_remote_conn(&self) -> ResultWithError<bool> {
+    fn prompt_for_remote_conn(&self, args: &AppArgs) -> ResultWithError<bool> {
+        args.ensure_can_prompt("Connection mode", "set it with --mode or PLAYMASTER_MODE")?;
         let res = inquire::Select::new(
             "Do you want to connect to a remote host?",
             vec!["Yes", "No"],
@@ -36,13 +40,35 @@ impl HookConnect {
         Ok(false)
     }
 
-    fn prompt_for_address(&self) -> ResultWithError<String> {
+    fn prompt_for_address(&self, args: &AppArgs) -> ResultWithError<String> {
+        args.ensure_can_prompt(
+            "Remote address",
+            "set it with --remote-addr or PLAYMASTER_REMOTE_ADDR",
+        )?;
         let addr = inquire::Text::new("Enter the remote address (e.g., user@ip_address:port):")
             .with_placeholder("dev@192.168.1.100:22")
             .prompt()?;
         Ok(addr)
     }
 
+    /// Reads the password from `env_var`, prompting for it otherwise.
+    fn get_password(&self, args: &AppArgs, env_var: &str, target: &str) -> ResultWithError<String> {
+        if let Ok(pass) = std::env::var(env_var) {
+            return Ok(pass);
+        }
+
+        args.ensure_can_prompt(
+            &format!("The {target}'s password"),
+            &format!("set it with {env_var}"),
+        )?;
+
+        let pass = inquire::Password::new(&format!("Enter your {target}'s password: "))
+            .without_confirmation()
+            .with_display_mode(inquire::PasswordDisplayMode::Hidden)
+            .prompt()?;
+        Ok(pass)
+    }
+
     /// parse "user@host:port" (port optional, defaults to 22)
     fn parse_addr(&self, s: &str) -> ResultWithError<(String, String, u16)> {
         // simple regex parsing: user@hostname:port
@@ -68,14 +94,39 @@ impl HookConnect {
         }
     }
 
+    /// Calls `attempt` with the attempt number until it succeeds, at most `retries + 1` times,
+    /// doubling the delay between attempts from `base_delay` up to `RETRY_MAX_DELAY`.
+    fn with_retries<T>(
+        retries: u32,
+        base_delay: Duration,
+        mut attempt: impl FnMut(u32) -> ResultWithError<T>,
+    ) -> ResultWithError<T> {
+        let mut delay = base_delay;
+        for n in 1..=retries {
+            match attempt(n) {
+                Ok(res) => return Ok(res),
+                Err(err) => {
+                    warn!("Attempt {} failed: {}, retrying in {:?}", n, err, delay);
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(RETRY_MAX_DELAY);
+                }
+            }
+        }
+
+        attempt(retries + 1)
+    }
+
     fn establish_ssh_connection(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
-        let remote_addr = if let models::args::Command::Run { remote_addr, .. } = &ctx.args.command
+        let (remote_addr, jump_addr) = if let models::args::Command::Run {
+            remote_addr, jump, ..
+        } = &ctx.args.command
         {
-            if let Some(addr) = remote_addr {
+            let addr = if let Some(addr) = remote_addr {
                 addr.clone()
             } else {
-                self.prompt_for_address()?
-            }
+                self.prompt_for_address(ctx.args)?
+            };
+            (addr, jump.clone())
         } else {
             return Err("Invalid command context for establishing SSH connection".into());
         };
@@ -83,28 +134,62 @@ impl HookConnect {
         info!("Establishing SSH connection to remote host: {remote_addr}...");
 
         // prompt for password
-        let password = if let Ok(pass) = std::env::var("REMOTE_PASSWORD") {
-            pass
-        } else {
-            inquire::Password::new("Enter your remote device's password: ")
-                .without_confirmation()
-                .with_display_mode(inquire::PasswordDisplayMode::Hidden)
-                .prompt()?
-        };
+        let password = self.get_password(ctx.args, "REMOTE_PASSWORD", "remote device")?;
 
         // parse address
         let (user, host, port) = self
             .parse_addr(&remote_addr)
             .map_err(|e| format!("address parse error: {}", e))?;
 
-        // Test connection now (attempt handshake)
-        let tcp = TcpStream::connect((host.as_str(), port))
-            .map_err(|e| format!("unable to connect to {}:{} — {}", host, port, e))?;
-        let mut sess = Session::new().auto_err("failed to create ssh session")?;
-        sess.set_tcp_stream(tcp);
-        sess.handshake()
-            .map_err(|e| format!("SSH handshake failed: {}", e))?;
-        sess.userauth_password(&user, &password)
+        let timeout = ctx.args.connect_timeout();
+        let jump = match jump_addr {
+            Some(jump_addr) => {
+                let (user, host, port) = self
+                    .parse_addr(&jump_addr)
+                    .map_err(|e| format!("jump address parse error: {}", e))?;
+                info!("Connecting through jump host {}:{}", host, port);
+                Some(Box::new(RemoteInfo {
+                    user,
+                    host,
+                    port,
+                    password: self.get_password(ctx.args, "JUMP_PASSWORD", "jump host")?,
+                    keepalive_interval_secs: ctx.config.keepalive_interval_secs,
+                    connect_timeout: timeout,
+                    jump: None,
+                }))
+            }
+            None => None,
+        };
+
+        let remote = RemoteInfo {
+            user,
+            host,
+            port,
+            password,
+            keepalive_interval_secs: ctx.config.keepalive_interval_secs,
+            connect_timeout: timeout,
+            jump,
+        };
+
+        // Test connection now (attempt handshake), retrying while the device boots
+        let retries = ctx.args.connect_retries();
+        let sess = Self::with_retries(retries, RETRY_BASE_DELAY, |attempt| {
+            info!(
+                "Connecting to {}:{} (attempt {}/{})...",
+                remote.host,
+                remote.port,
+                attempt,
+                retries + 1
+            );
+            remote.connect().map_err(|e| {
+                format!(
+                    "unable to connect to {}:{} — {}",
+                    remote.host, remote.port, e
+                )
+                .into()
+            })
+        })?;
+        sess.userauth_password(&remote.user, &remote.password)
             .map_err(|e| format!("SSH auth failed: {}", e))?;
 
         if !sess.authenticated() {
@@ -112,12 +197,7 @@ impl HookConnect {
         }
 
         // Save connection info into state so other parts of the app can use it.
-        ctx.initiate_remote(RemoteInfo {
-            user,
-            host,
-            port,
-            password,
-        })?;
+        ctx.initiate_remote(remote)?;
 
         info!("Remote connection info stored in state.");
 
@@ -144,7 +224,7 @@ impl Hook for HookConnect {
             if *mode == AppMode::Local {
                 return Ok(());
             }
-        } else if !self.prompt_for_remote_conn()? {
+        } else if !self.prompt_for_remote_conn(ctx.args)? {
             return Ok(());
         }
 
@@ -153,3 +233,72 @@ impl Hook for HookConnect {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, time::Instant};
+
+    use super::*;
+
+    #[test]
+    fn retries_until_the_connection_succeeds() {
+        let mut attempts = vec![];
+
+        let res = HookConnect::with_retries(3, Duration::ZERO, |attempt| {
+            attempts.push(attempt);
+            if attempt < 3 {
+                return Err(format!("connection refused #{attempt}").into());
+            }
+            Ok(attempt)
+        });
+
+        assert_eq!(res.unwrap(), 3);
+        assert_eq!(attempts, [1, 2, 3]);
+    }
+
+    #[test]
+    fn last_error_is_returned_once_retries_run_out() {
+        let mut attempts = 0;
+
+        let res: ResultWithError<()> = HookConnect::with_retries(2, Duration::ZERO, |attempt| {
+            attempts += 1;
+            Err(format!("connection refused #{attempt}").into())
+        });
+
+        assert_eq!(attempts, 3);
+        assert_eq!(res.unwrap_err().to_string(), "connection refused #3");
+    }
+
+    #[test]
+    fn delay_doubles_between_attempts() {
+        let base_delay = Duration::from_millis(10);
+        let mut started = vec![];
+
+        let res: ResultWithError<()> = HookConnect::with_retries(3, base_delay, |_| {
+            started.push(Instant::now());
+            Err("connection refused".into())
+        });
+
+        assert!(res.is_err());
+        let gaps = started
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect::<Vec<_>>();
+        assert_eq!(gaps.len(), 3);
+        for (gap, min) in gaps.iter().zip([10, 20, 40]) {
+            assert!(*gap >= Duration::from_millis(min), "{gaps:?}");
+        }
+    }
+
+    #[test]
+    fn tcp_connection_fails_once_the_port_is_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        RemoteInfo::connect_tcp("127.0.0.1", port, Duration::from_secs(1))
+            .expect("Should connect to the listener");
+
+        drop(listener);
+        assert!(RemoteInfo::connect_tcp("127.0.0.1", port, Duration::from_secs(1)).is_err());
+    }
+}
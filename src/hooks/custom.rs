@@ -6,6 +6,7 @@ use tracing::{debug, error, info, trace};
 
 use crate::utils::errors::ResultWithError;
 use crate::utils::os::OsUtils;
+use crate::utils::shlex::ShlexUtils;
 use crate::{
     hooks::iface::{Hook, HookContext, HookType},
     models::{
@@ -33,8 +34,43 @@ impl HookCustom {
         HookCustom { config }
     }
 
+    /// Variables exposing the run results to finished hooks, e.g. for notification scripts
+    fn results_env(ctx: &HookContext<'_, AppState>) -> ResultWithError<Vec<(String, String)>> {
+        let state = ctx.read_state()?;
+        let results = &state.results;
+        let status = if results.is_success() {
+            "passed"
+        } else {
+            "failed"
+        };
+        Ok(vec![
+            ("PLAYMASTER_PASSED".into(), results.passed.to_string()),
+            ("PLAYMASTER_FAILED".into(), results.failed.to_string()),
+            (
+                "PLAYMASTER_SETUP_FAILED".into(),
+                results.setup_failed.to_string(),
+            ),
+            ("PLAYMASTER_SKIPPED".into(), results.skipped.to_string()),
+            ("PLAYMASTER_TOTAL".into(), results.total.to_string()),
+            ("PLAYMASTER_STATUS".into(), status.into()),
+            (
+                "PLAYMASTER_LOGS_URL".into(),
+                state.logs_url.clone().unwrap_or_default(),
+            ),
+        ])
+    }
+
+    /// Variables set on top of the configured ones, the results for finished hooks
+    fn extra_env(&self, ctx: &HookContext<'_, AppState>) -> ResultWithError<Vec<(String, String)>> {
+        if self.config.hook_type == HookType::Finished {
+            Self::results_env(ctx)
+        } else {
+            Ok(vec![])
+        }
+    }
+
     /// Build command string with args and environment variables
-    fn build_cmd(&self) -> ResultWithError<RunCmd> {
+    fn build_cmd(&self, extra_env: &[(String, String)]) -> ResultWithError<RunCmd> {
         let mut s = String::new();
         let mut has_display = false;
         let file_path = OsUtils::write_temp_script(&self.config.command)
@@ -51,6 +87,10 @@ impl HookCustom {
             }
         }
 
+        for (key, value) in extra_env {
+            s.push_str(&format!("{}={} ", key, ShlexUtils::quote(value)?));
+        }
+
         // Ensure DISPLAY is set
         if !has_display {
             s.push_str(
@@ -68,12 +108,12 @@ impl HookCustom {
     }
 
     /// Local synchronous execution
-    fn run_local_sync(&self, root_dir: &str) -> EmptyResult {
+    fn run_local_sync(&self, root_dir: &str, extra_env: &[(String, String)]) -> EmptyResult {
         // Build the full command string using your existing helper
-        let cmd = self.build_cmd()?;
+        let cmd = self.build_cmd(extra_env)?;
 
-        // Always run through bash so it can interpret the full string
-        let mut command = Command::new("bash");
+        // Always run through the shell so it can interpret the full string
+        let mut command = Command::new(CommandUtils::shell_program());
         command
             .arg("-c")
             .arg(&CommandUtils::with_env_source(root_dir, &cmd.command)?);
@@ -119,12 +159,12 @@ impl HookCustom {
     }
 
     /// Local asynchronous execution
-    fn run_local_async(&self, root_dir: &str) -> EmptyResult {
-        let cmd = self.build_cmd()?;
+    fn run_local_async(&self, root_dir: &str, extra_env: &[(String, String)]) -> EmptyResult {
+        let cmd = self.build_cmd(extra_env)?;
         let name = self.config.name.clone();
 
-        let mut command = Command::new("bash");
-        command
+        let mut command = Command::new(CommandUtils::shell_program());
+        CommandUtils::in_own_process_group(&mut command)
             .arg("-c")
             .arg(&CommandUtils::with_env_source(root_dir, &cmd.command)?)
             .stdout(Stdio::piped())
@@ -141,8 +181,13 @@ impl HookCustom {
     }
 
     /// Remote synchronous execution with proper stdout/stderr logging
-    fn run_remote_sync(&self, remote: &RemoteInfo, root_dir: &str) -> EmptyResult {
-        let cmd = self.build_cmd()?;
+    fn run_remote_sync(
+        &self,
+        remote: &RemoteInfo,
+        root_dir: &str,
+        extra_env: &[(String, String)],
+    ) -> EmptyResult {
+        let cmd = self.build_cmd(extra_env)?;
 
         // Execute remote command and capture stdout/stderr separately
         CommandUtils::copy_file_to_remote(
@@ -180,8 +225,13 @@ impl HookCustom {
     }
 
     /// Remote asynchronous execution with logging
-    fn run_remote_async(&self, remote: &RemoteInfo, root_dir: &str) -> EmptyResult {
-        let cmd = self.build_cmd()?;
+    fn run_remote_async(
+        &self,
+        remote: &RemoteInfo,
+        root_dir: &str,
+        extra_env: &[(String, String)],
+    ) -> EmptyResult {
+        let cmd = self.build_cmd(extra_env)?;
 
         CommandUtils::copy_file_to_remote(
             remote,
@@ -196,20 +246,21 @@ impl HookCustom {
         let root_dir = root_dir.to_owned();
 
         thread::spawn(move || {
-            if let Err(err) = CommandUtils::track_remote_cmd(
-                cmd.command
-                    .split_whitespace()
-                    .last()
-                    .unwrap_or_default()
-                    .to_owned(),
-                remote_clone.clone(),
-            ) {
+            let pid_file = CommandUtils::remote_pid_file(&root_dir);
+            if let Err(err) = CommandUtils::track_remote_cmd(&name, &pid_file, remote_clone.clone())
+            {
                 error!("[{name}] Failed to track remote async command: {}", err);
             }
 
-            if let Err(err) =
-                CommandUtils::run_command_str(&cmd.command, Some(&remote_clone), &root_dir)
-            {
+            let res = CommandUtils::run_command_str(
+                &CommandUtils::with_remote_pid_file(&cmd.command, &pid_file),
+                Some(&remote_clone),
+                &root_dir,
+            );
+            if let Err(err) = CommandUtils::untrack_remote_cmd(&pid_file) {
+                error!("[{name}] Failed to untrack remote async command: {}", err);
+            }
+            if let Err(err) = res {
                 error!("[{name}] Failed to start remote async command: {}", err);
                 return;
             }
@@ -231,6 +282,14 @@ impl Hook for HookCustom {
     }
 
     fn run(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
+        if ctx.args.is_dry_run() {
+            info!(
+                "Dry run: skipping custom hook '{}': {}",
+                self.config.name, self.config.command
+            );
+            return Ok(());
+        }
+
         let remote = ctx.get_remote_info()?;
         let root_dir = ctx.get_root_dir()?;
 
@@ -254,16 +313,92 @@ impl Hook for HookCustom {
 
         info!("Executing custom hook: {}", self.config.name);
 
+        let extra_env = self.extra_env(ctx)?;
+
         if let Some(remote) = remote {
             if self.config.is_async {
-                self.run_remote_async(&remote, &root_dir)
+                self.run_remote_async(&remote, &root_dir, &extra_env)
             } else {
-                self.run_remote_sync(&remote, &root_dir)
+                self.run_remote_sync(&remote, &root_dir, &extra_env)
             }
         } else if self.config.is_async {
-            self.run_local_async(&root_dir)
+            self.run_local_async(&root_dir, &extra_env)
         } else {
-            self.run_local_sync(&root_dir)
+            self.run_local_sync(&root_dir, &extra_env)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+    };
+
+    use clap::Parser as _;
+
+    use super::*;
+    use crate::models::{app_state::Results, args::AppArgs, config::Config, vars::Vars};
+
+    /// `PLAYMASTER_*` variables seen by the command of a hook of the given type.
+    fn hook_env(hook_type: &str) -> String {
+        let hook = HookCustom::new(
+            serde_yaml::from_str(&format!(
+                "{{ name: notify, hook_type: {hook_type}, command: \"env | grep ^PLAYMASTER_ | sort\" }}"
+            ))
+            .expect("Should parse hook config"),
+        );
+        let args = AppArgs::parse_from(["playmaster", "run"]);
+        let config: Config =
+            serde_yaml::from_str("project_type: flutter").expect("Should parse config");
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        };
+        let ctx = HookContext {
+            args: &args,
+            config: &config,
+            vars: &vars,
+            state: Arc::new(RwLock::new(AppState {
+                results: Results {
+                    total: 5,
+                    passed: 3,
+                    failed: 1,
+                    skipped: 1,
+                    ..Default::default()
+                },
+                logs_url: Some("https://logs.example.com/run 1".to_owned()),
+                ..Default::default()
+            })),
+        };
+
+        let cmd = hook.build_cmd(&hook.extra_env(&ctx).unwrap()).unwrap();
+        let output = Command::new(CommandUtils::shell_program())
+            .arg("-c")
+            .arg(&cmd.command)
+            .output()
+            .unwrap();
+        _ = std::fs::remove_file(cmd.file_path);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    #[test]
+    fn finished_hooks_see_the_results() {
+        assert_eq!(
+            hook_env("finished"),
+            "PLAYMASTER_FAILED=1\n\
+             PLAYMASTER_LOGS_URL=https://logs.example.com/run 1\n\
+             PLAYMASTER_PASSED=3\n\
+             PLAYMASTER_SETUP_FAILED=0\n\
+             PLAYMASTER_SKIPPED=1\n\
+             PLAYMASTER_STATUS=failed\n\
+             PLAYMASTER_TOTAL=5\n"
+        );
+    }
+
+    #[test]
+    fn other_hooks_do_not_see_the_results() {
+        assert_eq!(hook_env("prepare_system"), "");
+    }
+}
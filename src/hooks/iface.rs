@@ -5,7 +5,7 @@ use std::sync::{Arc, RwLock};
 
 use crate::{
     models::{
-        app_state::{AppState, RemoteInfo, Results},
+        app_state::{AppState, ErrorPhase, RemoteInfo, ResultError, Results},
         args::AppArgs,
         config::Config,
         vars::Vars,
@@ -34,6 +34,16 @@ impl HookType {
     pub fn post_hooks() -> Vec<HookType> {
         vec![Self::Finished]
     }
+
+    /// Phase the errors of the hooks of this type are reported in.
+    pub fn error_phase(&self) -> ErrorPhase {
+        match self {
+            Self::Connect => ErrorPhase::Connect,
+            Self::VerifySystem => ErrorPhase::Verify,
+            Self::PrepareSystem => ErrorPhase::Prepare,
+            Self::Finished => ErrorPhase::Report,
+        }
+    }
 }
 
 /// Context passed to all hooks during execution providing access to CLI args,
@@ -84,12 +94,12 @@ impl<'a> HookContext<'a, AppState> {
         Ok(())
     }
 
-    pub fn add_results_error(&self, error: String) -> EmptyResult {
+    pub fn add_results_error(&self, phase: ErrorPhase, message: String) -> EmptyResult {
         let mut state = self
             .state
             .write()
             .auto_err("Failed to acquire write lock")?;
-        state.results.error.push(error);
+        state.results.error.push(ResultError { phase, message });
         Ok(())
     }
 
@@ -120,6 +130,36 @@ impl<'a> HookContext<'a, AppState> {
         Ok(())
     }
 
+    pub fn increment_results_setup_failed(&self) -> EmptyResult {
+        let mut state = self
+            .state
+            .write()
+            .auto_err("Failed to acquire write lock")?;
+        state.results.setup_failed += 1;
+        Ok(())
+    }
+
+    pub fn add_results_duration(&self, test_name: &str, duration_ms: u64) -> EmptyResult {
+        let mut state = self
+            .state
+            .write()
+            .auto_err("Failed to acquire write lock")?;
+        state
+            .results
+            .durations_ms
+            .push((test_name.to_owned(), duration_ms));
+        Ok(())
+    }
+
+    pub fn increment_results_skipped(&self) -> EmptyResult {
+        let mut state = self
+            .state
+            .write()
+            .auto_err("Failed to acquire write lock")?;
+        state.results.skipped += 1;
+        Ok(())
+    }
+
     pub fn set_results_time(
         &self,
         start: DateTime<chrono::Utc>,
@@ -155,14 +195,14 @@ pub trait Hook: Send + Sync {
 }
 
 pub trait HookListExt {
-    fn hooks_of_type(&self, hook_type: HookType) -> Vec<&dyn Hook>;
+    fn hooks_of_type(&self, hook_type: HookType) -> Vec<Arc<dyn Hook>>;
 }
 
-impl HookListExt for Vec<Box<dyn Hook>> {
-    fn hooks_of_type(&self, hook_type: HookType) -> Vec<&dyn Hook> {
+impl HookListExt for Vec<Arc<dyn Hook>> {
+    fn hooks_of_type(&self, hook_type: HookType) -> Vec<Arc<dyn Hook>> {
         self.iter()
             .filter(|hook| hook.get_type() == hook_type)
-            .map(|hook| hook.as_ref())
+            .cloned()
             .collect()
     }
 }
@@ -2,5 +2,7 @@ pub mod check_dependency;
 pub mod connect;
 pub mod custom;
 pub mod iface;
+pub mod remote_precheck;
 pub mod results;
 pub mod setup_state;
+pub mod state_capture;
@@ -0,0 +1,121 @@
+use tracing::{error, info};
+
+use crate::{
+    hooks::iface::{Hook, HookContext, HookType},
+    models::app_state::{AppState, RemoteInfo},
+    utils::{
+        command::CommandUtils,
+        errors::{EmptyResult, ResultWithError},
+        os::OsUtils,
+        shlex::ShlexUtils,
+    },
+};
+
+/// Hook to verify the remote device can run the tests before building, so a missing
+/// Flutter SDK or display fails upfront instead of mid-run.
+#[derive(Default)]
+pub struct HookRemotePrecheck {}
+
+impl HookRemotePrecheck {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn check_flutter(&self, remote: &RemoteInfo, root_dir: &str) -> ResultWithError<String> {
+        let output = CommandUtils::run_command_str("flutter --version", Some(remote), root_dir)?;
+        if output.status != 0 {
+            return Err(format!(
+                "`flutter --version` failed with status {}, is flutter on the PATH? {}",
+                output.status,
+                output.stderr.trim()
+            )
+            .into());
+        }
+
+        Ok(output.stdout.lines().next().unwrap_or_default().to_owned())
+    }
+
+    /// Checks the X server socket of `DISPLAY` exists, e.g. `/tmp/.X11-unix/X0` for `:0`.
+    fn check_display(&self, remote: &RemoteInfo, root_dir: &str) -> ResultWithError<String> {
+        let display = OsUtils::get_display();
+        let Some(number) = display
+            .strip_prefix(':')
+            .and_then(|d| d.split('.').next())
+            .filter(|d| !d.is_empty() && d.chars().all(|c| c.is_ascii_digit()))
+        else {
+            return Ok(format!("{} (not a local display, not checked)", display));
+        };
+
+        let socket = format!("/tmp/.X11-unix/X{}", number);
+        let output =
+            CommandUtils::run_command_str(&format!("test -S {}", socket), Some(remote), root_dir)?;
+        if output.status != 0 {
+            return Err(format!(
+                "no X server is listening on DISPLAY={} ({} not found), is the session up?",
+                display, socket
+            )
+            .into());
+        }
+
+        Ok(display)
+    }
+
+    fn check_root_dir(&self, remote: &RemoteInfo, root_dir: &str) -> ResultWithError<String> {
+        let quoted = ShlexUtils::quote(root_dir)?;
+        let output =
+            CommandUtils::run_command_str(&format!("test -w {}", quoted), Some(remote), root_dir)?;
+        if output.status != 0 {
+            return Err(format!("root dir {} is not writable", quoted).into());
+        }
+
+        Ok(root_dir.to_owned())
+    }
+}
+
+impl Hook for HookRemotePrecheck {
+    fn get_type(&self) -> HookType {
+        HookType::PrepareSystem
+    }
+
+    fn continue_on_error(&self) -> bool {
+        false
+    }
+
+    fn run(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
+        let root_dir = ctx.get_root_dir()?;
+        let state = ctx.read_state()?;
+        let Some(remote) = state.remote.as_ref() else {
+            return Ok(());
+        };
+
+        info!("Checking remote device {} is ready...", remote.host);
+
+        type Check = fn(&HookRemotePrecheck, &RemoteInfo, &str) -> ResultWithError<String>;
+        let checks: [(&str, Check); 3] = [
+            ("Flutter", Self::check_flutter),
+            ("Display", Self::check_display),
+            ("Root dir", Self::check_root_dir),
+        ];
+
+        let mut failed = Vec::new();
+        for (name, check) in checks {
+            match check(self, remote, &root_dir) {
+                Ok(detail) => info!("✅ {}: {}", name, detail),
+                Err(err) => {
+                    error!("❌ {}: {}", name, err);
+                    failed.push(name);
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(format!(
+                "Remote device is not ready, failed checks: {}",
+                failed.join(", ")
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
@@ -1,24 +1,15 @@
-use std::collections::HashMap;
-
-use aws_config::BehaviorVersion;
-use aws_sdk_s3::{primitives::ByteStream, types::ObjectCannedAcl};
-use tracing::{debug, error, info};
+use tracing::{error, info};
 
 use crate::{
     hooks::iface::{Hook, HookContext, HookType},
-    models::{
-        app_state::{AppState, Results},
-        config::{S3Config, WebhookConfig},
-    },
-    utils::{
-        errors::{EmptyResult, ResultTrait, ResultWithError},
-        variables::VariablesUtils,
-    },
+    models::app_state::AppState,
+    sinks::iface::ResultSink,
+    utils::errors::EmptyResult,
 };
 
-/// Hook to handle reports post test run.
+/// Hook publishing the results to the configured sinks post test run.
 pub struct HookResults {
-    config: WebhookConfig,
+    sinks: Vec<Box<dyn ResultSink>>,
 }
 
 impl Hook for HookResults {
@@ -31,159 +22,167 @@ impl Hook for HookResults {
     }
 
     fn run(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
+        if ctx.args.is_dry_run() {
+            for sink in &self.sinks {
+                info!("Dry run: skipping {}", sink.get_name());
+            }
+            return Ok(());
+        }
+
+        // A failing sink doesn't prevent the others from publishing
         let results = ctx.get_results()?;
-        self.call_webhook(results)?;
+        let mut errors = vec![];
+        for sink in &self.sinks {
+            if let Err(err) = sink.publish(&results) {
+                error!("Failed to publish results to {}: {}", sink.get_name(), err);
+                errors.push(format!("{}: {}", sink.get_name(), err));
+            }
+        }
+
+        // Shared with the custom finished hooks running next
+        if let Some(logs_url) = self.sinks.iter().find_map(|sink| sink.get_logs_url()) {
+            ctx.write_state()?.logs_url = Some(logs_url);
+        }
+
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+
         Ok(())
     }
 }
 
 impl HookResults {
-    pub fn new(config: WebhookConfig) -> Self {
-        HookResults { config }
+    pub fn new(sinks: Vec<Box<dyn ResultSink>>) -> Self {
+        HookResults { sinks }
     }
+}
 
-    fn call_webhook(&self, results: Results) -> EmptyResult {
-        if self.config.url.is_empty() {
-            info!("No webhook URL configured, skipping webhook call.");
-            return Ok(());
-        }
-
-        info!("Calling webhook {}...", self.config.url);
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, RwLock},
+    };
 
-        let client = reqwest::blocking::Client::new();
-        let logs_url = if let Some(s3_config) = self.config.s3_config.as_ref() {
-            match self.upload_logs_to_s3(&results, s3_config) {
-                Ok(url) => url,
-                Err(e) => {
-                    error!("Failed to upload logs to S3: {}", e);
-                    "".to_owned()
-                }
-            }
-        } else {
-            "".to_owned()
-        };
-        self.send_message(&client, &results, &logs_url)?;
+    use clap::Parser as _;
 
-        info!("Webhook called successfully.");
+    use super::*;
+    use crate::models::{app_state::Results, args::AppArgs, config::Config, vars::Vars};
 
-        Ok(())
+    /// Sink recording the totals it was given, failing when `fail` is set.
+    struct RecordingSink {
+        name: &'static str,
+        fail: bool,
+        logs_url: Option<String>,
+        published: Arc<Mutex<Vec<String>>>,
     }
 
-    fn send_message(
-        &self,
-        client: &reqwest::blocking::Client,
-        results: &Results,
-        logs_url: &str,
-    ) -> EmptyResult {
-        let message = self.get_message(results, logs_url);
-        debug!("Webhook message: {}", message);
-
-        let payload =
-            serde_json::json!({ "text": message, "results": results, "logs_url": logs_url });
-        let res = client
-            .post(&self.config.url)
-            .json(&payload)
-            .send()
-            .auto_err("Failed to send webhook message: {}")?;
-
-        if !res.status().is_success() {
-            if self.config.ignore_error {
-                info!(
-                    "Webhook API returned error status: {}, but ignoring as per configuration.",
-                    res.status()
-                );
-                return Ok(());
-            }
+    impl ResultSink for RecordingSink {
+        fn get_name(&self) -> String {
+            self.name.to_owned()
+        }
 
-            return Err(format!("Webhook API returned error status: {}", res.status()).into());
+        fn publish(&self, results: &Results) -> EmptyResult {
+            self.published.lock().unwrap().push(format!(
+                "{} {}/{}",
+                self.name, results.passed, results.total
+            ));
+            if self.fail {
+                return Err("unreachable".into());
+            }
+            Ok(())
         }
 
-        Ok(())
+        fn get_logs_url(&self) -> Option<String> {
+            self.logs_url.clone()
+        }
     }
 
-    fn get_message(&self, results: &Results, logs_url: &str) -> String {
-        let errors = if results.error.is_empty() {
-            "".to_owned()
-        } else {
-            results.error.join("\n")
+    fn run_hook(
+        args: &[&str],
+        sinks: &[(&'static str, bool, Option<&str>)],
+    ) -> (EmptyResult, Vec<String>, AppState) {
+        let published = Arc::new(Mutex::new(vec![]));
+        let hook = HookResults::new(
+            sinks
+                .iter()
+                .map(|(name, fail, logs_url)| {
+                    Box::new(RecordingSink {
+                        name,
+                        fail: *fail,
+                        logs_url: logs_url.map(str::to_owned),
+                        published: published.clone(),
+                    }) as Box<dyn ResultSink>
+                })
+                .collect(),
+        );
+
+        let args = AppArgs::parse_from(args);
+        let config: Config =
+            serde_yaml::from_str("project_type: flutter").expect("Should parse config");
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        };
+        let state = AppState {
+            results: Results {
+                total: 3,
+                passed: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ctx = HookContext {
+            args: &args,
+            config: &config,
+            vars: &vars,
+            state: Arc::new(RwLock::new(state)),
         };
 
-        if !self.config.message_template.is_empty() {
-            let mut data = serde_json::to_value(results)
-                .unwrap_or_default()
-                .as_object()
-                .cloned()
-                .unwrap_or_default()
-                .iter()
-                .map(|(k, v)| (k.clone(), v.as_str().unwrap_or_default().to_owned()))
-                .collect::<HashMap<_, _>>();
-            data.insert("logs_url".to_owned(), logs_url.to_owned());
-            data.insert("errors".to_owned(), errors);
-
-            data.insert("passed".to_owned(), results.passed.to_string());
-            data.insert("failed".to_owned(), results.failed.to_string());
-            data.insert("total".to_owned(), results.total.to_string());
-
-            let status = if results.failed > 0 || !results.error.is_empty() {
-                "Failed"
-            } else {
-                "Passed"
-            };
-            let status_icon = if status == "Passed" { "✅" } else { "❌" };
-            data.insert("status".to_owned(), status.to_owned());
-            data.insert("status_icon".to_owned(), status_icon.to_owned());
-
-            debug!("Webhook message data: {:?}", data);
-
-            VariablesUtils::replace_vars(&self.config.message_template, &data, None)
-        } else {
-            format!(
-                "Test Run Completed:\n✅ Passed: {}\n❌ Failed: {}\n📋 Total: {}\nStart Time: {}\nEnd Time: {}\nLogs: {}\nErrors: {}",
-                results.passed,
-                results.failed,
-                results.total,
-                results.start_time,
-                results.end_time,
-                logs_url,
-                errors
-            )
-        }
+        let result = hook.run(&ctx);
+        let state = ctx.read_state().unwrap().clone();
+        let published = published.lock().unwrap().clone();
+        (result, published, state)
     }
 
-    fn upload_logs_to_s3(
-        &self,
-        results: &Results,
-        s3_config: &S3Config,
-    ) -> ResultWithError<String> {
-        if results.full_log.is_empty() {
-            debug!("No logs to upload to S3.");
-            return Ok("".to_owned());
-        }
-
-        let key_prefix = s3_config.key_prefix.clone();
-        let bucket = s3_config.bucket.clone();
-
-        tokio::runtime::Handle::current().block_on(async {
-            let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-            let s3 = aws_sdk_s3::Client::new(&config);
+    #[test]
+    fn results_are_published_to_every_sink() {
+        let (result, published, state) = run_hook(
+            &["playmaster", "run"],
+            &[
+                ("webhook", false, None),
+                ("s3", false, Some("https://logs.example.com/run.log")),
+            ],
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(published, ["webhook 2/3", "s3 2/3"]);
+        assert_eq!(
+            state.logs_url.as_deref(),
+            Some("https://logs.example.com/run.log")
+        );
+    }
 
-            let key = format!("{}results_{}.txt", key_prefix, results.start_time).replace(" ", "_");
-            info!("Uploading logs to S3 to path: s3://{}/{}", bucket, key);
+    #[test]
+    fn a_failing_sink_does_not_stop_the_others() {
+        let (result, published, _) = run_hook(
+            &["playmaster", "run"],
+            &[("webhook", true, None), ("s3", false, None)],
+        );
 
-            // Upload from the in-memory string
-            s3.put_object()
-                .bucket(&bucket)
-                .key(&key)
-                .acl(ObjectCannedAcl::PublicRead)
-                .body(ByteStream::from(results.full_log.clone().into_bytes()))
-                .send()
-                .await
-                .auto_err("Failed to upload logs to S3")?;
+        assert_eq!(published, ["webhook 2/3", "s3 2/3"]);
+        assert_eq!(result.unwrap_err().to_string(), "webhook: unreachable");
+    }
 
-            let s3_url = format!("https://{}.s3.amazonaws.com/{}", bucket, key);
-            info!("Logs uploaded to S3 successfully: {}", s3_url);
+    #[test]
+    fn dry_runs_publish_nothing() {
+        let (result, published, _) = run_hook(
+            &["playmaster", "run", "--dry-run"],
+            &[("webhook", false, None)],
+        );
 
-            Ok(s3_url)
-        })
+        assert!(result.is_ok());
+        assert!(published.is_empty());
     }
 }
@@ -1,14 +1,19 @@
-use std::{path::PathBuf, str::FromStr};
-
-use tracing::info;
+use tracing::{debug, info};
 
 use crate::{
     hooks::iface::{Hook, HookContext, HookType},
     models::app_state::AppState,
-    utils::{command::CommandUtils, dir::DirUtils, errors::EmptyResult, os::OsUtils},
+    utils::{
+        command::CommandUtils,
+        dir::DirUtils,
+        errors::{EmptyResult, ResultWithError},
+        os::OsUtils,
+        setup_marker::SetupMarker,
+    },
 };
 
 /// Hook to establish connection to remote host if needed.
+#[derive(Default)]
 pub struct HookSetupState {}
 impl HookSetupState {
     pub fn new() -> Self {
@@ -27,8 +32,23 @@ impl Hook for HookSetupState {
 
     fn run(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
         info!("Setting up OS-specific state information...");
-        OsUtils::setup_state(ctx)?;
         self.set_root_dir(ctx)?;
+        OsUtils::setup_state(ctx)?;
+
+        if ctx.args.is_once() && self.check_setup_marker(ctx)? {
+            info!("Setup already complete with this config, skipping setup tasks (--once)");
+            return Ok(());
+        }
+
+        if ctx.args.is_dry_run() {
+            info!(
+                "Dry run: would create {} and export DISPLAY={} in it",
+                CommandUtils::env_file(&ctx.get_root_dir()?).display(),
+                OsUtils::get_display()
+            );
+            return Ok(());
+        }
+
         self.create_bashrc_if_not_existing(ctx)?;
         self.add_display_to_bashrc(ctx)?;
 
@@ -37,11 +57,19 @@ impl Hook for HookSetupState {
 }
 
 impl HookSetupState {
+    /// Computes the root dir once and caches it in the state, as it requires a round-trip
+    /// for remotes. Everything else should read it through `HookContext::get_root_dir`.
     fn set_root_dir(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
         let root_dir = {
             let state = ctx.read_state()?;
+            if !state.root_dir.is_empty() {
+                debug!("Reusing root dir from state: {}", state.root_dir);
+                return Ok(());
+            }
+
             DirUtils::root_dir(state.remote.as_ref())?
         };
+        info!("Using root dir: {}", root_dir.display());
 
         let mut state = ctx.write_state()?;
         state.root_dir = root_dir.to_string_lossy().to_string();
@@ -49,12 +77,22 @@ impl HookSetupState {
         Ok(())
     }
 
+    /// Flags the setup as complete in the state when the marker matches the current config.
+    fn check_setup_marker(&self, ctx: &HookContext<'_, AppState>) -> ResultWithError<bool> {
+        let root_dir = ctx.get_root_dir()?;
+        let remote = ctx.read_state()?.remote.clone();
+        let complete = SetupMarker::matches(remote.as_ref(), &root_dir, &ctx.config.content_hash)?;
+
+        ctx.write_state()?.setup_complete = complete;
+        Ok(complete)
+    }
+
     fn create_bashrc_if_not_existing(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
         let state = ctx.read_state().unwrap();
         let remote = state.remote.as_ref();
 
         let root_dir = ctx.get_root_dir()?;
-        let file_path = PathBuf::from_str(&root_dir)?.join(".bashrc");
+        let file_path = CommandUtils::env_file(&root_dir);
         CommandUtils::run_command_str(
             &format!(
                 "mkdir -p {} && touch {}",
@@ -0,0 +1,134 @@
+use tracing::info;
+
+use crate::{
+    code_gen::r#gen::CodeGen,
+    hooks::iface::{Hook, HookContext, HookType},
+    models::{app_state::AppState, args::Command, vars::Vars},
+    utils::{
+        command::CommandUtils,
+        errors::{EmptyResult, ResultWithError},
+        shlex::ShlexUtils,
+    },
+};
+
+/// Hook running the `state_set` command once before the tests when `capture_output` is set,
+/// and regenerating the tests with its output as `vars.state.output`, e.g. a fresh account.
+#[derive(Default)]
+pub struct HookStateCapture {}
+
+impl HookStateCapture {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn capture_output(&self, ctx: &HookContext<'_, AppState>) -> ResultWithError<String> {
+        let state_set = &ctx.config.state_set;
+        let mut parts = vec![state_set.command.clone()];
+        for argument in &state_set.arguments {
+            parts.push(ShlexUtils::quote(argument)?);
+        }
+        let cmd = parts.join(" ");
+
+        let root_dir = ctx.get_root_dir()?;
+        let remote = ctx.get_remote_info()?;
+        let output = CommandUtils::run_command_str(&cmd, remote.as_ref(), &root_dir)?;
+        if output.status != 0 {
+            return Err(format!(
+                "State set command `{}` failed with status {}: {}",
+                cmd,
+                output.status,
+                output.stderr.trim()
+            )
+            .into());
+        }
+
+        Ok(output.stdout.trim().to_owned())
+    }
+}
+
+impl Hook for HookStateCapture {
+    fn get_type(&self) -> HookType {
+        HookType::PrepareSystem
+    }
+
+    fn run(&self, ctx: &HookContext<'_, AppState>) -> EmptyResult {
+        let state_set = &ctx.config.state_set;
+        if !state_set.capture_output || state_set.command.is_empty() {
+            return Ok(());
+        }
+
+        if let Command::Run { setup: true, .. } = ctx.args.command {
+            return Ok(());
+        }
+
+        if ctx.args.is_dry_run() {
+            info!(
+                "Dry run: skipping state set command capture: {}",
+                state_set.command
+            );
+            return Ok(());
+        }
+
+        info!("Capturing the output of the state set command");
+        let output = self.capture_output(ctx)?;
+        info!("State set command output captured ({} bytes)", output.len());
+
+        let vars = Vars::all_from_curr_dir(&ctx.config.test_dirs)?
+            .with_overrides(&ctx.args.vars)?
+            .with_state_output(&output);
+        CodeGen::new(ctx.args.clone(), ctx.config.clone(), vars).execute()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+    };
+
+    use clap::Parser as _;
+
+    use super::*;
+    use crate::models::{args::AppArgs, config::Config};
+
+    fn capture(state_set: &str) -> ResultWithError<String> {
+        let root_dir = tempfile::tempdir().expect("Should create temp dir");
+        let args = AppArgs::parse_from(["playmaster", "run"]);
+        let config: Config =
+            serde_yaml::from_str(&format!("project_type: flutter\nstate_set: {state_set}"))
+                .expect("Should parse config");
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        };
+        let ctx = HookContext {
+            args: &args,
+            config: &config,
+            vars: &vars,
+            state: Arc::new(RwLock::new(AppState {
+                root_dir: root_dir.path().to_string_lossy().to_string(),
+                ..Default::default()
+            })),
+        };
+
+        HookStateCapture::new().capture_output(&ctx)
+    }
+
+    #[test]
+    fn output_is_the_trimmed_stdout_of_the_command() {
+        let output = capture(r#"{ command: "printf '%s\n'", arguments: ["token 1"] }"#);
+
+        assert_eq!(output.unwrap(), "token 1");
+    }
+
+    #[test]
+    fn failing_command_is_an_error() {
+        let err = capture(r#"{ command: "echo oops >&2; exit 3" }"#).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "State set command `echo oops >&2; exit 3` failed with status 3: oops"
+        );
+    }
+}
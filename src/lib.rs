@@ -0,0 +1,61 @@
+//! PlayMaster generates Flutter integration tests from YAML feature files and runs them,
+//! locally or on a remote device over SSH.
+//!
+//! The `playmaster` binary is a thin wrapper around [`generate`] and [`run`], which can be
+//! called directly to drive PlayMaster from other Rust tooling:
+//!
+//! ```no_run
+//! use clap::Parser as _;
+//! use playmaster::models::{args::AppArgs, config::Config, feature_test::FeatureTest, vars::Vars};
+//!
+//! # async fn example() -> playmaster::utils::errors::EmptyResult {
+//! let args = AppArgs::parse_from(["playmaster", "run", "--mode", "local", "--yes"]);
+//! let config = Config::from_curr_dir(false)?;
+//! let features = FeatureTest::all_from_curr_dir(&config.test_dirs, false, false)?;
+//!
+//! let vars = Vars::all_from_curr_dir(&config.test_dirs)?;
+//! playmaster::generate(&args, config.clone(), vars, features)?;
+//!
+//! let vars = Vars::all_from_curr_dir(&config.test_dirs)?;
+//! let results = playmaster::run(&args, config, vars).await?;
+//! println!("{} passed, {} failed", results.passed, results.failed);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    code_gen::r#gen::CodeGen,
+    code_run::run::CodeRun,
+    models::{
+        app_state::Results, args::AppArgs, config::Config, feature_test::FeatureTest, vars::Vars,
+    },
+    utils::errors::{EmptyResult, ResultWithError},
+};
+
+pub mod code_gen;
+pub mod code_run;
+pub mod doctor;
+pub mod gmail;
+pub mod hooks;
+#[cfg(target_os = "linux")]
+pub mod linux;
+pub mod models;
+pub mod schemas;
+pub mod sinks;
+pub mod utils;
+
+/// Generates the Dart tests of `features` into `args.output_dir`, relative to the current directory.
+pub fn generate(
+    args: &AppArgs,
+    config: Config,
+    vars: Vars,
+    features: Vec<FeatureTest>,
+) -> EmptyResult {
+    CodeGen::new(args.clone(), config, vars).execute_with_features(features)
+}
+
+/// Runs the hooks and the tests of the feature files found in `config.test_dirs`,
+/// returning the test results. Errors are returned when the run couldn't complete.
+pub async fn run(args: &AppArgs, config: Config, vars: Vars) -> ResultWithError<Results> {
+    CodeRun::new(args.clone(), config, vars).execute().await
+}
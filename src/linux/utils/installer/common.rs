@@ -1,11 +1,11 @@
-use std::{fs, io::Write as _, path::PathBuf, str::FromStr as _};
+use std::{fs, io::Write as _};
 
 use tracing::{error, info};
 
 use crate::{
     hooks::iface::HookContext,
     models::app_state::{AppState, RemoteInfo},
-    utils::{command::CommandUtils, errors::EmptyResult, os::InstallType},
+    utils::{command::CommandUtils, errors::EmptyResult, os::InstallType, shlex::ShlexUtils},
 };
 
 /// Trait that all installer implementations must adhere to.
@@ -27,13 +27,12 @@ impl dyn Installer {
 }
 
 pub fn add_bin_to_path(bin_path: &str, remote: Option<&RemoteInfo>, root_dir: &str) -> EmptyResult {
-    let line = format!("export PATH=\"{}:\\\\$PATH\"", bin_path);
+    let line = format!("export PATH=\"{}:$PATH\"", bin_path);
     add_line_to_bashrc(&line, remote, root_dir)
 }
 
 pub fn add_line_to_bashrc(line: &str, remote: Option<&RemoteInfo>, root_dir: &str) -> EmptyResult {
-    let file_path = PathBuf::from_str(root_dir)?
-        .join(".bashrc")
+    let file_path = CommandUtils::env_file(root_dir)
         .to_string_lossy()
         .to_string();
 
@@ -41,8 +40,9 @@ pub fn add_line_to_bashrc(line: &str, remote: Option<&RemoteInfo>, root_dir: &st
 
     if let Some(remote) = remote {
         // Remote: only append if not already present
+        let quoted = ShlexUtils::quote(line)?;
         let check_cmd =
-            format!("grep -Fxq \"{line}\" {file_path} || echo \"{line}\" >> {file_path}");
+            format!("grep -Fxq {quoted} {file_path} || printf '%s\\n' {quoted} >> {file_path}");
         let res = CommandUtils::run_command_str(&check_cmd, Some(remote), root_dir)?;
         if res.status != 0 {
             error!("Failed to add line remotely: {}", res.stderr);
@@ -52,7 +52,7 @@ pub fn add_line_to_bashrc(line: &str, remote: Option<&RemoteInfo>, root_dir: &st
     } else {
         // Local: only append if not already present
         let contents = fs::read_to_string(&file_path).unwrap_or_default();
-        if !contents.contains(line) {
+        if !contents.lines().any(|existing| existing == line) {
             let mut file = fs::OpenOptions::new()
                 .append(true)
                 .create(true)
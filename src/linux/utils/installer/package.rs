@@ -105,11 +105,16 @@ impl PackageInstaller {
             return Ok(());
         }
 
-        let full_cmd = "grep -qxF 'export PATH=\"$HOME/.local/bin:$PATH\"' ~/.bashrc || \
-                        echo 'export PATH=\"$HOME/.local/bin:$PATH\"' >> ~/.bashrc";
+        // Goes to the playmaster env file, as it is the one sourced before each command
+        let env_file = CommandUtils::env_file(root_dir);
+        let env_file = env_file.to_string_lossy();
+        let full_cmd = format!(
+            "grep -qxF 'export PATH=\"$HOME/.local/bin:$PATH\"' {env_file} || \
+             echo 'export PATH=\"$HOME/.local/bin:$PATH\"' >> {env_file}"
+        );
 
         let conn_type = if remote.is_some() { "remote" } else { "local" };
-        let result = CommandUtils::run_command_str(full_cmd, remote, root_dir)?;
+        let result = CommandUtils::run_command_str(&full_cmd, remote, root_dir)?;
 
         if result.status != 0 {
             error!(
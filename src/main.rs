@@ -8,42 +8,36 @@ use std::sync::mpsc;
 use std::thread;
 use tracing::{debug, error, info, warn};
 
-use crate::{
+use playmaster::{
     code_gen::r#gen::CodeGen,
-    code_run::run::CodeRun,
-    gmail::client::GmailClient,
-    models::{args::AppArgs, config::Config, vars::Vars},
+    doctor::Doctor,
+    gmail::{client::GmailClient, imap_client::ImapGmailClient},
+    models::{self, args::AppArgs, config::Config, vars::Vars},
     schemas::schema_gen::SchemaGen,
     utils::{
         command::CommandUtils,
-        errors::{EmptyResult, ResultTrait},
+        dotenv::DotEnvUtils,
+        errors::{EmptyResult, OptionResultTrait as _, ResultTrait, ResultWithError},
         execution::ExecutionUtils,
         logger::LoggerUtils,
     },
 };
 
-mod code_gen;
-mod code_run;
-mod gmail;
-mod hooks;
-#[cfg(target_os = "linux")]
-mod linux;
-mod models;
-mod schemas;
-mod utils;
-
-#[tokio::main]
-async fn main() -> EmptyResult {
-    let mut signals = Signals::new([SIGINT, SIGTERM]).auto_err("Failed to init signal handler")?;
+fn main() -> EmptyResult {
+    let signals = Signals::new([SIGINT, SIGTERM]).auto_err("Failed to init signal handler")?;
     let args = AppArgs::parse();
 
     #[cfg(target_os = "linux")]
     {
-        use crate::utils::command::CommandUtils;
+        use playmaster::utils::command::CommandUtils;
         CommandUtils::set_death_signal();
     }
 
-    LoggerUtils::init();
+    // Before the logger so `RUST_LOG` can come from the env file, and before the runtime starts
+    // its worker threads, as setting env vars is only sound while no other thread is running.
+    let env_file = DotEnvUtils::load(args.env_file.as_deref(), args.env_override);
+
+    LoggerUtils::init(&args.log_format, args.is_json_output());
 
     aws_lc_rs::default_provider()
         .install_default()
@@ -52,8 +46,34 @@ async fn main() -> EmptyResult {
     let version = env!("CARGO_PKG_VERSION");
     info!("🔧 PlayMaster, Version: {version}");
 
+    match env_file {
+        Ok(Some(summary)) => {
+            info!(
+                "Loaded {} env vars from {}",
+                summary.loaded,
+                summary.path.display()
+            );
+            for key in summary.kept {
+                debug!("Kept existing env var {}", key);
+            }
+        }
+        Ok(None) => debug!("No env file found, skipping"),
+        Err(e) => {
+            error!("❌ Error: {e}");
+            std::process::exit(ExitReason::Error.code());
+        }
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .auto_err("Failed to start the async runtime")?
+        .block_on(run(args, signals))
+}
+
+async fn run(args: AppArgs, mut signals: Signals) -> EmptyResult {
     // Channel to notify when to exit
-    let (tx, rx) = mpsc::channel::<&'static str>();
+    let (tx, rx) = mpsc::channel::<ExitReason>();
 
     // 🧩 Spawn your worker thread
     let tx_worker = tx.clone();
@@ -66,12 +86,12 @@ async fn main() -> EmptyResult {
         let result = process_command(args).await;
 
         match result {
-            Ok(_) => {
-                let _ = tx_worker.send("done");
+            Ok(reason) => {
+                let _ = tx_worker.send(reason);
             }
             Err(e) => {
                 error!("❌ Error: {e}");
-                let _ = tx_worker.send("error");
+                let _ = tx_worker.send(ExitReason::Error);
             }
         }
 
@@ -80,29 +100,34 @@ async fn main() -> EmptyResult {
 
     // 🧩 Spawn signal handler thread
     let tx_signal = tx.clone();
-    thread::spawn(move || {
-        for sig in signals.forever() {
-            match sig {
-                SIGINT => {
-                    warn!("Received SIGINT (Ctrl+C)");
-                    let _ = tx_signal.send("signal");
-                    break;
-                }
-                SIGTERM => {
-                    warn!("Received SIGTERM (system kill)");
-                    let _ = tx_signal.send("signal");
-                    break;
+    let signal_thread = thread::Builder::new().name("signal".to_owned());
+    signal_thread
+        .spawn(move || {
+            for sig in signals.forever() {
+                match sig {
+                    SIGINT => {
+                        warn!("Received SIGINT (Ctrl+C)");
+                        let _ = tx_signal.send(ExitReason::Signal);
+                        break;
+                    }
+                    SIGTERM => {
+                        warn!("Received SIGTERM (system kill)");
+                        let _ = tx_signal.send(ExitReason::Signal);
+                        break;
+                    }
+                    _ => {}
                 }
-                _ => {}
             }
-        }
-    });
+        })
+        .auto_err("Failed to spawn signal handler thread")?;
 
     // 🧩 Wait for either thread to finish
-    match rx.recv() {
-        Ok("done") => info!("✅ Execution completed successfully."),
-        Ok("error") => error!("❌ Execution ended with error."),
-        Ok("signal") => {
+    let reason = rx.recv().unwrap_or(ExitReason::Error);
+    match reason {
+        ExitReason::Done => info!("✅ Execution completed successfully."),
+        ExitReason::TestsFailed(failed) => error!("❌ Execution ended with {failed} failed tests."),
+        ExitReason::Error => error!("❌ Execution ended with error."),
+        ExitReason::Signal => {
             warn!("⚠️ Termination signal received.");
             if let Err(err) = ExecutionUtils::set_running(false) {
                 error!("Failed to set running to false: {}", err);
@@ -112,42 +137,111 @@ async fn main() -> EmptyResult {
                 error!("Failed to terminate running commands: {}", err);
             }
         }
-        _ => error!("Unknown exit reason."),
     }
 
-    Ok(())
+    std::process::exit(reason.code());
+}
+
+/// Why the execution ended, mapped to the process exit code documented in the README.
+#[derive(Debug, Clone, Copy)]
+enum ExitReason {
+    /// Command succeeded, all tests passed.
+    Done,
+    /// Tests ran but some failed.
+    TestsFailed(i16),
+    /// Setup, pre-hook or command error before the results were known.
+    Error,
+    /// Terminated by SIGINT/SIGTERM, after cleaning up running commands.
+    Signal,
 }
 
-async fn process_command(args: AppArgs) -> EmptyResult {
+impl ExitReason {
+    fn code(&self) -> i32 {
+        match self {
+            ExitReason::Done => 0,
+            ExitReason::TestsFailed(_) => 1,
+            ExitReason::Error => 2,
+            ExitReason::Signal => 130,
+        }
+    }
+}
+
+async fn process_command(args: AppArgs) -> ResultWithError<ExitReason> {
     match args.command {
         models::args::Command::Run { .. } => {
-            let config = Config::from_curr_dir()?;
-            let vars = Vars::all_from_curr_dir()?;
-            let run = CodeRun::new(args, config, vars);
-            run.execute().await
+            if args.non_interactive && args.is_interactive() {
+                return Err("--interactive can't be used with --non-interactive".into());
+            }
+
+            let config = Config::from_curr_dir(args.version_check)?;
+            let vars = Vars::all_from_curr_dir(&config.test_dirs)?.with_overrides(&args.vars)?;
+            let results = playmaster::run(&args, config, vars).await?;
+            if results.failed > 0 {
+                return Ok(ExitReason::TestsFailed(results.failed));
+            }
+            Ok(ExitReason::Done)
         }
-        models::args::Command::Gen => {
-            let config = Config::from_curr_dir()?;
-            let vars = Vars::all_from_curr_dir()?;
+        models::args::Command::Gen { .. } => {
+            let config = Config::from_curr_dir(args.version_check)?;
+            let vars = Vars::all_from_curr_dir(&config.test_dirs)?.with_overrides(&args.vars)?;
             let code_gen = CodeGen::new(args, config, vars);
-            code_gen.execute()
+            code_gen.execute()?;
+            Ok(ExitReason::Done)
         }
-        models::args::Command::Schema => {
-            let schema_gen = SchemaGen::new();
-            schema_gen.execute()
+        models::args::Command::Schema { out } => {
+            let schema_gen = SchemaGen::new(out);
+            schema_gen.execute()?;
+            Ok(ExitReason::Done)
         }
-        models::args::Command::Gmail => {
-            let config = Config::from_curr_dir()?;
+        models::args::Command::Doctor => {
+            let doctor = Doctor::new(args);
+            doctor.execute().await?;
+            Ok(ExitReason::Done)
+        }
+        models::args::Command::Gmail { imap: true, .. } => {
+            let config = Config::from_curr_dir(args.version_check)?;
+            let imap_config = config
+                .gmail
+                .credentials
+                .imap
+                .as_ref()
+                .auto_err("No gmail.credentials.imap section in playmaster.yaml")?;
+
+            let vars = Vars::all_from_curr_dir(&config.test_dirs)?.with_overrides(&args.vars)?;
+            let app_password = imap_config.resolve_app_password(&vars);
+            if app_password.trim().is_empty() {
+                return Err(format!(
+                    "The IMAP app password of {} is empty, check the env var it is read from",
+                    imap_config.email
+                )
+                .into());
+            }
+
+            ImapGmailClient::new(imap_config.email.clone(), app_password).validate_credentials()?;
+            info!("IMAP credentials of {} are valid", imap_config.email);
+            Ok(ExitReason::Done)
+        }
+        models::args::Command::Gmail { imap: false, .. } => {
+            let config = Config::from_curr_dir(args.version_check)?;
 
             let gmail_client = if config.gmail.enabled
                 && let Some(creds) = config.gmail.credentials.s3
             {
-                GmailClient::new(Some(creds.bucket), Some(creds.key_prefix))
+                GmailClient::new(Some(creds))
             } else {
-                GmailClient::new(None, None)
+                GmailClient::new(None)
             };
 
-            gmail_client.generate_refresh_token().await
+            let storage = gmail_client.generate_refresh_token().await?;
+            if args.is_json_output() {
+                println!(
+                    "{}",
+                    serde_json::json!({ "status": "stored", "storage": storage })
+                );
+            } else {
+                info!("Gmail token stored in {}", storage);
+            }
+            Ok(ExitReason::Done)
         }
     }
 }
@@ -1,17 +1,27 @@
 use std::{
-    io::{Read as _, Write as _},
-    net::TcpStream,
-    sync::mpsc::{self, Receiver},
-    time::Duration,
+    fmt, fs,
+    io::{Read as _, Write},
+    net::{TcpStream, ToSocketAddrs as _},
+    os::unix::net::UnixStream,
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver},
+    },
+    time::{Duration, Instant},
 };
 
 use chrono::DateTime;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use ssh2::{PtyModes, Session};
 use terminal_size::{Height, Width, terminal_size};
+use tracing::{debug, info, warn};
 
-use crate::utils::errors::ResultWithError;
+use crate::utils::command::CommandUtils;
+use crate::utils::errors::{EmptyResult, ResultTrait, ResultWithError};
+use crate::utils::logger::LoggerUtils;
 use crate::utils::serializers::date_serializer;
+use crate::utils::shlex::ShlexUtils;
 
 #[derive(Default, Debug)]
 pub struct CommandOutput {
@@ -30,21 +40,118 @@ pub struct AppState {
     pub sudo_password: String,
     pub root_dir: String,
     pub results: Results,
+    /// Setup already completed with the current config, skipped because of `--once`
+    pub setup_complete: bool,
+    /// URL of the logs uploaded by the result sinks, if any
+    pub logs_url: Option<String>,
 }
 
-#[derive(Clone, Debug, Default, Serialize)]
+/// Results of the last run, relative to the project root, published again by `--report-only`.
+pub const LAST_RESULTS_FILE: &str = ".playmaster/last_results.json";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Results {
     pub total: i16,
     pub passed: i16,
     pub failed: i16,
-    pub error: Vec<String>,
+    /// Failed tests, counted in `failed`, whose `before_each` steps failed, e.g. broken fixtures
+    #[serde(default)]
+    pub setup_failed: i16,
+    pub skipped: i16,
+    pub error: Vec<ResultError>,
     pub full_log: String,
+    /// Duration of each passed or failed test, recorded with `--timings-file`
+    #[serde(skip)]
+    pub durations_ms: Vec<(String, u64)>,
+    /// Slice of the tests these results cover when sharding, e.g. `2/4`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<String>,
+    /// Wall-clock duration of each phase of the run, in order, recorded with `--profile-build`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phase_timings: Vec<PhaseTiming>,
     #[serde(with = "date_serializer")]
     pub start_time: DateTime<chrono::Utc>,
     #[serde(with = "date_serializer")]
     pub end_time: DateTime<chrono::Utc>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    /// Phase name, e.g. `connect`, `build` or `sync integration tests`
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+/// Error preventing the run or a part of it, tagged with the phase it happened in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResultError {
+    pub phase: ErrorPhase,
+    pub message: String,
+}
+
+impl fmt::Display for ResultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.phase.as_str(), self.message)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPhase {
+    /// Connecting to the remote host
+    Connect,
+    /// Checking the dependencies and the system
+    Verify,
+    /// Preparing the system and the tests before the build
+    Prepare,
+    /// Building the app and syncing it to the remote host
+    Build,
+    /// Driving the tests
+    Run,
+    /// Publishing the results and running the finished hooks
+    Report,
+}
+
+impl ErrorPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorPhase::Connect => "connect",
+            ErrorPhase::Verify => "verify",
+            ErrorPhase::Prepare => "prepare",
+            ErrorPhase::Build => "build",
+            ErrorPhase::Run => "run",
+            ErrorPhase::Report => "report",
+        }
+    }
+}
+
+impl Results {
+    /// No test failed and no error occurred.
+    pub fn is_success(&self) -> bool {
+        self.failed == 0 && self.error.is_empty()
+    }
+
+    pub fn load(path: &Path) -> ResultWithError<Self> {
+        let content = fs::read_to_string(path).auto_err(&format!(
+            "Failed to read results file {}, run the tests first",
+            path.display()
+        ))?;
+        serde_json::from_str(&content)
+            .auto_err(&format!("Failed to parse results file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> EmptyResult {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        debug!("Results saved to {}", path.display());
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct OsInfo {
     pub is_ostree: bool,
@@ -56,32 +163,146 @@ pub struct RemoteInfo {
     pub host: String,
     pub port: u16,
     pub password: String,
+    /// Interval of the SSH keepalives and "still running" heartbeats, 0 to disable them
+    pub keepalive_interval_secs: u32,
+    /// Timeout of the TCP connection, so a dead host doesn't hang on the OS default
+    pub connect_timeout: Duration,
+    /// Bastion the host is only reachable through, with its own credentials
+    pub jump: Option<Box<RemoteInfo>>,
 }
 
 impl RemoteInfo {
-    pub fn get_sess(&self) -> ResultWithError<Session> {
-        let tcp = TcpStream::connect((&self.host[..], self.port))?;
+    /// Connects to the first reachable address of `host`, waiting at most `timeout` for each.
+    pub fn connect_tcp(host: &str, port: u16, timeout: Duration) -> ResultWithError<TcpStream> {
+        let mut last_err = None;
+        for addr in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(tcp) => return Ok(tcp),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        match last_err {
+            Some(e) => Err(e.into()),
+            None => Err(format!("No address found for {}", host).into()),
+        }
+    }
+
+    /// Connects and handshakes with the host, through the jump host if any, without authenticating.
+    pub fn connect(&self) -> ResultWithError<Session> {
         let mut sess = Session::new()?;
-        sess.set_tcp_stream(tcp);
+        match self.jump.as_deref() {
+            Some(jump) => sess.set_tcp_stream(jump.tunnel_to(&self.host, self.port)?),
+            None => sess.set_tcp_stream(Self::connect_tcp(
+                &self.host,
+                self.port,
+                self.connect_timeout,
+            )?),
+        }
         sess.handshake()?;
+        Ok(sess)
+    }
+
+    /// Opens a `direct-tcpip` channel to `host:port` from this host and bridges it to a local
+    /// socket, for the session with the final host to be established over it.
+    fn tunnel_to(&self, host: &str, port: u16) -> ResultWithError<UnixStream> {
+        let jump_sess = self.get_sess()?;
+        let mut channel = jump_sess.channel_direct_tcpip(host, port, None)?;
+        let (local, mut bridge) = UnixStream::pair()?;
+        bridge.set_nonblocking(true)?;
+        jump_sess.set_blocking(false);
+
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 16384];
+            loop {
+                let mut made_progress = false;
+
+                match channel.read(&mut buf) {
+                    Ok(0) if channel.eof() => break,
+                    Ok(n) if n > 0 => {
+                        made_progress = true;
+                        if Self::write_all_nonblocking(&mut bridge, &buf[..n]).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() != std::io::ErrorKind::WouldBlock => break,
+                    _ => {}
+                }
+
+                match bridge.read(&mut buf) {
+                    // The tunneled session was dropped
+                    Ok(0) => break,
+                    Ok(n) => {
+                        made_progress = true;
+                        if Self::write_all_nonblocking(&mut channel, &buf[..n]).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() != std::io::ErrorKind::WouldBlock => break,
+                    _ => {}
+                }
+
+                let _ = jump_sess.keepalive_send();
+                if !made_progress {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            }
+
+            let _ = channel.close();
+        });
+
+        Ok(local)
+    }
+
+    fn write_all_nonblocking(writer: &mut impl Write, mut data: &[u8]) -> std::io::Result<()> {
+        while !data.is_empty() {
+            match writer.write(data) {
+                Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+                Ok(n) => data = &data[n..],
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        writer.flush()
+    }
+
+    /// `ssh` command for tools such as rsync, going through the jump host if any.
+    pub fn ssh_command(&self) -> String {
+        match self.jump.as_deref() {
+            Some(jump) => format!(
+                "ssh -p {} -J {}@{}:{}",
+                self.port, jump.user, jump.host, jump.port
+            ),
+            None => format!("ssh -p {}", self.port),
+        }
+    }
+
+    pub fn get_sess(&self) -> ResultWithError<Session> {
+        let sess = self.connect()?;
         sess.userauth_password(&self.user, &self.password)?;
         if !sess.authenticated() {
             return Err("SSH auth failed".into());
         }
 
+        // Keep idle channels open during long silent builds, sent from the read loops
+        if self.keepalive_interval_secs > 0 {
+            sess.set_keepalive(false, self.keepalive_interval_secs);
+        }
+
         Ok(sess)
     }
 
     pub fn exec(&self, cmd: &str) -> ResultWithError<CommandOutput> {
-        let cmd = cmd.replace("\\$", "$");
         let sess = self.get_sess()?;
 
         let mut channel = sess.channel_session()?;
         channel.request_pty("xterm", None, None)?;
 
-        // Run the command safely through bash -c
-        let bash_cmd = format!("bash -c '{}'", cmd.replace("'", "'\\''"));
-        channel.exec(&bash_cmd)?;
+        // Run the command safely through the configured shell
+        let shell_cmd = Self::shell_cmd(cmd)?;
+        channel.exec(&shell_cmd)?;
 
         // Set non-blocking mode so we can read both stdout and stderr without deadlocking (including carriage returns)
         sess.set_blocking(false);
@@ -91,6 +312,7 @@ impl RemoteInfo {
 
         let mut out_buf = [0u8; 4096];
         let mut err_buf = [0u8; 4096];
+        let mut heartbeat = Heartbeat::new(self.keepalive_interval_secs);
 
         loop {
             let mut made_progress = false;
@@ -101,15 +323,17 @@ impl RemoteInfo {
                     let chunk = String::from_utf8_lossy(&out_buf[..n]);
                     // print without forcing newlines so carriage returns updates correctly
                     if chunk != "exited" {
-                        print!("[Remote Log]: {chunk}");
+                        if LoggerUtils::is_json() {
+                            info!("[Remote Log]: {}", chunk.trim_end());
+                        } else {
+                            print!("[Remote Log]: {chunk}");
+                            std::io::stdout().flush().ok();
+                        }
                     }
-                    std::io::stdout().flush().ok();
                     stdout.push_str(&chunk);
                 }
-                Err(e) => {
-                    if !self.is_would_block(&e) {
-                        return Err(e.into());
-                    }
+                Err(e) if !self.is_would_block(&e) => {
+                    return Err(e.into());
                 }
                 _ => {}
             }
@@ -118,14 +342,16 @@ impl RemoteInfo {
                 Ok(n) if n > 0 => {
                     made_progress = true;
                     let chunk = String::from_utf8_lossy(&err_buf[..n]);
-                    eprint!("[Remote Error Log]: {chunk}");
-                    std::io::stderr().flush().ok();
+                    if LoggerUtils::is_json() {
+                        warn!("[Remote Error Log]: {}", chunk.trim_end());
+                    } else {
+                        eprint!("[Remote Error Log]: {chunk}");
+                        std::io::stderr().flush().ok();
+                    }
                     stderr.push_str(&chunk);
                 }
-                Err(e) => {
-                    if !self.is_would_block(&e) {
-                        return Err(e.into());
-                    }
+                Err(e) if !self.is_would_block(&e) => {
+                    return Err(e.into());
                 }
                 _ => {}
             }
@@ -135,6 +361,8 @@ impl RemoteInfo {
                 break;
             }
 
+            heartbeat.tick(&sess, made_progress);
+
             // If neither stream had data this tick, back off briefly
             if !made_progress {
                 std::thread::sleep(Duration::from_millis(50));
@@ -152,16 +380,22 @@ impl RemoteInfo {
         })
     }
 
+    /// Runs the command through the configured shell, quoted so it reaches it unchanged.
+    fn shell_cmd(cmd: &str) -> ResultWithError<String> {
+        Ok(format!(
+            "{} -c {}",
+            CommandUtils::shell_program(),
+            ShlexUtils::quote(cmd)?
+        ))
+    }
+
     fn is_would_block(&self, e: &std::io::Error) -> bool {
         matches!(e.kind(), std::io::ErrorKind::WouldBlock)
     }
 
     /// Executes a remote command and yields stdout lines in real-time.
-    pub fn exec_remote_stream<'a>(
-        &'a self,
-        cmd: &str,
-    ) -> ResultWithError<impl Iterator<Item = String> + 'a> {
-        let cmd = cmd.replace("\\$", "$");
+    /// The exit code can be read from the iterator once it is exhausted.
+    pub fn exec_remote_stream(&self, cmd: &str) -> ResultWithError<RemoteLineIterator> {
         let sess = self.get_sess()?;
 
         // Get local terminal width/height
@@ -177,12 +411,15 @@ impl RemoteInfo {
         let mut channel = sess.channel_session()?;
         channel.request_pty("xterm", Some(modes), Some((cols as u32, rows as u32, 0, 0)))?;
 
-        let shell_cmd = format!("sh -c '{}'", cmd.replace("'", "'\\''"));
+        let shell_cmd = Self::shell_cmd(cmd)?;
         channel.exec(&shell_cmd)?;
 
         sess.set_blocking(false);
 
         let (tx, rx) = mpsc::channel::<String>();
+        let exit_code = Arc::new(Mutex::new(None));
+        let thread_exit_code = Arc::clone(&exit_code);
+        let mut heartbeat = Heartbeat::new(self.keepalive_interval_secs);
 
         // Spawn a thread that continuously reads stdout and sends complete lines
         std::thread::spawn(move || {
@@ -192,6 +429,7 @@ impl RemoteInfo {
             loop {
                 match channel.read(&mut tmp_buf) {
                     Ok(n) if n > 0 => {
+                        heartbeat.tick(&sess, true);
                         buffer.extend_from_slice(&tmp_buf[..n]);
                         // Split by newlines for streaming
                         while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
@@ -205,6 +443,7 @@ impl RemoteInfo {
                         if channel.eof() {
                             break;
                         }
+                        heartbeat.tick(&sess, false);
                         std::thread::sleep(Duration::from_millis(30));
                     }
                     Err(_) => break,
@@ -223,15 +462,67 @@ impl RemoteInfo {
             }
 
             let _ = channel.wait_close();
+
+            // Stored before `tx` is dropped, so it is set by the time the iterator ends
+            if let Ok(status) = channel.exit_status()
+                && let Ok(mut exit_code) = thread_exit_code.lock()
+            {
+                *exit_code = Some(status);
+            }
         });
 
         // Return an iterator that yields lines from the channel
-        Ok(RemoteLineIterator { rx })
+        Ok(RemoteLineIterator { rx, exit_code })
     }
 }
 
-struct RemoteLineIterator {
+/// Sends the SSH keepalives and tells the user a silent remote command is still running.
+struct Heartbeat {
+    interval: Duration,
+    start: Instant,
+    last_activity: Instant,
+}
+
+impl Heartbeat {
+    fn new(interval_secs: u32) -> Self {
+        let now = Instant::now();
+        Heartbeat {
+            interval: Duration::from_secs(interval_secs as u64),
+            start: now,
+            last_activity: now,
+        }
+    }
+
+    fn tick(&mut self, sess: &Session, had_output: bool) {
+        if self.interval.is_zero() {
+            return;
+        }
+
+        // Only sends when due, errors surface on the next channel read anyway
+        let _ = sess.keepalive_send();
+
+        if had_output {
+            self.last_activity = Instant::now();
+        } else if self.last_activity.elapsed() >= self.interval {
+            self.last_activity = Instant::now();
+            info!(
+                "⏳ Still running ({}m elapsed)",
+                self.start.elapsed().as_secs() / 60
+            );
+        }
+    }
+}
+
+pub struct RemoteLineIterator {
     rx: Receiver<String>,
+    exit_code: Arc<Mutex<Option<i32>>>,
+}
+
+impl RemoteLineIterator {
+    /// Exit code of the remote command, only available once all lines were consumed.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code.lock().ok().and_then(|code| *code)
+    }
 }
 
 impl Iterator for RemoteLineIterator {
@@ -240,3 +531,80 @@ impl Iterator for RemoteLineIterator {
         self.rx.recv().ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    fn sh_output(cmd: &str) -> String {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .output()
+            .expect("Should run sh");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn shell_cmd_runs_the_command_as_written() {
+        // The SSH server runs the exec string through the login shell, like `sh -c`
+        for cmd in [
+            "printf %s 'it'\\''s'",
+            "printf '%s|' \"$((1 + 2))\" \"`echo hi`\" \"$(echo there)\"",
+            "X='a b'; printf %s \"$X\"",
+            "printf '%s\\n' first && printf %s second",
+        ] {
+            let wrapped = RemoteInfo::shell_cmd(cmd).expect("Should wrap");
+            assert_eq!(sh_output(&wrapped), sh_output(cmd), "{}", wrapped);
+        }
+    }
+
+    #[test]
+    fn results_round_trip_through_the_results_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".playmaster/last_results.json");
+        let start_time = DateTime::parse_from_rfc3339("2026-10-17T08:30:00Z")
+            .unwrap()
+            .to_utc();
+        let results = Results {
+            total: 5,
+            passed: 3,
+            failed: 1,
+            setup_failed: 1,
+            skipped: 1,
+            error: vec![ResultError {
+                phase: ErrorPhase::Run,
+                message: "Login - Valid timed out".to_owned(),
+            }],
+            full_log: "flutter drive output".to_owned(),
+            durations_ms: vec![("Login - Valid".to_owned(), 1200)],
+            shard: Some("2/4".to_owned()),
+            start_time,
+            end_time: start_time + chrono::Duration::minutes(3),
+            ..Default::default()
+        };
+
+        results.save(&path).unwrap();
+        let loaded = Results::load(&path).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&results).unwrap()
+        );
+        assert_eq!(loaded.error[0].to_string(), "[run] Login - Valid timed out");
+        assert_eq!(loaded.start_time, start_time);
+        // Only kept for the timings file
+        assert!(loaded.durations_ms.is_empty());
+    }
+
+    #[test]
+    fn missing_results_file_asks_for_a_run() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = Results::load(&dir.path().join("last_results.json")).unwrap_err();
+
+        assert!(err.to_string().contains("run the tests first"), "{err}");
+    }
+}
@@ -1,18 +1,51 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use clap::{Parser, Subcommand, ValueEnum};
 
+use crate::{models::shard::Shard, utils::errors::EmptyResult};
+
 #[derive(Debug, Clone, ValueEnum, PartialEq)]
 pub enum AppMode {
     Local,
     Remote,
 }
 
+#[derive(Debug, Clone, ValueEnum, PartialEq)]
+pub enum LogFormat {
+    /// Human readable single-line logs
+    Pretty,
+    /// One JSON object per event, for log aggregators
+    Json,
+}
+
+#[derive(Debug, Clone, ValueEnum, PartialEq)]
+pub enum OutputFormat {
+    /// Logged for humans
+    Text,
+    /// One JSON object on stdout, the logs going to stderr
+    Json,
+}
+
 #[derive(Debug, Subcommand, Clone)]
 pub enum Command {
     /// Generate Dart integration tests from YAML files
-    Gen,
+    Gen {
+        /// Only generate the tests with this tag, can be repeated to accept any of several tags
+        ///
+        /// Example: --tag smoke
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
 
     /// Generate JSON schema for the YAML files
-    Schema,
+    Schema {
+        /// Directory to write the schemas to
+        #[arg(long, default_value = "schemas")]
+        out: PathBuf,
+    },
 
     /// Run tests in either local or remote mode
     Run {
@@ -20,7 +53,7 @@ pub enum Command {
         ///
         /// When in remote mode, the controller will connect to an IP address in which to run the tests
         /// When in local mode, the controller will run the tests in the local machine
-        #[arg(short, long, value_enum)]
+        #[arg(short, long, value_enum, env = "PLAYMASTER_MODE")]
         mode: Option<AppMode>,
 
         /// Auto accept dependency installation prompts
@@ -31,14 +64,132 @@ pub enum Command {
         #[arg(short, long, default_value_t = false)]
         setup: bool,
 
+        /// Skip the dependency checks and setup tasks when they already completed with the
+        /// current config, as recorded in `<root dir>/.setup-complete`
+        #[arg(long, default_value_t = false)]
+        once: bool,
+
         /// Address of the remote host to connect to in remote mode, such as: user@ip_address:port
         /// Example: dev@192.168.1.100:22
-        #[arg(short = 'a', long)]
+        #[arg(short = 'a', long, env = "PLAYMASTER_REMOTE_ADDR")]
         remote_addr: Option<String>,
+
+        /// Jump host the remote host is only reachable through, such as: user@ip_address:port
+        ///
+        /// Its password is read from `JUMP_PASSWORD` or prompted for
+        #[arg(long, env = "PLAYMASTER_JUMP")]
+        jump: Option<String>,
+
+        /// Overwrite the screenshot baselines with the screenshots captured during this run
+        /// instead of comparing against them
+        #[arg(long, default_value_t = false)]
+        update_screenshots: bool,
+
+        /// Only run features whose name contains this value (case-insensitive)
+        ///
+        /// When exactly one feature matches, its own generated test file is built and driven
+        /// instead of `all_tests.dart`
+        #[arg(short, long)]
+        filter: Option<String>,
+
+        /// Flutter device id to run the tests on, as listed by `flutter devices`
+        ///
+        /// Overrides the `device` config value, defaults to `linux`
+        #[arg(short, long)]
+        device: Option<String>,
+
+        /// Set an env var for the `flutter drive` process, locally or on the remote, can be repeated
+        ///
+        /// Takes precedence over the `run_env` config values. Values are redacted in the logs.
+        /// Example: --env API_URL=https://staging.example.com
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = AppArgs::parse_env)]
+        env: Vec<(String, String)>,
+
+        /// Generate the tests and check dependencies, then print the build, sync and drive
+        /// commands that would be executed without running them
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Run each test this many times to hunt flaky tests, only the features matching `--filter` if set
+        ///
+        /// The tests are regenerated with one `testWidgets` per iteration, suffixed with `(run i/N)`,
+        /// so all iterations run in a single `flutter drive`
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        repeat: u32,
+
+        /// Seconds to wait for the TCP connection to the remote host, per attempt
+        #[arg(long, default_value_t = 10)]
+        connect_timeout: u64,
+
+        /// Times to retry connecting to the remote host, with a doubling delay, e.g. while it boots
+        #[arg(long, default_value_t = 3)]
+        connect_retries: u32,
+
+        /// Skip the build and drive the tests against the existing bundle, e.g. when only the
+        /// test YAML changed since the last run
+        #[arg(long, default_value_t = false)]
+        no_build: bool,
+
+        /// Record the wall-clock duration of each phase, e.g. connect, build, each sync and
+        /// drive, and print a breakdown at the end, also included in the saved results
+        #[arg(long, default_value_t = false)]
+        profile_build: bool,
+
+        /// Pause before each test step and ask whether to run it, skip it or abort, to step
+        /// through a failing flow
+        ///
+        /// The tests are regenerated with a pause around each step
+        #[arg(long, default_value_t = false)]
+        interactive: bool,
+
+        /// Don't generate the tests before running, and fail when they are missing or out of
+        /// date with the feature files
+        ///
+        /// By default they are generated when the feature files, config or flags changed since
+        /// the last generation
+        #[arg(long, default_value_t = false)]
+        no_gen: bool,
+
+        /// Only run this slice of the tests, such as 2/4 for the second of four CI machines
+        ///
+        /// The tests are sorted and split evenly, so each machine passing its own index runs
+        /// a disjoint set of tests
+        #[arg(long, value_name = "INDEX/COUNT")]
+        shard: Option<Shard>,
+
+        /// JSON file keeping the recent test durations, updated after the run and used by
+        /// `--shard` to balance the shards by time rather than by test count
+        #[arg(long, value_name = "PATH")]
+        timings_file: Option<PathBuf>,
+
+        /// Don't run the tests, publish the results of the last run to the result sinks again,
+        /// e.g. when the webhook was down
+        ///
+        /// The results of each run are kept in `.playmaster/last_results.json`
+        #[arg(long, default_value_t = false)]
+        report_only: bool,
+
+        /// Only generate and run the tests with this tag, can be repeated to accept any of several tags
+        ///
+        /// Example: --tag smoke
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
     },
 
+    /// Checks the tools, display, config and cloud credentials PlayMaster needs
+    Doctor,
+
     /// Generates a gmail refresh token for capturing MFA code automatically via Gmail API
-    Gmail,
+    Gmail {
+        /// Check the `gmail.credentials.imap` email and app password by logging in, instead of
+        /// generating a token, e.g. for CI to fail fast on a revoked app password
+        #[arg(long, default_value_t = false)]
+        imap: bool,
+
+        /// Format of the outcome, `json` to provision credentials from scripts
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -58,4 +209,239 @@ them locally or remotely.
 pub struct AppArgs {
     #[command(subcommand)]
     pub command: Command,
+
+    /// Format of the log output
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty, global = true)]
+    pub log_format: LogFormat,
+
+    /// Fail instead of prompting for a missing value or confirmation, e.g. on CI where a prompt
+    /// would wait forever
+    #[arg(
+        long,
+        default_value_t = false,
+        global = true,
+        env = "PLAYMASTER_NON_INTERACTIVE"
+    )]
+    pub non_interactive: bool,
+
+    /// Fail when a config or feature file has no `schema_version` or a newer minor one,
+    /// mismatched major versions always fail
+    #[arg(long, default_value_t = false, global = true)]
+    pub version_check: bool,
+
+    /// Validate feature test files against the JSON schema before loading them,
+    /// reporting the file and location of each mistake
+    #[arg(long, default_value_t = false, global = true)]
+    pub validate_schema: bool,
+
+    /// Env file to load variables from, defaults to `.env` in the current directory when present
+    #[arg(long, global = true)]
+    pub env_file: Option<PathBuf>,
+
+    /// Let variables from the env file overwrite ones already set in the environment
+    #[arg(long, default_value_t = false, global = true)]
+    pub env_override: bool,
+
+    /// Directory the Dart tests are generated into and driven from, relative to the project root
+    #[arg(long, default_value = "integration_test/generated", global = true)]
+    pub output_dir: PathBuf,
+
+    /// After generating the tests, warn about the vars of the `*.vars.yaml` files never referenced
+    #[arg(long, default_value_t = false, global = true)]
+    pub warn_unused_vars: bool,
+
+    /// Override a var of the `*.vars.yaml` files, taking precedence over the files, can be repeated
+    ///
+    /// The key is `vars.<file>.<key>`, `<file>.<key>` or just `<key>` when only one file defines it.
+    /// Example: --var common.validEmail=ci@test.com
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = AppArgs::parse_var, global = true)]
+    pub vars: Vec<(String, String)>,
+}
+
+impl AppArgs {
+    fn parse_var(s: &str) -> Result<(String, String), String> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_owned(), value.to_owned()))
+            }
+            _ => Err(format!("invalid var '{s}', expected KEY=VALUE")),
+        }
+    }
+
+    fn parse_env(s: &str) -> Result<(String, String), String> {
+        match s.split_once('=') {
+            Some((key, value)) if Self::is_env_name(key) => Ok((key.to_owned(), value.to_owned())),
+            _ => Err(format!(
+                "invalid env var '{s}', expected KEY=VALUE with a KEY made of letters, digits and _"
+            )),
+        }
+    }
+
+    /// Whether `name` can be set as a shell env var, e.g. `API_URL`.
+    pub fn is_env_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    /// Fails with `--non-interactive` instead of prompting for `what`, with a `hint` on how to
+    /// provide it otherwise.
+    pub fn ensure_can_prompt(&self, what: &str, hint: &str) -> EmptyResult {
+        if self.non_interactive {
+            return Err(
+                format!("{} required but running non-interactively; {}", what, hint).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the command prints its outcome as JSON on stdout, the logs then going to stderr.
+    pub fn is_json_output(&self) -> bool {
+        matches!(
+            self.command,
+            Command::Gmail {
+                output: OutputFormat::Json,
+                ..
+            }
+        )
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self.command, Command::Run { dry_run: true, .. })
+    }
+
+    pub fn filter(&self) -> Option<&str> {
+        match &self.command {
+            Command::Run { filter, .. } => filter.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn tags(&self) -> &[String] {
+        match &self.command {
+            Command::Gen { tags } | Command::Run { tags, .. } => tags,
+            _ => &[],
+        }
+    }
+
+    pub fn is_once(&self) -> bool {
+        matches!(self.command, Command::Run { once: true, .. })
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        matches!(
+            self.command,
+            Command::Run {
+                interactive: true,
+                ..
+            }
+        )
+    }
+
+    pub fn is_no_gen(&self) -> bool {
+        matches!(self.command, Command::Run { no_gen: true, .. })
+    }
+
+    pub fn is_report_only(&self) -> bool {
+        matches!(
+            self.command,
+            Command::Run {
+                report_only: true,
+                ..
+            }
+        )
+    }
+
+    pub fn is_profile_build(&self) -> bool {
+        matches!(
+            self.command,
+            Command::Run {
+                profile_build: true,
+                ..
+            }
+        )
+    }
+
+    pub fn is_no_build(&self) -> bool {
+        matches!(self.command, Command::Run { no_build: true, .. })
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        match self.command {
+            Command::Run {
+                connect_timeout, ..
+            } => Duration::from_secs(connect_timeout),
+            _ => Duration::from_secs(10),
+        }
+    }
+
+    pub fn connect_retries(&self) -> u32 {
+        match self.command {
+            Command::Run {
+                connect_retries, ..
+            } => connect_retries,
+            _ => 0,
+        }
+    }
+
+    pub fn shard(&self) -> Option<Shard> {
+        match self.command {
+            Command::Run { shard, .. } => shard,
+            _ => None,
+        }
+    }
+
+    pub fn timings_file(&self) -> Option<&Path> {
+        match &self.command {
+            Command::Run { timings_file, .. } => timings_file.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Env vars from `--env`, set for the `flutter drive` process.
+    pub fn run_env(&self) -> &[(String, String)] {
+        match &self.command {
+            Command::Run { env, .. } => env,
+            _ => &[],
+        }
+    }
+
+    pub fn repeat(&self) -> u32 {
+        match self.command {
+            Command::Run { repeat, .. } => repeat,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_can_be_repeated_on_run_and_gen() {
+        let args = AppArgs::parse_from(["playmaster", "run", "--tag", "smoke", "--tag", "@Login"]);
+        assert_eq!(args.tags(), ["smoke", "@Login"]);
+
+        let args = AppArgs::parse_from(["playmaster", "gen", "--tag", "smoke"]);
+        assert_eq!(args.tags(), ["smoke"]);
+
+        assert!(AppArgs::parse_from(["playmaster", "gen"]).tags().is_empty());
+    }
+
+    #[test]
+    fn tag_is_rejected_by_other_commands() {
+        assert!(AppArgs::try_parse_from(["playmaster", "doctor", "--tag", "smoke"]).is_err());
+        assert!(AppArgs::try_parse_from(["playmaster", "schema", "--tag", "smoke"]).is_err());
+        assert!(AppArgs::try_parse_from(["playmaster", "gmail", "--tag", "smoke"]).is_err());
+        // Not global, so it can't come before the command either
+        assert!(AppArgs::try_parse_from(["playmaster", "--tag", "smoke", "gen"]).is_err());
+    }
+
+    #[test]
+    fn tag_requires_a_value() {
+        assert!(AppArgs::try_parse_from(["playmaster", "run", "--tag"]).is_err());
+    }
 }
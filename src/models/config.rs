@@ -1,11 +1,16 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+};
 
 use schemars::JsonSchema;
 use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
 use tracing::debug;
 
 use crate::{
     hooks::iface::HookType,
+    models::{schema_version::SchemaVersion, vars::Vars},
     utils::{
         dir::DirUtils,
         errors::{ResultTrait, ResultWithError},
@@ -22,21 +27,60 @@ pub enum ProjectType {
 /// Configuration structure for the test controller application.
 #[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct Config {
+    /// Version of the YAML format the file was written for, e.g. `"1.0"`
+    #[serde(default)]
+    pub schema_version: Option<String>,
     pub project_type: ProjectType,
+    /// Directories containing the `*.test.yaml` and `*.vars.yaml` files, relative to the project root.
+    /// Defaults to `["feature_test"]`
+    #[serde(default = "Config::default_test_dirs")]
+    pub test_dirs: Vec<String>,
+    /// Default device id to run the tests on, as listed by `flutter devices`, defaults to `linux`
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Command used to build the app before running the tests, supports the `{{target}}` and `{{mode}}` placeholders.
+    /// Defaults to `flutter pub get && flutter build linux --{{mode}} --target={{target}}`
+    #[serde(default)]
+    pub build_command: Option<String>,
+    /// Env vars set for the `flutter drive` process, locally or on the remote, e.g. feature flags
+    /// or backend URLs. Overridden by `--env`, values are redacted in the logs
+    #[serde(default)]
+    pub run_env: BTreeMap<String, String>,
+    /// Default values for steps that don't set their own
+    #[serde(default)]
+    pub defaults: StepDefaults,
+    /// Shell used to run commands, locally and remotely, and the profile sourced before them
+    #[serde(default)]
+    pub shell: ShellConfig,
+    /// Proxy for HTTP requests (downloads and webhooks), e.g. `http://proxy.corp:3128`.
+    /// Overrides the `HTTP_PROXY`/`HTTPS_PROXY` env vars, which are used otherwise
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Seconds between SSH keepalives and "still running" heartbeats while a remote command
+    /// is silent, e.g. during a long build. Defaults to 60, 0 disables them
+    #[serde(default = "Config::default_keepalive_interval_secs")]
+    pub keepalive_interval_secs: u32,
     #[serde(default)]
     pub dependencies: Vec<Dependency>,
     #[serde(default)]
     pub state_set: StateSet,
     #[serde(default)]
     pub hooks: Vec<HookConfig>,
+    /// Webhooks called with the results, same as `webhook` sinks in `results`
     #[serde(default)]
     pub webhooks: Vec<WebhookConfig>,
     #[serde(default)]
+    pub results: ResultsConfig,
+    #[serde(default)]
     pub gmail: GmailConfig,
+    /// SHA-256 of the config file, before env vars expansion, to tell whether setup must run again
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub content_hash: String,
 }
 
 impl Config {
-    pub fn from_curr_dir() -> ResultWithError<Self> {
+    pub fn from_curr_dir(version_check: bool) -> ResultWithError<Self> {
         debug!("Loading configuration from current directory...");
 
         let config_path = DirUtils::curr_dir()?.join("playmaster.yaml");
@@ -45,19 +89,43 @@ impl Config {
         let content = fs::read_to_string(config_path).auto_err("Could not read config file")?;
         debug!("Config loaded");
 
-        let expanded = VariablesUtils::expand_env_vars(&content);
+        Self::from_content(&content, version_check)
+    }
+
+    /// Parses `playmaster.yaml` content, expanding the env vars it references.
+    pub fn from_content(content: &str, version_check: bool) -> ResultWithError<Self> {
+        let expanded = VariablesUtils::expand_env_vars(content);
         debug!("Config expanded");
 
         let mut config: Config =
             serde_yaml::from_str(&expanded).auto_err("Invalid config format")?;
         debug!("Config deserialized");
 
+        SchemaVersion::check(
+            config.schema_version.as_deref(),
+            "playmaster.yaml",
+            version_check,
+        )?;
+
         config.load_default_configs();
         debug!("Config default values loaded");
 
+        config.content_hash = Sha256::digest(content.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
         Ok(config)
     }
 
+    fn default_test_dirs() -> Vec<String> {
+        vec!["feature_test".to_owned()]
+    }
+
+    fn default_keepalive_interval_secs() -> u32 {
+        60
+    }
+
     fn load_default_configs(&mut self) {
         if self.project_type == ProjectType::Flutter {
             self.add_flutter_defaults();
@@ -65,15 +133,18 @@ impl Config {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone, JsonSchema)]
+#[derive(Debug, Deserialize, PartialEq, Clone, JsonSchema, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum WebhookType {
+    #[default]
     #[serde(alias = "Results", alias = "RESULTS")]
     Results,
 }
 
 #[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct WebhookConfig {
+    /// Defaults to `results`, the only type
+    #[serde(default)]
     pub webhook_type: WebhookType,
     pub url: String,
     #[serde(default)]
@@ -85,10 +156,129 @@ pub struct WebhookConfig {
 }
 
 #[derive(Debug, Deserialize, Clone, JsonSchema, Default)]
+pub struct ResultsConfig {
+    /// Destinations the results are published to once all tests completed
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Webhook(WebhookConfig),
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct S3Config {
     #[serde(default)]
     pub key_prefix: String,
     pub bucket: String,
+    /// Canned ACL of the uploaded logs, defaults to `private`
+    #[serde(default)]
+    pub acl: S3Acl,
+    /// Whether to share uploads through presigned URLs, which work on buckets blocking public access.
+    /// Defaults to true, otherwise plain `https://<bucket>.s3.amazonaws.com/<key>` URLs are used
+    #[serde(default = "S3Config::default_presign")]
+    pub presign: bool,
+    /// Validity of presigned URLs in seconds, defaults to 7 days which is also the maximum
+    #[serde(default = "S3Config::default_presign_expiry_secs")]
+    pub presign_expiry_secs: u64,
+    /// Custom endpoint for S3 compatible backends such as MinIO or localstack,
+    /// path-style addressing is used when set
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    /// Region of the bucket, defaults to the one from the AWS environment
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+impl S3Config {
+    fn default_presign() -> bool {
+        true
+    }
+
+    fn default_presign_expiry_secs() -> u64 {
+        7 * 24 * 60 * 60
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum S3Acl {
+    #[default]
+    Private,
+    PublicRead,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct StepDefaults {
+    /// Timeout of `wait_for` key and text steps, defaults to 5000
+    #[serde(default = "StepDefaults::default_wait_timeout_millis")]
+    pub wait_timeout_millis: u32,
+    /// Timeout of `wait_for` progress steps, defaults to 30000
+    #[serde(default = "StepDefaults::default_progress_timeout_millis")]
+    pub progress_timeout_millis: u32,
+    /// Timeout of `not_found` steps, defaults to 10000
+    #[serde(default = "StepDefaults::default_not_found_timeout_millis")]
+    pub not_found_timeout_millis: u32,
+    /// Whether `wait_for` steps settle after waiting, defaults to false
+    #[serde(default)]
+    pub settle: bool,
+    /// Timeout of the settles done by steps, defaults to Flutter's own 10 minutes
+    #[serde(default)]
+    pub settle_timeout_millis: Option<u32>,
+}
+
+impl StepDefaults {
+    fn default_wait_timeout_millis() -> u32 {
+        5000
+    }
+
+    fn default_progress_timeout_millis() -> u32 {
+        30000
+    }
+
+    fn default_not_found_timeout_millis() -> u32 {
+        10000
+    }
+}
+
+impl Default for StepDefaults {
+    fn default() -> Self {
+        Self {
+            wait_timeout_millis: Self::default_wait_timeout_millis(),
+            progress_timeout_millis: Self::default_progress_timeout_millis(),
+            not_found_timeout_millis: Self::default_not_found_timeout_millis(),
+            settle: false,
+            settle_timeout_millis: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct ShellConfig {
+    /// Shell binary commands are run through with `-c`, defaults to `bash`
+    #[serde(default = "ShellConfig::default_program")]
+    pub program: String,
+    /// User profile sourced before every command, e.g. `~/.zshrc`.
+    /// The playmaster env file (`<root>/.bashrc`) is always sourced after it
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+impl ShellConfig {
+    fn default_program() -> String {
+        "bash".to_owned()
+    }
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            program: Self::default_program(),
+            profile: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, JsonSchema, Default)]
@@ -96,6 +286,9 @@ pub struct StateSet {
     pub command: String,
     #[serde(default)]
     pub arguments: Vec<String>,
+    /// Run the command once before the tests and expose its trimmed stdout as `vars.state.output`
+    #[serde(default)]
+    pub capture_output: bool,
 }
 
 #[derive(Debug, Deserialize, Clone, JsonSchema)]
@@ -117,6 +310,14 @@ pub struct InstallSpec {
     /// Optional binary path inside the archive, e.g. "flutter/bin"
     pub bin_path: Option<String>,
 
+    /// Additional binary paths, added to PATH along with `bin_path`
+    #[serde(default)]
+    pub bin_paths: Vec<String>,
+
+    /// Environment variables exported after installation, e.g. `FLUTTER_ROOT`
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
     /// Optional setup command to run after installation
     pub setup: Option<String>,
 
@@ -168,3 +369,18 @@ pub struct ImapConfig {
     /// Can use environment variable syntax like ${GMAIL_APP_PASSWORD}
     pub app_password: String,
 }
+
+impl ImapConfig {
+    /// App password with its vars replaced, read from the environment when it is a `${VAR}` reference.
+    pub fn resolve_app_password(&self, vars: &Vars) -> String {
+        let app_password = vars.replace_var(&self.app_password, None);
+        match app_password
+            .trim()
+            .strip_prefix("${")
+            .and_then(|name| name.strip_suffix('}'))
+        {
+            Some(name) => env::var(name).unwrap_or_default(),
+            None => app_password,
+        }
+    }
+}
@@ -11,6 +11,8 @@ impl Config {
                     tool: "flutter".into(),
                     version: Some("3.29.2".into()),
                     bin_path: Some("flutter/bin".into()),
+                    bin_paths: vec![],
+                    env: Default::default(),
                     setup: Some("flutter --version || true".into()),
                     source: Some(crate::models::config::InstallSource::Url {
                         url: "https://storage.googleapis.com/flutter_infra_release/releases/stable/linux/flutter_linux_{{version}}-stable.tar.xz".into()
@@ -1,50 +1,127 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
 
-use schemars::JsonSchema;
-use serde::Deserialize;
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
 
-use crate::utils::{
-    dir::{DirUtils, YamlType},
-    errors::ResultWithError,
+use crate::{
+    models::schema_version::SchemaVersion,
+    utils::{
+        dir::{DirUtils, YamlType},
+        errors::{EmptyResult, ResultTrait, ResultWithError},
+        variables::VariablesUtils,
+    },
 };
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct FeatureTest {
+    /// Version of the YAML format the file was written for, e.g. `"1.0"`
+    #[serde(default)]
+    pub schema_version: Option<String>,
     pub name: String,
     #[serde(default)]
     pub description: String,
     pub before_each: Option<BeforeEach>,
+    pub before_all: Option<FeatureSetup>,
+    pub after_all: Option<FeatureSetup>,
     pub tests: Vec<TestCase>,
     #[serde(default)]
     pub vars: HashMap<String, String>,
     #[serde(default)]
     pub step_definitions: HashMap<String, Vec<Step>>,
+    /// Extra Dart import statements added to the generated file, e.g. `import 'package:app/utils.dart';`
+    #[serde(default)]
+    pub imports: Vec<String>,
+    /// Shared definition files, relative to this file, whose `vars` and `step_definitions`
+    /// are merged into this feature. Values defined in the feature itself take precedence
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// File the feature was loaded from
+    #[serde(skip)]
+    pub source_path: PathBuf,
+}
+
+/// Content of a file referenced by `include`, e.g. `_shared.defs.yaml`.
+/// It can include other files itself, its own values overriding the included ones.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct SharedDefinitions {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    #[serde(default)]
+    pub step_definitions: HashMap<String, Vec<Step>>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct TestCase {
     pub name: String,
     #[serde(default)]
     pub description: String,
     #[serde(default)]
     pub state: String,
+    /// Skips the test, reported as skipped by Flutter, with `true` or the reason as value.
+    /// Ignored when `only` is set
+    #[serde(default)]
+    pub skip: Option<Skip>,
+    /// Only generates the tests marked `only` across all features, to focus on them while writing tests
+    #[serde(default)]
+    pub only: bool,
+    /// Labels to select the test with `--tag`, e.g. `smoke`
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub steps: Vec<Step>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+/// `skip` value, a flag or the reason the test is skipped for, which implies skipping it.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum Skip {
+    Flag(bool),
+    Reason(String),
+}
+
+impl TestCase {
+    /// Whether the test is skipped, `only` taking precedence over `skip` so a focused test runs.
+    pub fn is_skipped(&self) -> bool {
+        !self.only && matches!(self.skip, Some(Skip::Flag(true) | Skip::Reason(_)))
+    }
+
+    /// Reason the test is skipped for, `None` when it runs or is skipped with `skip: true`.
+    pub fn skip_reason(&self) -> Option<&str> {
+        match &self.skip {
+            Some(Skip::Reason(reason)) if self.is_skipped() => Some(reason),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct BeforeEach {
     #[serde(default)]
     pub steps: Vec<Step>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+/// Steps executed once per feature (`setUpAll`/`tearDownAll`).
+/// No `WidgetTester` is available there, so only steps that don't interact with widgets are allowed.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct FeatureSetup {
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum SimpleStep {
     Settle,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum Step {
     WaitFor {
@@ -52,107 +129,597 @@ pub enum Step {
     },
     NotFound {
         not_found: FindBy,
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
         timeout_millis: Option<u32>,
     },
     Tap {
         tap: TapFindBy,
+        /// Timeout of the settles around the tap, defaults to the `defaults.settle_timeout_millis` config value
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        settle_timeout_millis: Option<u32>,
+    },
+    /// Taps a widget twice in quick succession, e.g. to open a list item
+    DoubleTap {
+        double_tap: FindBy,
+    },
+    /// Presses a widget and holds it before releasing, e.g. to open a context menu
+    LongPress {
+        long_press: FindBy,
+        /// Time the widget is held, defaults to 500ms
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        hold_millis: Option<u32>,
     },
     Type {
         r#type: TypeAction,
     },
+    /// Presses and releases a keyboard key, named like the `LogicalKeyboardKey` constants,
+    /// e.g. `escape` to close a dialog or `tab` to move the focus
+    Key {
+        key: String,
+    },
+    /// Empties a text field, e.g. before a `type` step with `append` on a field kept across runs
+    Clear {
+        clear: FindBy,
+    },
     Match {
         r#match: Match,
     },
     NotMatch {
         r#not_match: Match,
     },
+    MatchProperty {
+        match_property: MatchProperty,
+    },
+    MatchScroll {
+        match_scroll: MatchScroll,
+    },
     Scroll {
         scroll: ScrollTarget,
     },
+    /// Drags a widget onto another one, e.g. to reorder a list or drop a file on an upload zone
+    DragTo {
+        drag_to: DragTo,
+    },
+    /// Scrolls until a widget is built and visible, e.g. an item far down a long list
+    ScrollUntilVisible {
+        scroll_until_visible: ScrollUntilVisible,
+    },
+    /// Drags a widget in a direction by a fraction of its size, unlike `scroll` which takes pixels
+    Swipe {
+        swipe: SwipeAction,
+    },
     Pointer {
         pointer: PointerAction,
     },
+    Pinch {
+        pinch: PinchAction,
+    },
+    /// Leaves the mouse pointer over the center of a widget, e.g. to reveal tooltips or hover menus
+    Hover {
+        hover: FindBy,
+    },
+    /// Saves a screenshot to `integration_test/screenshots/captures/<name>.png` without comparing it
+    Capture {
+        capture: String,
+    },
+    /// Runs `then` when a widget is present after settling, `else` otherwise, e.g. to dismiss optional banners
+    If {
+        if_present: FindBy,
+        then: Vec<Step>,
+        #[serde(default)]
+        r#else: Option<Vec<Step>>,
+    },
+    /// Same as `if_present`, with the widget and the branches grouped under `if`
+    IfExists {
+        r#if: IfStep,
+    },
+    /// Runs a block of steps several times, the current index being available as `{{ loop.index }}`
+    Repeat {
+        repeat: RepeatAction,
+    },
     Use {
         use_step: String,
+        /// Arguments of the definition, replacing the `{{ name }}` usages in its steps
+        #[serde(default)]
+        with: HashMap<String, String>,
     },
     UserInput {
         user_input: UserInput,
     },
+    /// Same as the `settle` simple step, with a timeout
+    Settle {
+        settle: SettleAction,
+    },
     Simple(SimpleStep),
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+impl Step {
+    /// Whether the step, or one nested in it, presses a pointer which must be released at the end of the test.
+    pub fn has_pointer_down(&self, step_definitions: &HashMap<String, Vec<Step>>) -> bool {
+        let any = |steps: &[Step]| steps.iter().any(|s| s.has_pointer_down(step_definitions));
+        match self {
+            Step::Pointer {
+                pointer: PointerAction::Down { .. },
+            } => true,
+            Step::If { then, r#else, .. }
+            | Step::IfExists {
+                r#if: IfStep { then, r#else, .. },
+            } => any(then) || r#else.as_deref().is_some_and(any),
+            Step::Repeat { repeat } => any(&repeat.steps),
+            Step::Use { use_step, .. } => step_definitions.get(use_step).is_some_and(|s| any(s)),
+            _ => false,
+        }
+    }
+
+    /// Whether the step, or one nested in it, is a `user_input` declaring a variable for the next steps.
+    pub fn declares_input(&self, step_definitions: &HashMap<String, Vec<Step>>) -> bool {
+        let any = |steps: &[Step]| steps.iter().any(|s| s.declares_input(step_definitions));
+        match self {
+            Step::UserInput { .. } => true,
+            Step::If { then, r#else, .. }
+            | Step::IfExists {
+                r#if: IfStep { then, r#else, .. },
+            } => any(then) || r#else.as_deref().is_some_and(any),
+            Step::Repeat { repeat } => any(&repeat.steps),
+            Step::Use { use_step, .. } => step_definitions.get(use_step).is_some_and(|s| any(s)),
+            _ => false,
+        }
+    }
+
+    /// Short readable description of the step, its kind followed by its target,
+    /// e.g. `tap text: Log in` or `type by: key: email, value: a@b.c`.
+    pub fn label(&self) -> String {
+        fn describe(value: &serde_yaml::Value, nested: bool) -> String {
+            match value {
+                serde_yaml::Value::String(text) => text.clone(),
+                serde_yaml::Value::Number(number) => number.to_string(),
+                serde_yaml::Value::Bool(flag) => flag.to_string(),
+                serde_yaml::Value::Sequence(values) => format!("{} step(s)", values.len()),
+                serde_yaml::Value::Mapping(mapping) => {
+                    let entries = mapping
+                        .iter()
+                        .filter(|(_, v)| !matches!(v, serde_yaml::Value::Null))
+                        .filter(|(_, v)| !matches!(v, serde_yaml::Value::Bool(false)))
+                        .filter(|(_, v)| !v.as_mapping().is_some_and(|m| m.is_empty()))
+                        .map(|(k, v)| format!("{}: {}", describe(k, true), describe(v, true)))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    if nested && mapping.len() > 1 {
+                        format!("[{}]", entries)
+                    } else {
+                        entries
+                    }
+                }
+                _ => String::new(),
+            }
+        }
+
+        match serde_yaml::to_value(self) {
+            // The first field of every step is its kind, e.g. `tap`, holding its target
+            Ok(serde_yaml::Value::Mapping(mapping)) => match mapping.iter().next() {
+                Some((kind, target)) => {
+                    format!("{} {}", describe(kind, false), describe(target, false))
+                        .trim_end()
+                        .to_owned()
+                }
+                None => String::new(),
+            },
+            Ok(value) => describe(&value, false),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// Copy of the step with the `{{ name }}` usages of the `use_step` arguments replaced by
+    /// their values, in every text of the step and the steps nested in it.
+    pub fn with_args(&self, args: &HashMap<String, String>) -> ResultWithError<Step> {
+        fn replace(value: &mut serde_yaml::Value, args: &HashMap<String, String>) {
+            match value {
+                serde_yaml::Value::String(text) => {
+                    *text = VariablesUtils::replace_args(text, args);
+                }
+                serde_yaml::Value::Sequence(values) => {
+                    values.iter_mut().for_each(|value| replace(value, args));
+                }
+                serde_yaml::Value::Mapping(mapping) => {
+                    mapping.values_mut().for_each(|value| replace(value, args));
+                }
+                _ => {}
+            }
+        }
+
+        if args.is_empty() {
+            return Ok(self.clone());
+        }
+
+        let mut value = serde_yaml::to_value(self)?;
+        replace(&mut value, args);
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    /// Whether a `use_step` argument name can be used as `{{ name }}`, e.g. `email`.
+    fn is_param_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    /// Ensures every `use_step` in the step, or nested in it, refers to a known definition
+    /// without using itself. `chain` holds the definitions currently being expanded.
+    fn check_uses(
+        &self,
+        step_definitions: &HashMap<String, Vec<Step>>,
+        chain: &mut Vec<String>,
+    ) -> EmptyResult {
+        let nested: &[Step] = match self {
+            Step::If { then, r#else, .. }
+            | Step::IfExists {
+                r#if: IfStep { then, r#else, .. },
+            } => {
+                for step in r#else.iter().flatten() {
+                    step.check_uses(step_definitions, chain)?;
+                }
+                then
+            }
+            Step::Repeat { repeat } => &repeat.steps,
+            Step::Use { use_step, with } => {
+                if let Some(name) = with.keys().find(|name| !Self::is_param_name(name)) {
+                    return Err(format!(
+                        "Invalid argument '{}' of use_step '{}', expected letters, digits and _",
+                        name, use_step
+                    )
+                    .into());
+                }
+                if chain.contains(use_step) {
+                    chain.push(use_step.clone());
+                    return Err(format!("Recursive use_step chain: {}", chain.join(" -> ")).into());
+                }
+
+                let steps = step_definitions
+                    .get(use_step)
+                    .ok_or_else(|| format!("Step definition '{}' not found", use_step))?;
+                chain.push(use_step.clone());
+                for step in steps {
+                    step.check_uses(step_definitions, chain)?;
+                }
+                chain.pop();
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        for step in nested {
+            step.check_uses(step_definitions, chain)?;
+        }
+        Ok(())
+    }
+
+    /// Ensures `{{ loop.index }}` is only used in a `repeat` step, or in a definition used from
+    /// one, since the loop variable is undefined anywhere else in the generated code.
+    fn check_loop_index(
+        &self,
+        step_definitions: &HashMap<String, Vec<Step>>,
+        in_repeat: bool,
+    ) -> EmptyResult {
+        let (own, nested): (serde_yaml::Value, Vec<&Step>) = match self {
+            Step::Repeat { repeat } => {
+                for step in &repeat.steps {
+                    step.check_loop_index(step_definitions, true)?;
+                }
+                return Ok(());
+            }
+            Step::If {
+                if_present: finder,
+                then,
+                r#else,
+            }
+            | Step::IfExists {
+                r#if:
+                    IfStep {
+                        exists: finder,
+                        then,
+                        r#else,
+                    },
+            } => (
+                serde_yaml::to_value(finder)?,
+                then.iter().chain(r#else.iter().flatten()).collect(),
+            ),
+            Step::Use { use_step, with } => (
+                serde_yaml::to_value(with)?,
+                step_definitions
+                    .get(use_step)
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            ),
+            _ => (serde_yaml::to_value(self)?, vec![]),
+        };
+
+        if !in_repeat && Self::uses_loop_index(&own) {
+            return Err(format!(
+                "Step '{}' uses {{{{ loop.index }}}} outside of a repeat step",
+                self.label()
+            )
+            .into());
+        }
+        for step in nested {
+            step.check_loop_index(step_definitions, in_repeat)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a string of the serialized step reads `{{ loop.index }}`.
+    fn uses_loop_index(value: &serde_yaml::Value) -> bool {
+        match value {
+            serde_yaml::Value::String(text) => VariablesUtils::uses_loop_index(text),
+            serde_yaml::Value::Sequence(values) => values.iter().any(Self::uses_loop_index),
+            serde_yaml::Value::Mapping(mapping) => mapping
+                .iter()
+                .any(|(k, v)| Self::uses_loop_index(k) || Self::uses_loop_index(v)),
+            serde_yaml::Value::Tagged(tagged) => Self::uses_loop_index(&tagged.value),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct IfStep {
+    /// Widget to look for after settling
+    pub exists: FindBy,
+    /// Steps to run when the widget is present
+    pub then: Vec<Step>,
+    /// Steps to run otherwise
+    #[serde(default)]
+    pub r#else: Option<Vec<Step>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct RepeatAction {
+    /// Number of runs of the steps, also accepted as `count`
+    #[serde(alias = "count")]
+    pub times: u32,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct SettleAction {
+    /// Defaults to the `defaults.settle_timeout_millis` config value
+    #[serde(default, deserialize_with = "deserialize_opt_millis")]
+    #[schemars(with = "Option<DurationMillis>")]
+    pub timeout_millis: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct UserInput {
     pub name: String,
     #[serde(default)]
     pub gmail: Option<UserInputGmail>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum ProgressWidgetType {
     Linear,
     Radial,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum WaitFor {
     Key {
         key: String,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
         timeout_millis: Option<u32>,
+        /// Whether to settle after waiting, defaults to the `defaults.settle` config value
         #[serde(default)]
-        settle: bool,
+        settle: Option<bool>,
+        /// Timeout of the settle, defaults to the `defaults.settle_timeout_millis` config value
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        settle_timeout_millis: Option<u32>,
     },
     Text {
         text: String,
-        #[serde(default)]
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
         timeout_millis: Option<u32>,
+        /// Whether to settle after waiting, defaults to the `defaults.settle` config value
         #[serde(default)]
-        settle: bool,
+        settle: Option<bool>,
+        /// Timeout of the settle, defaults to the `defaults.settle_timeout_millis` config value
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        settle_timeout_millis: Option<u32>,
     },
     Delay {
+        #[serde(deserialize_with = "deserialize_millis")]
+        #[schemars(with = "DurationMillis")]
         delay: u64,
+        /// Whether to settle after waiting, defaults to the `defaults.settle` config value
         #[serde(default)]
-        settle: bool,
+        settle: Option<bool>,
+        /// Timeout of the settle, defaults to the `defaults.settle_timeout_millis` config value
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        settle_timeout_millis: Option<u32>,
     },
     Progress {
         progress: ProgressWidgetType,
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        timeout_millis: Option<u32>,
+        /// Whether to settle after waiting, defaults to the `defaults.settle` config value
         #[serde(default)]
+        settle: Option<bool>,
+        /// Timeout of the settle, defaults to the `defaults.settle_timeout_millis` config value
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        settle_timeout_millis: Option<u32>,
+    },
+    /// Waits for the app to signal that its network requests are done
+    NetworkIdle {
+        network_idle: NetworkIdleSignal,
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
         timeout_millis: Option<u32>,
+        /// Whether to settle after waiting, defaults to the `defaults.settle` config value
         #[serde(default)]
-        settle: bool,
+        settle: Option<bool>,
+        /// Timeout of the settle, defaults to the `defaults.settle_timeout_millis` config value
+        #[serde(default, deserialize_with = "deserialize_opt_millis")]
+        #[schemars(with = "Option<DurationMillis>")]
+        settle_timeout_millis: Option<u32>,
     },
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+/// PlayMaster can't see the network, the app shows a marker widget while requests are in flight.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct NetworkIdleSignal {
+    /// Key of the marker widget, defaults to `network_busy`
+    #[serde(default)]
+    pub marker: Option<String>,
+    /// Dart function `Future<void> Function(WidgetTester, Duration)` from the feature `imports`, replacing the marker check
+    #[serde(default)]
+    pub helper: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 
 pub struct ScrollTarget {
     pub by: FindBy,
     pub delta: Offset,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct DragTo {
+    pub from: FindBy,
+    pub to: FindBy,
+    /// Time the widget is held before moving, e.g. `500` for `LongPressDraggable`, defaults to 0
+    #[serde(default, deserialize_with = "deserialize_opt_millis")]
+    #[schemars(with = "Option<DurationMillis>")]
+    pub hold_millis: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ScrollUntilVisible {
+    pub target: FindBy,
+    /// Scroll view to scroll, defaults to the first `Scrollable`
+    #[serde(default)]
+    pub scrollable: Option<FindBy>,
+    /// Pixels scrolled per attempt, negative to scroll back, defaults to 100
+    #[serde(default)]
+    pub delta: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct SwipeAction {
+    pub by: FindBy,
+    pub direction: SwipeDirection,
+    /// Distance as a fraction of the widget's height for `up`/`down` or width for `left`/`right`,
+    /// defaults to 0.5
+    #[serde(default)]
+    pub amount: Option<f64>,
+}
+
+/// Direction the pointer moves in, `up` scrolling a list towards its end.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl SwipeDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwipeDirection::Up => "up",
+            SwipeDirection::Down => "down",
+            SwipeDirection::Left => "left",
+            SwipeDirection::Right => "right",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum PointerAction {
+    /// Moves the pressed pointer if any, a hovering mouse pointer otherwise
     Move { to: Offset, remove: bool },
+    /// Presses a pointer, kept down until an `up` step or the end of the test
+    Down { down: Offset },
+    /// Releases the pressed pointer
+    Up { up: PointerUp },
+}
+
+/// Empty mapping, written `up: {}`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct PointerUp {}
+
+/// Two-finger pinch on a widget, `scale` above 1 zooms in and below 1 zooms out.
+/// Supported scales range from 0.2 to 5.0.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct PinchAction {
+    pub by: FindBy,
+    pub scale: f64,
+    /// Center of the pinch relative to the center of the widget, defaults to the widget center
+    #[serde(default)]
+    pub center: Option<Offset>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Offset {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum TapFindBy {
-    Key { key: String },
-    Text { text: String },
-    Placeholder { placeholder: String },
-    Type { r#type: String },
-    Coords { x: i32, y: i32 },
+    Key {
+        key: String,
+    },
+    Text {
+        text: String,
+    },
+    Placeholder {
+        placeholder: String,
+    },
+    Type {
+        r#type: String,
+    },
+    /// Material icon name, e.g. `settings` for `Icons.settings`
+    Icon {
+        icon: String,
+    },
+    /// Message of the `Tooltip` around the widget
+    Tooltip {
+        tooltip: String,
+    },
+    Coords {
+        x: i32,
+        y: i32,
+    },
+    Descendant {
+        matching: Box<FindBy>,
+        #[serde(rename = "descendant_of")]
+        of: Box<FindBy>,
+    },
+    Ancestor {
+        matching: Box<FindBy>,
+        #[serde(rename = "ancestor_of")]
+        of: Box<FindBy>,
+    },
+    Nth {
+        index: usize,
+        of: Box<FindBy>,
+    },
 }
 
 impl TapFindBy {
@@ -166,57 +733,1038 @@ impl TapFindBy {
             TapFindBy::Type { r#type } => Some(FindBy::Type {
                 r#type: r#type.clone(),
             }),
+            TapFindBy::Icon { icon } => Some(FindBy::Icon { icon: icon.clone() }),
+            TapFindBy::Tooltip { tooltip } => Some(FindBy::Tooltip {
+                tooltip: tooltip.clone(),
+            }),
             TapFindBy::Coords { .. } => None,
+            TapFindBy::Descendant { matching, of } => Some(FindBy::Descendant {
+                matching: matching.clone(),
+                of: of.clone(),
+            }),
+            TapFindBy::Ancestor { matching, of } => Some(FindBy::Ancestor {
+                matching: matching.clone(),
+                of: of.clone(),
+            }),
+            TapFindBy::Nth { index, of } => Some(FindBy::Nth {
+                index: *index,
+                of: of.clone(),
+            }),
         }
     }
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum FindBy {
-    Key { key: String },
-    Text { text: String },
-    Placeholder { placeholder: String },
-    Type { r#type: String },
+    Key {
+        key: String,
+    },
+    Text {
+        text: String,
+    },
+    Placeholder {
+        placeholder: String,
+    },
+    Type {
+        r#type: String,
+    },
+    /// Material icon name, e.g. `settings` for `Icons.settings`, to find icon-only buttons
+    Icon {
+        icon: String,
+    },
+    /// Message of the `Tooltip` around the widget, e.g. on icon buttons labelled for accessibility
+    Tooltip {
+        tooltip: String,
+    },
+    /// Widget found by `matching` inside the widget found by `descendant_of`, e.g. the button of a card
+    Descendant {
+        matching: Box<FindBy>,
+        #[serde(rename = "descendant_of")]
+        of: Box<FindBy>,
+    },
+    /// Widget found by `matching` around the widget found by `ancestor_of`, e.g. the card of a title
+    Ancestor {
+        matching: Box<FindBy>,
+        #[serde(rename = "ancestor_of")]
+        of: Box<FindBy>,
+    },
+    /// Widget at the zero-based `index` among the widgets found by `of`, e.g. the third "Add" button.
+    /// A wrapper rather than an `index` next to the other finders, which would silently ignore it.
+    Nth {
+        index: usize,
+        of: Box<FindBy>,
+    },
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct TypeAction {
     pub by: FindBy,
     pub value: String,
+    /// Append the value to the existing text instead of replacing it
+    #[serde(default)]
+    pub append: bool,
+    /// Submit the field after typing, calling its `onSubmitted` like pressing enter, e.g. for a search bar
+    #[serde(default)]
+    pub submit: bool,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Match {
     #[serde(flatten)]
     pub target: MatchTarget,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+/// Asserts a property of the first widget found, compared as a string (`"true"`/`"false"` for flags).
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct MatchProperty {
+    pub by: FindBy,
+    pub property: WidgetProperty,
+    pub expected: String,
+}
+
+/// Asserts the scroll offset of the scroll view found by `of`, or of the one within it,
+/// e.g. to check an infinite list loaded more items.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct MatchScroll {
+    pub of: FindBy,
+    #[serde(default)]
+    pub axis: ScrollAxis,
+    /// Minimum offset in logical pixels from the start of the scroll view
+    pub at_least: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollAxis {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+impl ScrollAxis {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScrollAxis::Vertical => "vertical",
+            ScrollAxis::Horizontal => "horizontal",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum WidgetProperty {
+    /// Buttons, `IconButton`, `TextField`, `Checkbox` and `Switch`
+    Enabled,
+    /// `Text` and text fields
+    Text,
+    /// `Checkbox`, `Switch` and their list tiles
+    Checked,
+}
+
+impl WidgetProperty {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WidgetProperty::Enabled => "enabled",
+            WidgetProperty::Text => "text",
+            WidgetProperty::Checked => "checked",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum MatchTarget {
-    Key { key: String },
-    Text { text: String },
-    Screenshot { screenshot: String },
+    Key {
+        key: String,
+    },
+    Text {
+        text: String,
+    },
+    /// Text containing this substring, e.g. `Total:` for a dynamic total
+    TextContains {
+        contains: String,
+    },
+    /// Material icon name, e.g. `close` for `Icons.close`
+    Icon {
+        icon: String,
+    },
+    /// Message of a `Tooltip`
+    Tooltip {
+        tooltip: String,
+    },
+    /// Exact number of widgets found, e.g. the rows of a list, `0` asserting there are none
+    Count {
+        by: FindBy,
+        count: usize,
+    },
+    /// Widget absent right now, unlike the `not_found` step which waits for it to disappear
+    NotExists {
+        not_exists: FindBy,
+    },
+    Screenshot {
+        screenshot: String,
+        /// Fraction of pixels allowed to differ, e.g. `0.01` for an animated screen, defaults to `0.003`
+        #[serde(default)]
+        threshold: Option<f64>,
+    },
 }
 
 impl FeatureTest {
-    pub fn all_from_curr_dir() -> ResultWithError<Vec<Self>> {
-        let res = DirUtils::parse_all_from_curr_dir::<Self>(YamlType::FeatureTest)?;
-        Ok(res.into_iter().map(|f| f.content).collect::<Vec<_>>())
+    /// Whether the feature is selected by the `--filter` value (case-insensitive substring).
+    pub fn matches_filter(&self, filter: &str) -> bool {
+        self.name.to_lowercase().contains(&filter.to_lowercase())
+    }
+
+    /// Name of the `iteration`th repetition of a test when running with `--repeat`.
+    pub fn repeated_test_name(test_name: &str, iteration: u32, repeat: u32) -> String {
+        if repeat > 1 {
+            format!("{} (run {}/{})", test_name, iteration, repeat)
+        } else {
+            test_name.to_owned()
+        }
+    }
+
+    /// Reverts `repeated_test_name`, so results can be matched against the YAML test names.
+    pub fn strip_repeat_suffix(test_name: &str) -> &str {
+        if let Some((name, suffix)) = test_name.rsplit_once(" (run ")
+            && suffix.ends_with(')')
+            && suffix[..suffix.len() - 1]
+                .split_once('/')
+                .is_some_and(|(i, n)| i.parse::<u32>().is_ok() && n.parse::<u32>().is_ok())
+        {
+            return name;
+        }
+        test_name
+    }
+
+    /// Keeps only the tests marked `only` when any is, dropping the features left without tests.
+    pub fn apply_only(features: Vec<Self>) -> Vec<Self> {
+        let only_count = features
+            .iter()
+            .flat_map(|f| &f.tests)
+            .filter(|t| t.only)
+            .count();
+        if only_count == 0 {
+            return features;
+        }
+
+        warn!(
+            "⚠️ {} test(s) marked `only`, ALL OTHER TESTS ARE EXCLUDED. Remove `only` before committing",
+            only_count
+        );
+
+        features
+            .into_iter()
+            .filter_map(|mut f| {
+                f.tests.retain(|t| t.only);
+                (!f.tests.is_empty()).then_some(f)
+            })
+            .collect()
+    }
+
+    /// Keeps only the tests with one of the `--tag` tags when any is given, dropping the features
+    /// left without tests. Tags are compared case-insensitively, with or without a leading `@`.
+    pub fn apply_tags(features: Vec<Self>, tags: &[String]) -> ResultWithError<Vec<Self>> {
+        if tags.is_empty() {
+            return Ok(features);
+        }
+
+        let normalize = |tag: &str| tag.trim().trim_start_matches('@').to_lowercase();
+        let wanted = tags.iter().map(|t| normalize(t)).collect::<HashSet<_>>();
+        let features = features
+            .into_iter()
+            .filter_map(|mut f| {
+                f.tests
+                    .retain(|t| t.tags.iter().any(|tag| wanted.contains(&normalize(tag))));
+                (!f.tests.is_empty()).then_some(f)
+            })
+            .collect::<Vec<_>>();
+
+        let count = features.iter().map(|f| f.tests.len()).sum::<usize>();
+        if count == 0 {
+            return Err(format!("No test is tagged {}", tags.join(" or ")).into());
+        }
+        info!("Tags {} matched {} test(s)", tags.join(", "), count);
+
+        Ok(features)
+    }
+
+    pub fn all_from_curr_dir(
+        test_dirs: &[String],
+        version_check: bool,
+        validate_schema: bool,
+    ) -> ResultWithError<Vec<Self>> {
+        if validate_schema {
+            Self::validate_schema(test_dirs)?;
+        }
+
+        let res = DirUtils::parse_all_from_curr_dir::<Self>(YamlType::FeatureTest, test_dirs)?;
+        res.into_iter()
+            .map(|f| {
+                SchemaVersion::check(
+                    f.content.schema_version.as_deref(),
+                    &f.path.display().to_string(),
+                    version_check,
+                )?;
+
+                let mut feature = f.content;
+                feature.resolve_includes(&f.path)?;
+                feature
+                    .check_uses()
+                    .map_err(|e| format!("{}: {}", f.path.display(), e))?;
+                feature.source_path = f.path;
+                Ok(feature)
+            })
+            .collect()
+    }
+
+    /// Validates every feature test file against the JSON schema of `FeatureTest`,
+    /// to report mistakes in the untagged steps by file and location rather than with serde errors.
+    fn validate_schema(test_dirs: &[String]) -> EmptyResult {
+        let schema = serde_json::to_value(schema_for!(FeatureTest))?;
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| format!("Invalid feature test schema: {}", e))?;
+
+        let files = DirUtils::parse_all_from_curr_dir::<serde_json::Value>(
+            YamlType::FeatureTest,
+            test_dirs,
+        )?;
+
+        let errors = files
+            .iter()
+            .flat_map(|f| {
+                validator.iter_errors(&f.content).map(|err| {
+                    // Untagged steps only report that no variant matched, so name the keys found
+                    let any_of = matches!(
+                        err.kind(),
+                        jsonschema::error::ValidationErrorKind::AnyOf { .. }
+                    );
+                    let keys = match err.instance().as_object() {
+                        Some(obj) if any_of && !obj.is_empty() => format!(
+                            " (found keys: {})",
+                            obj.keys().cloned().collect::<Vec<_>>().join(", ")
+                        ),
+                        _ => "".to_owned(),
+                    };
+                    format!(
+                        "{}: {}: {}{}",
+                        f.path.display(),
+                        Self::schema_error_location(&err.instance_path().to_string()),
+                        err.masked(),
+                        keys
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !errors.is_empty() {
+            return Err(format!(
+                "Feature tests do not match the schema:\n{}",
+                errors.join("\n")
+            )
+            .into());
+        }
+
+        debug!("Feature tests match the schema");
+        Ok(())
+    }
+
+    /// Turns a JSON pointer like `/tests/0/steps/2` into `tests[0].steps[2]`.
+    fn schema_error_location(pointer: &str) -> String {
+        let location = pointer
+            .split('/')
+            .skip(1)
+            .fold(String::new(), |mut location, segment| {
+                if segment.parse::<usize>().is_ok() {
+                    location.push_str(&format!("[{}]", segment));
+                } else {
+                    if !location.is_empty() {
+                        location.push('.');
+                    }
+                    location.push_str(&segment.replace("~1", "/").replace("~0", "~"));
+                }
+                location
+            });
+
+        if location.is_empty() {
+            "root".to_owned()
+        } else {
+            location
+        }
+    }
+
+    /// Checks the `use_step`s of the feature, after its includes are merged, so a missing or
+    /// recursive definition fails before generating rather than in the generated code.
+    fn check_uses(&self) -> EmptyResult {
+        let setups = [
+            self.before_each.as_ref().map(|b| &b.steps),
+            self.before_all.as_ref().map(|b| &b.steps),
+            self.after_all.as_ref().map(|b| &b.steps),
+        ];
+        let steps = setups
+            .into_iter()
+            .flatten()
+            .chain(self.tests.iter().map(|t| &t.steps))
+            .flatten();
+        for step in steps {
+            step.check_uses(&self.step_definitions, &mut vec![])?;
+        }
+
+        // Definitions not used by this feature, e.g. included ones, are checked too
+        let mut names = self.step_definitions.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            Step::Use {
+                use_step: name.clone(),
+                with: HashMap::new(),
+            }
+            .check_uses(&self.step_definitions, &mut vec![])?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `{{ loop.index }}` is only used in `repeat` steps, before generating code
+    /// that would read an undefined Dart variable.
+    pub fn check_loop_index(&self) -> EmptyResult {
+        let setups = [
+            self.before_each.as_ref().map(|b| &b.steps),
+            self.before_all.as_ref().map(|b| &b.steps),
+            self.after_all.as_ref().map(|b| &b.steps),
+        ];
+        let steps = setups
+            .into_iter()
+            .flatten()
+            .chain(self.tests.iter().map(|t| &t.steps))
+            .flatten();
+        for step in steps {
+            step.check_loop_index(&self.step_definitions, false)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_includes(&mut self, path: &Path) -> EmptyResult {
+        if self.include.is_empty() {
+            return Ok(());
+        }
+
+        let mut chain = vec![SharedDefinitions::canonicalize(path)?];
+        let shared = SharedDefinitions::load_all(&self.include, path, &mut chain)?;
+
+        for (key, value) in shared.vars {
+            self.vars.entry(key).or_insert(value);
+        }
+        for (key, steps) in shared.step_definitions {
+            self.step_definitions.entry(key).or_insert(steps);
+        }
+
+        Ok(())
+    }
+}
+
+impl SharedDefinitions {
+    /// Loads and merges the `includes` of the file at `from`, later includes overriding earlier ones.
+    /// `chain` holds the files currently being resolved, to detect cycles.
+    fn load_all(
+        includes: &[String],
+        from: &Path,
+        chain: &mut Vec<PathBuf>,
+    ) -> ResultWithError<Self> {
+        let base_dir = from.parent().unwrap_or(Path::new("."));
+        let mut merged = Self::default();
+
+        for include in includes {
+            let path = Self::canonicalize(&base_dir.join(include))?;
+            if chain.contains(&path) {
+                let cycle = chain
+                    .iter()
+                    .chain(std::iter::once(&path))
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                return Err(format!("Include cycle detected: {}", cycle).into());
+            }
+
+            chain.push(path.clone());
+            let shared = Self::load(&path, chain)?;
+            chain.pop();
+
+            merged.vars.extend(shared.vars);
+            merged.step_definitions.extend(shared.step_definitions);
+        }
+
+        Ok(merged)
+    }
+
+    fn load(path: &Path, chain: &mut Vec<PathBuf>) -> ResultWithError<Self> {
+        let content =
+            fs::read_to_string(path).auto_err(&format!("Failed to read file: {:?}", path))?;
+        let shared: Self = serde_yaml::from_str(&content)
+            .auto_err(&format!("Failed to parse YAML: {:?}", path))?;
+
+        let mut merged = Self::load_all(&shared.include, path, chain)?;
+        merged.vars.extend(shared.vars);
+        merged.step_definitions.extend(shared.step_definitions);
+
+        Ok(merged)
+    }
+
+    fn canonicalize(path: &Path) -> ResultWithError<PathBuf> {
+        fs::canonicalize(path).auto_err(&format!("Included file not found: {:?}", path))
     }
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct UserInputGmail {
     pub from: String,
     pub subject_contains: String,
     pub regex: UserInputGmailRegexType,
 }
 
-#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum UserInputGmailRegexType {
     Custom { pattern: String },
     Mfa,
 }
+
+/// Duration in milliseconds, or a number with a unit such as `"500ms"`, `"30s"` or `"2m"`.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum DurationMillis {
+    Millis(u64),
+    /// Number followed by `ms`, `s`, `m` or `h`, e.g. `"1.5s"`
+    Human(String),
+}
+
+impl DurationMillis {
+    pub fn to_millis(&self) -> Result<u64, String> {
+        let text = match self {
+            DurationMillis::Millis(millis) => return Ok(*millis),
+            DurationMillis::Human(text) => text.trim(),
+        };
+
+        let invalid = || {
+            format!(
+                "invalid duration '{}', expected milliseconds or a number with a unit such as 500ms, 30s or 2m",
+                text
+            )
+        };
+        let split = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(split);
+        let number = number.parse::<f64>().map_err(|_| invalid())?;
+        let factor = match unit.trim() {
+            "" | "ms" => 1.0,
+            "s" => 1_000.0,
+            "m" => 60_000.0,
+            "h" => 3_600_000.0,
+            _ => return Err(invalid()),
+        };
+
+        Ok((number * factor).round() as u64)
+    }
+
+    /// Converts to the millisecond type of the field being deserialized.
+    fn convert<T: TryFrom<u64>, E: serde::de::Error>(&self) -> Result<T, E> {
+        let millis = self.to_millis().map_err(E::custom)?;
+        T::try_from(millis).map_err(|_| E::custom(format!("duration {}ms is too long", millis)))
+    }
+}
+
+fn deserialize_millis<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    DurationMillis::deserialize(deserializer)?.convert()
+}
+
+fn deserialize_opt_millis<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    Option::<DurationMillis>::deserialize(deserializer)?
+        .map(|duration| duration.convert())
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_case(yaml: &str) -> TestCase {
+        serde_yaml::from_str(&format!("name: Login\nsteps: []\n{}", yaml))
+            .expect("Should parse test case")
+    }
+
+    fn feature(yaml: &str) -> FeatureTest {
+        serde_yaml::from_str(yaml).expect("Should parse feature")
+    }
+
+    fn test_names(features: &[FeatureTest]) -> Vec<String> {
+        features
+            .iter()
+            .flat_map(|f| {
+                f.tests
+                    .iter()
+                    .map(move |t| format!("{} - {}", f.name, t.name))
+            })
+            .collect()
+    }
+
+    /// Loads the feature file like `all_from_curr_dir`, with its includes merged and checked.
+    fn load_feature(path: &Path) -> ResultWithError<FeatureTest> {
+        let mut feature = feature(&fs::read_to_string(path)?);
+        feature.resolve_includes(path)?;
+        feature.check_uses()?;
+        Ok(feature)
+    }
+
+    fn step(yaml: &str) -> Step {
+        serde_yaml::from_str(yaml).expect("Should parse step")
+    }
+
+    #[test]
+    fn declares_input_looks_into_nested_steps() {
+        let definitions: HashMap<String, Vec<Step>> = serde_yaml::from_str(
+            "ask_code:\n  - user_input: { name: code }\nlogin:\n  - use_step: ask_code\n",
+        )
+        .unwrap();
+        let declares = |yaml: &str| step(yaml).declares_input(&definitions);
+
+        assert!(declares("user_input: { name: code }"));
+        assert!(declares(
+            "if_present: { key: mfa }\nthen:\n  - user_input: { name: code }\n"
+        ));
+        assert!(declares(
+            "if_present: { key: mfa }\nthen: []\nelse:\n  - user_input: { name: code }\n"
+        ));
+        assert!(declares(
+            "if:\n  exists: { key: mfa }\n  then:\n    - use_step: ask_code\n"
+        ));
+        assert!(declares(
+            "repeat:\n  times: 2\n  steps:\n    - user_input: { name: code }\n"
+        ));
+        assert!(declares("use_step: login"));
+
+        assert!(!declares("tap: { key: submit }"));
+        assert!(!declares(
+            "if_present: { key: mfa }\nthen:\n  - tap: { key: submit }\n"
+        ));
+        assert!(!declares("use_step: missing"));
+    }
+
+    #[test]
+    fn label_names_the_step_kind_and_target() {
+        assert_eq!(step("tap: { text: Log in }").label(), "tap text: Log in");
+        assert_eq!(
+            step("type: { by: { key: email }, value: a@b.c }").label(),
+            "type by: key: email, value: a@b.c"
+        );
+        assert_eq!(
+            step("tap: { descendant_of: { key: card }, matching: { text: Delete } }").label(),
+            "tap matching: text: Delete, descendant_of: key: card"
+        );
+        assert_eq!(step("use_step: login").label(), "use_step login");
+        assert_eq!(
+            step("repeat: { times: 3, steps: [settle] }").label(),
+            "repeat times: 3, steps: 1 step(s)"
+        );
+        assert_eq!(step("settle").label(), "settle");
+    }
+
+    #[test]
+    fn durations_accept_millis_or_a_unit() {
+        let millis = |text: &str| DurationMillis::Human(text.to_owned()).to_millis();
+
+        assert_eq!(DurationMillis::Millis(500).to_millis(), Ok(500));
+        assert_eq!(millis("500"), Ok(500));
+        assert_eq!(millis("250ms"), Ok(250));
+        assert_eq!(millis("1s"), Ok(1_000));
+        assert_eq!(millis("1.5s"), Ok(1_500));
+        assert_eq!(millis(" 2m "), Ok(120_000));
+        assert_eq!(millis("1h"), Ok(3_600_000));
+
+        for invalid in ["5d", "s", "1.5.2s", "ten seconds", ""] {
+            let err = millis(invalid).unwrap_err();
+            assert!(err.contains("invalid duration"), "{invalid}: {err}");
+        }
+    }
+
+    #[test]
+    fn durations_are_converted_to_the_field_type() {
+        let settle = |yaml: &str| serde_yaml::from_str::<SettleAction>(yaml);
+
+        assert_eq!(
+            settle("timeout_millis: 2s").unwrap().timeout_millis,
+            Some(2_000)
+        );
+        assert_eq!(
+            settle("timeout_millis: 750").unwrap().timeout_millis,
+            Some(750)
+        );
+        assert_eq!(settle("{}").unwrap().timeout_millis, None);
+
+        // Above the `u32` of the field
+        let err = settle("timeout_millis: 2000h").unwrap_err().to_string();
+        assert!(err.contains("duration 7200000000ms is too long"), "{err}");
+        let err = settle("timeout_millis: 3d").unwrap_err().to_string();
+        assert!(err.contains("invalid duration '3d'"), "{err}");
+    }
+
+    #[test]
+    fn skip_accepts_a_flag_or_a_reason() {
+        let not_skipped = test_case("skip: false\n");
+        assert!(!not_skipped.is_skipped());
+        assert_eq!(not_skipped.skip_reason(), None);
+
+        let flagged = test_case("skip: true\n");
+        assert!(flagged.is_skipped());
+        assert_eq!(flagged.skip_reason(), None);
+
+        let with_reason = test_case("skip: \"Flaky\"\n");
+        assert!(with_reason.is_skipped());
+        assert_eq!(with_reason.skip_reason(), Some("Flaky"));
+
+        assert!(!test_case("").is_skipped());
+    }
+
+    #[test]
+    fn only_takes_precedence_over_skip() {
+        let test = test_case("skip: \"Flaky\"\nonly: true\n");
+        assert!(!test.is_skipped());
+        assert_eq!(test.skip_reason(), None);
+    }
+
+    #[test]
+    fn apply_only_keeps_the_focused_tests_across_features() {
+        let features = vec![
+            feature(
+                "name: Login\ntests:\n  - { name: Valid, only: true, steps: [] }\n  - { name: Invalid, steps: [] }\n",
+            ),
+            feature("name: Search\ntests:\n  - { name: Empty, steps: [] }\n"),
+            feature("name: Settings\ntests:\n  - { name: Theme, only: true, steps: [] }\n"),
+        ];
+
+        let features = FeatureTest::apply_only(features);
+
+        assert_eq!(test_names(&features), ["Login - Valid", "Settings - Theme"]);
+    }
+
+    #[test]
+    fn apply_only_keeps_everything_without_focused_tests() {
+        let features = vec![
+            feature("name: Login\ntests:\n  - { name: Valid, steps: [] }\n"),
+            feature("name: Search\ntests:\n  - { name: Empty, steps: [] }\n"),
+        ];
+
+        let features = FeatureTest::apply_only(features);
+
+        assert_eq!(test_names(&features), ["Login - Valid", "Search - Empty"]);
+    }
+
+    #[test]
+    fn shared_login_definition_is_used_across_features() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("_shared.defs.yaml"),
+            "step_definitions:\n  login:\n    - tap: { key: login_button }\n    - use_step: wait_home\n  \
+             wait_home:\n    - wait_for: { key: home }\n",
+        )
+        .unwrap();
+        for name in ["cart", "profile"] {
+            fs::write(
+                dir.path().join(format!("{}.test.yaml", name)),
+                format!(
+                    "name: {}\ninclude: [_shared.defs.yaml]\ntests:\n  - name: Logged in\n    steps:\n      - use_step: login\n",
+                    name
+                ),
+            )
+            .unwrap();
+        }
+
+        for name in ["cart", "profile"] {
+            let feature = load_feature(&dir.path().join(format!("{}.test.yaml", name)))
+                .expect("Should resolve the shared login");
+            let mut definitions = feature.step_definitions.keys().collect::<Vec<_>>();
+            definitions.sort();
+            assert_eq!(definitions, ["login", "wait_home"]);
+        }
+    }
+
+    #[test]
+    fn recursive_use_step_chains_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("_shared.defs.yaml"),
+            "step_definitions:\n  login:\n    - use_step: open_menu\n  \
+             open_menu:\n    - if_present: { key: logged_out }\n      then:\n        - use_step: login\n",
+        )
+        .unwrap();
+        let path = dir.path().join("cart.test.yaml");
+        fs::write(
+            &path,
+            "name: Cart\ninclude: [_shared.defs.yaml]\ntests:\n  - name: Add\n    steps:\n      - use_step: login\n",
+        )
+        .unwrap();
+
+        let err = load_feature(&path).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Recursive use_step chain: login -> open_menu -> login"
+        );
+    }
+
+    #[test]
+    fn unknown_use_step_is_rejected() {
+        let feature = feature(
+            "name: Cart\ntests:\n  - name: Add\n    steps:\n      - repeat: { times: 2, steps: [{ use_step: logout }] }\n",
+        );
+
+        let err = feature.check_uses().unwrap_err();
+        assert_eq!(err.to_string(), "Step definition 'logout' not found");
+    }
+
+    fn tagged_features() -> Vec<FeatureTest> {
+        vec![
+            feature(
+                "name: Login\ntests:\n  \
+                 - { name: Valid, tags: [Smoke], steps: [] }\n  \
+                 - { name: Invalid, tags: [regression], steps: [] }\n",
+            ),
+            feature("name: Search\ntests:\n  - { name: Empty, tags: [\"@smoke\"], steps: [] }\n"),
+            feature("name: Settings\ntests:\n  - { name: Theme, steps: [] }\n"),
+        ]
+    }
+
+    #[test]
+    fn apply_tags_ignores_case_and_leading_at() {
+        let features = FeatureTest::apply_tags(tagged_features(), &["@SMOKE".to_owned()]).unwrap();
+
+        assert_eq!(test_names(&features), ["Login - Valid", "Search - Empty"]);
+    }
+
+    #[test]
+    fn apply_tags_accepts_any_of_several_tags() {
+        let tags = ["smoke".to_owned(), "Regression".to_owned()];
+        let features = FeatureTest::apply_tags(tagged_features(), &tags).unwrap();
+
+        assert_eq!(
+            test_names(&features),
+            ["Login - Valid", "Login - Invalid", "Search - Empty"]
+        );
+        assert_eq!(
+            test_names(&FeatureTest::apply_tags(tagged_features(), &[]).unwrap()).len(),
+            4
+        );
+    }
+
+    #[test]
+    fn apply_tags_fails_when_no_test_matches() {
+        let tags = ["nightly".to_owned(), "@perf".to_owned()];
+        let err = FeatureTest::apply_tags(tagged_features(), &tags).unwrap_err();
+
+        assert_eq!(err.to_string(), "No test is tagged nightly or @perf");
+    }
+
+    #[test]
+    fn local_definitions_override_included_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("base.defs.yaml"),
+            "vars:\n  user: base\n  env: staging\nstep_definitions:\n  login:\n    - tap: { key: base_login }\n  \
+             logout:\n    - tap: { key: base_logout }\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("_shared.defs.yaml"),
+            "include: [base.defs.yaml]\nvars:\n  user: shared\nstep_definitions:\n  logout:\n    - tap: { key: shared_logout }\n",
+        )
+        .unwrap();
+        let path = dir.path().join("cart.test.yaml");
+        fs::write(
+            &path,
+            "name: Cart\ninclude: [_shared.defs.yaml]\nvars:\n  env: local\nstep_definitions:\n  \
+             login:\n    - tap: { key: cart_login }\ntests: []\n",
+        )
+        .unwrap();
+
+        let feature = load_feature(&path).unwrap();
+        let first_key = |name: &str| match &feature.step_definitions[name][0] {
+            Step::Tap {
+                tap: TapFindBy::Key { key },
+                ..
+            } => key.clone(),
+            other => panic!("Unexpected step {:?}", other),
+        };
+
+        // The feature wins over its includes, which win over the files they include
+        assert_eq!(first_key("login"), "cart_login");
+        assert_eq!(first_key("logout"), "shared_logout");
+        assert_eq!(feature.vars["env"], "local");
+        assert_eq!(feature.vars["user"], "shared");
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.defs.yaml"), "include: [b.defs.yaml]\n").unwrap();
+        fs::write(dir.path().join("b.defs.yaml"), "include: [a.defs.yaml]\n").unwrap();
+        let path = dir.path().join("cart.test.yaml");
+        fs::write(&path, "name: Cart\ninclude: [a.defs.yaml]\ntests: []\n").unwrap();
+
+        let err = load_feature(&path).unwrap_err().to_string();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        assert_eq!(
+            err.replace(&format!("{}/", dir.display()), ""),
+            "Include cycle detected: cart.test.yaml -> a.defs.yaml -> b.defs.yaml -> a.defs.yaml"
+        );
+    }
+
+    #[test]
+    fn missing_include_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cart.test.yaml");
+        fs::write(
+            &path,
+            "name: Cart\ninclude: [missing.defs.yaml]\ntests: []\n",
+        )
+        .unwrap();
+
+        let err = load_feature(&path).unwrap_err().to_string();
+        assert!(err.starts_with("Included file not found"), "{err}");
+    }
+
+    #[test]
+    fn index_and_of_deserialize_to_the_nth_finder() {
+        let by: FindBy = serde_yaml::from_str("{ index: 2, of: { text: Add } }").unwrap();
+        assert!(
+            matches!(&by, FindBy::Nth { index: 2, of } if matches!(**of, FindBy::Text { ref text } if text == "Add")),
+            "{by:?}"
+        );
+
+        let tap: TapFindBy =
+            serde_yaml::from_str("{ index: 0, of: { type: ElevatedButton } }").unwrap();
+        assert!(matches!(tap, TapFindBy::Nth { index: 0, .. }), "{tap:?}");
+        assert!(matches!(
+            tap.to_find_by(),
+            Some(FindBy::Nth { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn icon_finders_round_trip_through_the_schema() {
+        let yaml = "name: Settings\ntests:\n  - name: Close\n    steps:\n      \
+                    - tap: { icon: close }\n      \
+                    - double_tap: { icon: settings }\n      \
+                    - match: { icon: check }\n      \
+                    - not_found: { icon: close }\n";
+
+        let schema = serde_json::to_value(schema_for!(FeatureTest)).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let value: serde_json::Value = serde_yaml::from_str(yaml).unwrap();
+        assert!(validator.is_valid(&value));
+
+        let parsed = feature(yaml);
+        let serialized = serde_json::to_value(&parsed).unwrap();
+        assert!(validator.is_valid(&serialized));
+        let steps = &feature(&serde_yaml::to_string(&parsed).unwrap()).tests[0].steps;
+        assert!(
+            matches!(&steps[0], Step::Tap { tap: TapFindBy::Icon { icon }, .. } if icon == "close")
+        );
+        assert!(
+            matches!(&steps[1], Step::DoubleTap { double_tap: FindBy::Icon { icon } } if icon == "settings")
+        );
+        assert!(
+            matches!(&steps[2], Step::Match { r#match } if matches!(&r#match.target, MatchTarget::Icon { icon } if icon == "check"))
+        );
+        assert!(
+            matches!(&steps[3], Step::NotFound { not_found: FindBy::Icon { icon }, .. } if icon == "close")
+        );
+    }
+
+    #[test]
+    fn not_exists_is_told_apart_from_count() {
+        let target = |yaml: &str| serde_yaml::from_str::<Match>(yaml).unwrap().target;
+
+        for finder in [
+            "{ key: spinner }",
+            "{ text: Loading }",
+            "{ icon: close }",
+            "{ placeholder: Email }",
+        ] {
+            let not_exists = target(&format!("not_exists: {}", finder));
+            assert!(
+                matches!(not_exists, MatchTarget::NotExists { .. }),
+                "{not_exists:?}"
+            );
+        }
+        assert!(matches!(
+            target("{ count: 0, by: { key: spinner } }"),
+            MatchTarget::Count { count: 0, .. }
+        ));
+
+        // A `count` without its finder is neither shape
+        assert!(serde_yaml::from_str::<Match>("{ count: 2 }").is_err());
+        let schema = serde_json::to_value(schema_for!(FeatureTest)).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let feature = |step: &str| -> serde_json::Value {
+            serde_yaml::from_str(&format!(
+                "name: Logout\ntests:\n  - name: Done\n    steps:\n      - match: {}\n",
+                step
+            ))
+            .unwrap()
+        };
+        assert!(validator.is_valid(&feature("{ not_exists: { key: spinner } }")));
+        assert!(validator.is_valid(&feature("{ count: 2, by: { key: row } }")));
+        assert!(!validator.is_valid(&feature("{ count: 2 }")));
+        assert!(!validator.is_valid(&feature("{ not_exists: spinner }")));
+    }
+
+    #[test]
+    fn malformed_step_is_reported_with_its_location() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("login.test.yaml"),
+            "name: Login\ntests:\n  - name: Valid\n    steps:\n      - tap: { key: email }\n      - tapp: { key: login }\n",
+        )
+        .unwrap();
+        let test_dirs = [dir.path().to_string_lossy().to_string()];
+
+        let err = FeatureTest::validate_schema(&test_dirs)
+            .unwrap_err()
+            .to_string();
+
+        assert_eq!(
+            err.replace(&format!("{}/", test_dirs[0]), ""),
+            "Feature tests do not match the schema:\n\
+             login.test.yaml: tests[0].steps[1]: value is not valid under any of the schemas listed in the 'anyOf' keyword (found keys: tapp)"
+        );
+    }
+
+    #[test]
+    fn valid_feature_matches_the_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("login.test.yaml"),
+            "name: Login\ntests:\n  - name: Valid\n    steps:\n      - tap: { key: email }\n      - wait_for: { text: Home }\n",
+        )
+        .unwrap();
+
+        FeatureTest::validate_schema(&[dir.path().to_string_lossy().to_string()])
+            .expect("Should match the schema");
+    }
+
+    #[test]
+    fn schema_error_locations_are_readable() {
+        assert_eq!(
+            FeatureTest::schema_error_location("/tests/0/steps/2"),
+            "tests[0].steps[2]"
+        );
+        assert_eq!(FeatureTest::schema_error_location(""), "root");
+    }
+}
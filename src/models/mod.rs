@@ -4,4 +4,7 @@ pub mod config;
 mod config_flutter;
 pub mod feature_test;
 pub mod gen_state;
+pub mod schema_version;
+pub mod shard;
+pub mod timings;
 pub mod vars;
@@ -0,0 +1,79 @@
+use tracing::{debug, warn};
+
+use crate::utils::errors::{EmptyResult, ResultWithError};
+
+/// Version of the YAML format understood by this build.
+/// Bump the major on breaking changes to the config or steps, the minor on additions.
+pub const SCHEMA_VERSION: &str = "1.0";
+
+pub struct SchemaVersion;
+
+impl SchemaVersion {
+    /// Checks the `schema_version` declared by a YAML file against `SCHEMA_VERSION`.
+    /// Major mismatches always fail, with `strict` a missing version or a newer minor fail too.
+    pub fn check(found: Option<&str>, source: &str, strict: bool) -> EmptyResult {
+        let Some(found) = found else {
+            if strict {
+                return Err(format!(
+                    "{} has no schema_version, add `schema_version: \"{}\"`",
+                    source, SCHEMA_VERSION
+                )
+                .into());
+            }
+            debug!(
+                "{} has no schema_version, assuming {}",
+                source, SCHEMA_VERSION
+            );
+            return Ok(());
+        };
+
+        let (major, minor) = Self::parse(found, source)?;
+        let (curr_major, curr_minor) = Self::parse(SCHEMA_VERSION, "PlayMaster")?;
+
+        if major > curr_major {
+            return Err(format!(
+                "{} uses schema_version {}, which requires a newer PlayMaster (supports {})",
+                source, found, SCHEMA_VERSION
+            )
+            .into());
+        }
+
+        if major < curr_major {
+            return Err(format!(
+                "{} uses schema_version {}, but this PlayMaster supports {}. \
+                 See the \"Schema versions\" section of the README to migrate it",
+                source, found, SCHEMA_VERSION
+            )
+            .into());
+        }
+
+        if minor > curr_minor {
+            let msg = format!(
+                "{} uses schema_version {}, newer than the supported {}, some fields may be ignored",
+                source, found, SCHEMA_VERSION
+            );
+            if strict {
+                return Err(msg.into());
+            }
+            warn!("{}", msg);
+        }
+
+        Ok(())
+    }
+
+    fn parse(version: &str, source: &str) -> ResultWithError<(u64, u64)> {
+        let invalid = || format!("Invalid schema_version '{}' in {}", version, source);
+
+        let mut parts = version.trim().splitn(2, '.');
+        let major = parts
+            .next()
+            .and_then(|p| p.parse::<u64>().ok())
+            .ok_or_else(invalid)?;
+        let minor = match parts.next() {
+            Some(p) => p.parse::<u64>().map_err(|_| invalid())?,
+            None => 0,
+        };
+
+        Ok((major, minor))
+    }
+}
@@ -0,0 +1,202 @@
+use std::{cmp::Reverse, collections::HashSet, fmt, str::FromStr};
+
+use tracing::info;
+
+use crate::models::{feature_test::FeatureTest, timings::Timings};
+
+/// Slice of the tests run by one CI machine, written `index/count` with a 1-based index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid shard '{}', expected index/count such as 2/4", s);
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let index = index.trim().parse::<usize>().map_err(|_| invalid())?;
+        let count = count.trim().parse::<usize>().map_err(|_| invalid())?;
+        if count == 0 || index == 0 || index > count {
+            return Err(format!(
+                "invalid shard '{}', the index must be between 1 and the count",
+                s
+            ));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl Shard {
+    /// Keeps the tests of this shard, dropping the features left without tests.
+    ///
+    /// Tests are sorted by feature and test name so every machine computes the same partition.
+    /// They are dealt round-robin, so shard sizes differ by one test at most, or balanced by
+    /// their recorded durations when timings are available.
+    pub fn apply(&self, features: Vec<FeatureTest>, timings: Option<&Timings>) -> Vec<FeatureTest> {
+        let mut names = features
+            .iter()
+            .flat_map(|f| {
+                f.tests
+                    .iter()
+                    .map(move |t| (f.name.as_str(), t.name.as_str()))
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let assigned = match timings {
+            Some(timings) if !timings.tests.is_empty() => self.assign_by_duration(&names, timings),
+            _ => (0..names.len()).map(|i| i % self.count).collect(),
+        };
+        let selected = names
+            .into_iter()
+            .zip(assigned)
+            .filter(|(_, shard)| *shard == self.index - 1)
+            .map(|((feature, test), _)| (feature.to_owned(), test.to_owned()))
+            .collect::<HashSet<_>>();
+
+        info!("Shard {} runs {} test(s)", self, selected.len());
+
+        features
+            .into_iter()
+            .filter_map(|mut f| {
+                f.tests
+                    .retain(|t| selected.contains(&(f.name.clone(), t.name.clone())));
+                (!f.tests.is_empty()).then_some(f)
+            })
+            .collect()
+    }
+
+    /// Gives each test, longest first, to the shard with the least total duration so far.
+    /// Tests without timings, e.g. new ones, are assumed to take the average duration.
+    fn assign_by_duration(&self, names: &[(&str, &str)], timings: &Timings) -> Vec<usize> {
+        let known = names
+            .iter()
+            .filter_map(|(feature, test)| timings.median_ms(&format!("{} - {}", feature, test)))
+            .collect::<Vec<_>>();
+        let average = if known.is_empty() {
+            1
+        } else {
+            (known.iter().sum::<u64>() / known.len() as u64).max(1)
+        };
+        let durations = names
+            .iter()
+            .map(|(feature, test)| {
+                timings
+                    .median_ms(&format!("{} - {}", feature, test))
+                    .unwrap_or(average)
+            })
+            .collect::<Vec<_>>();
+
+        // Stable sort, ties keep the name order
+        let mut order = (0..names.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| Reverse(durations[i]));
+
+        let mut loads = vec![0u64; self.count];
+        let mut assigned = vec![0; names.len()];
+        for i in order {
+            let shard = (0..self.count)
+                .min_by_key(|&shard| loads[shard])
+                .unwrap_or_default();
+            loads[shard] += durations[i];
+            assigned[i] = shard;
+        }
+
+        info!(
+            "Shard {} is estimated to take {}s",
+            self,
+            loads[self.index - 1] / 1000
+        );
+        assigned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feature `Checkout` with a 60s test and five 10s ones, plus a new test without timings.
+    fn skewed_workload() -> (Vec<FeatureTest>, Timings) {
+        let feature = serde_yaml::from_str(
+            "name: Checkout\ntests:\n\
+             - { name: t1 slow, steps: [] }\n\
+             - { name: t2, steps: [] }\n\
+             - { name: t3, steps: [] }\n\
+             - { name: t4, steps: [] }\n\
+             - { name: t5, steps: [] }\n\
+             - { name: t6, steps: [] }\n",
+        )
+        .expect("Should parse feature");
+
+        let mut timings = Timings::default();
+        timings.record("Checkout - t1 slow", 60_000);
+        for test in ["t2", "t3", "t4", "t5"] {
+            timings.record(&format!("Checkout - {}", test), 10_000);
+        }
+        (vec![feature], timings)
+    }
+
+    /// Total duration of each shard, the tests without timings counting for 10s.
+    fn shard_loads(timings: Option<&Timings>, recorded: &Timings) -> Vec<u64> {
+        (1..=2)
+            .map(|index| {
+                let (features, _) = skewed_workload();
+                Shard { index, count: 2 }
+                    .apply(features, timings)
+                    .iter()
+                    .flat_map(|f| {
+                        f.tests
+                            .iter()
+                            .map(move |t| format!("{} - {}", f.name, t.name))
+                    })
+                    .map(|name| recorded.median_ms(&name).unwrap_or(10_000))
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shard_parses_index_and_count() {
+        assert_eq!("2/4".parse(), Ok(Shard { index: 2, count: 4 }));
+        assert!("0/4".parse::<Shard>().is_err());
+        assert!("5/4".parse::<Shard>().is_err());
+        assert!("2-4".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn durations_balance_a_skewed_workload_better_than_round_robin() {
+        let (_, timings) = skewed_workload();
+
+        let round_robin = shard_loads(None, &timings);
+        let balanced = shard_loads(Some(&timings), &timings);
+
+        assert_eq!(round_robin, [80_000, 30_000]);
+        assert_eq!(balanced, [60_000, 50_000]);
+        assert!(balanced.iter().max() < round_robin.iter().max());
+    }
+
+    #[test]
+    fn every_test_runs_in_exactly_one_shard() {
+        let (_, timings) = skewed_workload();
+        let mut names = (1..=3)
+            .flat_map(|index| {
+                let (features, _) = skewed_workload();
+                Shard { index, count: 3 }
+                    .apply(features, Some(&timings))
+                    .into_iter()
+                    .flat_map(|f| f.tests.into_iter().map(|t| t.name))
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names, ["t1 slow", "t2", "t3", "t4", "t5", "t6"]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::utils::errors::{EmptyResult, ResultTrait, ResultWithError};
+
+/// Number of durations kept per test, so a few slow runs don't skew the median for long.
+const HISTORY_SIZE: usize = 5;
+
+/// Durations of the previous runs, keyed by `Feature - Test` name, used to balance shards.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Timings {
+    pub tests: BTreeMap<String, TestTiming>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TestTiming {
+    pub median_ms: u64,
+    /// Latest durations, oldest first
+    pub history_ms: Vec<u64>,
+}
+
+impl Timings {
+    /// Reads the timings file, empty when it doesn't exist yet.
+    pub fn load(path: &Path) -> ResultWithError<Self> {
+        if !path.exists() {
+            debug!("No timings file at {}", path.display());
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .auto_err(&format!("Failed to read timings file {}", path.display()))?;
+        serde_json::from_str(&content)
+            .auto_err(&format!("Failed to parse timings file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> EmptyResult {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        info!("Test timings saved to {}", path.display());
+        Ok(())
+    }
+
+    pub fn record(&mut self, test_name: &str, duration_ms: u64) {
+        let timing = self.tests.entry(test_name.to_owned()).or_default();
+        timing.history_ms.push(duration_ms);
+        if timing.history_ms.len() > HISTORY_SIZE {
+            timing.history_ms.remove(0);
+        }
+
+        let mut sorted = timing.history_ms.clone();
+        sorted.sort_unstable();
+        timing.median_ms = sorted[sorted.len() / 2];
+    }
+
+    pub fn median_ms(&self, test_name: &str) -> Option<u64> {
+        self.tests.get(test_name).map(|t| t.median_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_keeps_the_latest_durations_and_their_median() {
+        let mut timings = Timings::default();
+        timings.record("Login - Valid", 1_000);
+        assert_eq!(timings.median_ms("Login - Valid"), Some(1_000));
+
+        for duration in [9_000, 2_000, 3_000] {
+            timings.record("Login - Valid", duration);
+        }
+        // Upper median of an even count
+        assert_eq!(timings.median_ms("Login - Valid"), Some(3_000));
+
+        // The oldest durations are dropped past the history size
+        for duration in [4_000, 5_000, 6_000] {
+            timings.record("Login - Valid", duration);
+        }
+        assert_eq!(
+            timings.tests["Login - Valid"].history_ms,
+            [2_000, 3_000, 4_000, 5_000, 6_000]
+        );
+        assert_eq!(timings.median_ms("Login - Valid"), Some(4_000));
+        assert_eq!(timings.median_ms("Login - Missing"), None);
+    }
+
+    #[test]
+    fn timings_round_trip_through_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("timings").join("timings.json");
+        assert!(Timings::load(&path).unwrap().tests.is_empty());
+
+        let mut timings = Timings::default();
+        timings.record("Login - Valid", 1_500);
+        timings.save(&path).unwrap();
+
+        assert_eq!(
+            Timings::load(&path).unwrap().median_ms("Login - Valid"),
+            Some(1_500)
+        );
+    }
+}
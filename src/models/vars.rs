@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
 use schemars::JsonSchema;
 use serde::Deserialize;
@@ -8,10 +8,14 @@ use crate::utils::{
     errors::ResultWithError,
 };
 
-#[derive(Debug, Deserialize, JsonSchema)]
+/// Vars file holding the captured output of the `state_set` command, as `vars.state.output`.
+const STATE_FILE_NAME: &str = "state";
+const STATE_OUTPUT_KEY: &str = "output";
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct VarsData(pub HashMap<String, String>);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct Vars {
     pub data: Vec<YamlResult<VarsData>>,
@@ -19,12 +23,26 @@ pub struct Vars {
 }
 
 impl Vars {
-    pub fn all_from_curr_dir() -> ResultWithError<Vars> {
-        let all_vars = DirUtils::parse_all_from_curr_dir::<VarsData>(YamlType::Vars)?;
+    pub fn all_from_curr_dir(test_dirs: &[String]) -> ResultWithError<Vars> {
+        let all_vars = DirUtils::parse_all_from_curr_dir::<VarsData>(YamlType::Vars, test_dirs)?;
         let data = all_vars
             .into_iter()
             .filter(|v| !v.content.0.is_empty())
             .collect::<Vec<_>>();
+
+        // Each file becomes a Dart class named after it, so names must be unique across test dirs
+        let mut paths_by_name: HashMap<&str, &YamlResult<VarsData>> = HashMap::new();
+        for var in data.iter() {
+            if let Some(other) = paths_by_name.insert(&var.file_name, var) {
+                return Err(format!(
+                    "Duplicate vars file name '{}' in: {}, {}",
+                    var.file_name,
+                    other.path.display(),
+                    var.path.display()
+                )
+                .into());
+            }
+        }
         let mut all_vars = HashMap::new();
 
         for var in data.iter() {
@@ -37,6 +55,78 @@ impl Vars {
         Ok(Vars { data, all_vars })
     }
 
+    /// Applies the `--var` overrides, keyed by `vars.<file>.<key>`, `<file>.<key>` or a `<key>`
+    /// defined in a single file.
+    pub fn with_overrides(mut self, overrides: &[(String, String)]) -> ResultWithError<Self> {
+        for (key, value) in overrides {
+            let key = key.strip_prefix("vars.").unwrap_or(key);
+            let (file_name, var_key) = match key.split_once('.') {
+                Some((file_name, var_key)) => (file_name.to_owned(), var_key),
+                None => {
+                    let files = self
+                        .data
+                        .iter()
+                        .filter(|v| v.content.0.contains_key(key))
+                        .map(|v| v.file_name.clone())
+                        .collect::<Vec<_>>();
+                    match files.as_slice() {
+                        [file_name] => (file_name.clone(), key),
+                        [] => return Err(format!("Unknown var '{key}' in --var").into()),
+                        _ => {
+                            return Err(format!(
+                                "Var '{key}' is defined in several files ({}), use <file>.{key} in --var",
+                                files.join(", ")
+                            )
+                            .into());
+                        }
+                    }
+                }
+            };
+
+            let Some(var) = self.data.iter_mut().find(|v| v.file_name == file_name) else {
+                return Err(format!("Unknown vars file '{file_name}' in --var {key}").into());
+            };
+            var.content.0.insert(var_key.to_owned(), value.clone());
+            self.all_vars
+                .insert(format!("vars.{file_name}.{var_key}"), value.clone());
+        }
+
+        Ok(self)
+    }
+
+    /// Sets `vars.state.output`, in the `state` vars file when there is one.
+    pub fn with_state_output(mut self, output: &str) -> Self {
+        match self
+            .data
+            .iter_mut()
+            .find(|v| v.file_name == STATE_FILE_NAME)
+        {
+            Some(var) => {
+                var.content
+                    .0
+                    .insert(STATE_OUTPUT_KEY.to_owned(), output.to_owned());
+            }
+            None => self.data.push(YamlResult {
+                file_name: STATE_FILE_NAME.to_owned(),
+                path: PathBuf::from("playmaster.yaml"),
+                content: VarsData(HashMap::from([(
+                    STATE_OUTPUT_KEY.to_owned(),
+                    output.to_owned(),
+                )])),
+            }),
+        }
+        self.all_vars.insert(
+            format!("vars.{STATE_FILE_NAME}.{STATE_OUTPUT_KEY}"),
+            output.to_owned(),
+        );
+        self
+    }
+
+    pub fn has_state_output(&self) -> bool {
+        self.all_vars
+            .contains_key(&format!("vars.{STATE_FILE_NAME}.{STATE_OUTPUT_KEY}"))
+    }
+
     pub fn replace_var_usage(&self, input: &str) -> String {
         crate::utils::variables::VariablesUtils::replace_var_usage(input)
     }
@@ -45,3 +135,127 @@ impl Vars {
         crate::utils::variables::VariablesUtils::replace_vars(input, &self.all_vars, extra_map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(files: &[(&str, &[(&str, &str)])]) -> Vars {
+        let data = files
+            .iter()
+            .map(|(file_name, vars)| YamlResult {
+                file_name: (*file_name).to_owned(),
+                path: PathBuf::from(format!("{file_name}.vars.yaml")),
+                content: VarsData(
+                    vars.iter()
+                        .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+                        .collect(),
+                ),
+            })
+            .collect::<Vec<YamlResult<VarsData>>>();
+        let all_vars =
+            data.iter()
+                .flat_map(|var| {
+                    var.content.0.iter().map(|(key, value)| {
+                        (format!("vars.{}.{}", var.file_name, key), value.clone())
+                    })
+                })
+                .collect();
+        Vars { data, all_vars }
+    }
+
+    fn overrides(overrides: &[(&str, &str)]) -> Vec<(String, String)> {
+        overrides
+            .iter()
+            .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn cli_override_wins_over_the_file_value() {
+        let vars = vars(&[("common", &[("email", "a@b.c"), ("password", "secret")])]);
+
+        let vars = vars
+            .with_overrides(&overrides(&[("vars.common.email", "ci@test.com")]))
+            .unwrap();
+
+        assert_eq!(vars.all_vars["vars.common.email"], "ci@test.com");
+        assert_eq!(vars.all_vars["vars.common.password"], "secret");
+        assert_eq!(vars.data[0].content.0["email"], "ci@test.com");
+    }
+
+    #[test]
+    fn short_override_keys_find_their_file() {
+        let vars = vars(&[
+            ("common", &[("email", "a@b.c")]),
+            ("auth", &[("token", "abc")]),
+        ]);
+
+        let vars = vars
+            .with_overrides(&overrides(&[
+                ("auth.token", "def"),
+                ("email", "ci@test.com"),
+            ]))
+            .unwrap();
+
+        assert_eq!(vars.all_vars["vars.auth.token"], "def");
+        assert_eq!(vars.all_vars["vars.common.email"], "ci@test.com");
+    }
+
+    #[test]
+    fn unknown_or_ambiguous_overrides_are_rejected() {
+        let files: &[(&str, &[(&str, &str)])] = &[
+            ("common", &[("email", "a@b.c")]),
+            ("admin", &[("email", "admin@b.c")]),
+        ];
+
+        let err = vars(files)
+            .with_overrides(&overrides(&[("token", "abc")]))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Unknown var 'token' in --var");
+
+        let err = vars(files)
+            .with_overrides(&overrides(&[("email", "ci@test.com")]))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("Var 'email' is defined in several files"),
+            "{err}"
+        );
+
+        let err = vars(files)
+            .with_overrides(&overrides(&[("auth.token", "abc")]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown vars file 'auth' in --var auth.token"
+        );
+    }
+
+    #[test]
+    fn state_output_is_exposed_as_a_var() {
+        let vars = vars(&[("common", &[("email", "a@b.c")])]);
+        assert!(!vars.has_state_output());
+
+        let vars = vars.with_state_output("token-123");
+
+        assert!(vars.has_state_output());
+        assert_eq!(
+            vars.replace_var("Bearer {{ vars.state.output }}", None),
+            "Bearer token-123"
+        );
+        let state = vars.data.iter().find(|v| v.file_name == "state").unwrap();
+        assert_eq!(state.content.0.get("output").unwrap(), "token-123");
+        assert_eq!(vars.data.len(), 2);
+    }
+
+    #[test]
+    fn state_output_joins_the_state_vars_file() {
+        let vars = vars(&[("state", &[("user", "qa")])]).with_state_output("token-123");
+
+        assert_eq!(vars.data.len(), 1);
+        let state = &vars.data[0].content.0;
+        assert_eq!(state.get("user").unwrap(), "qa");
+        assert_eq!(state.get("output").unwrap(), "token-123");
+    }
+}
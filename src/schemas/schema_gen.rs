@@ -1,47 +1,94 @@
-use std::{fs, path::Path};
+use std::{fs, path::PathBuf};
 
 use schemars::{JsonSchema, schema_for};
 use tracing::info;
 
-use crate::utils::{self, errors::EmptyResult};
+use crate::{models::schema_version::SCHEMA_VERSION, utils::errors::EmptyResult};
 
-pub struct SchemaGen {}
+/// Where the schemas are published, used as the base of each schema `$id`.
+const SCHEMA_BASE_URL: &str = "https://raw.githubusercontent.com/playtron-os/playmaster/refs/heads/master/src/schemas/generated";
+
+pub struct SchemaGen {
+    out_dir: PathBuf,
+}
 
 impl SchemaGen {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(out_dir: PathBuf) -> Self {
+        Self { out_dir }
     }
 
     pub fn execute(&self) -> EmptyResult {
         self.generate_single::<crate::models::feature_test::FeatureTest>(
             "feature_test_schema.json",
+            "PlayMaster feature test",
+        )?;
+        self.generate_single::<crate::models::config::Config>(
+            "config_schema.json",
+            "PlayMaster config",
         )?;
-        self.generate_single::<crate::models::config::Config>("config_schema.json")?;
-        self.generate_single::<crate::models::vars::VarsData>("vars_schema.json")?;
+        self.generate_single::<crate::models::vars::VarsData>(
+            "vars_schema.json",
+            "PlayMaster vars",
+        )?;
+        self.generate_index(&[
+            (
+                "feature_test_schema.json",
+                &["**/*.test.yaml", "**/*.test.yml"],
+            ),
+            ("config_schema.json", &["playmaster.yaml", "playmaster.yml"]),
+            ("vars_schema.json", &["**/*.vars.yaml", "**/*.vars.yml"]),
+        ])?;
+        Ok(())
+    }
+
+    /// Writes `index.json`, mapping file globs to the generated schemas.
+    ///
+    /// The content is a VS Code `yaml.schemas` setting, ready to be merged into `.vscode/settings.json`.
+    /// Schema paths are relative to the current directory, which should be the project root.
+    fn generate_index(&self, schemas: &[(&str, &[&str])]) -> EmptyResult {
+        let mapping = schemas
+            .iter()
+            .map(|(file_name, globs)| {
+                let path = self.out_dir.join(file_name).display().to_string();
+                (path, serde_json::json!(globs))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        let index = serde_json::json!({ "yaml.schemas": mapping });
+
+        let path = self.out_dir.join("index.json");
+        fs::create_dir_all(&self.out_dir)?;
+        fs::write(&path, serde_json::to_string_pretty(&index)?)?;
+        info!(
+            "✅ Schema index generated successfully at {}",
+            path.display()
+        );
         Ok(())
     }
 
     /// Generic JSON schema generator.
     ///
     /// # Arguments
-    /// * `output_path` - Path where the schema should be saved.
+    /// * `file_name` - File name of the schema inside the output directory.
+    /// * `title` - Human readable title of the schema.
     ///
     /// # Example
-    /// ```rust
-    /// generate_schema::<FeatureTest>("feature_test_schema.json")?;
+    /// ```ignore
+    /// generate_single::<FeatureTest>("feature_test_schema.json", "PlayMaster feature test")?;
     /// ```
-    fn generate_single<T>(&self, file_name: impl AsRef<Path>) -> EmptyResult
+    fn generate_single<T>(&self, file_name: &str, title: &str) -> EmptyResult
     where
         T: JsonSchema,
     {
-        let schema = schema_for!(T);
+        let mut schema = schema_for!(T);
+        schema.insert(
+            "$id".to_owned(),
+            format!("{}/{}", SCHEMA_BASE_URL, file_name).into(),
+        );
+        schema.insert("title".to_owned(), title.into());
+        schema.insert("x-schema-version".to_owned(), SCHEMA_VERSION.into());
         let schema_str = serde_json::to_string_pretty(&schema)?;
 
-        let path = utils::dir::DirUtils::curr_dir()?
-            .join("src")
-            .join("schemas")
-            .join("generated")
-            .join(file_name);
+        let path = self.out_dir.join(file_name);
 
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
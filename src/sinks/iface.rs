@@ -0,0 +1,14 @@
+use crate::{models::app_state::Results, utils::errors::EmptyResult};
+
+/// Destination the results of a test run are published to once all tests completed.
+pub trait ResultSink: Send + Sync {
+    /// Name shown in the logs, e.g. the webhook URL.
+    fn get_name(&self) -> String;
+
+    fn publish(&self, results: &Results) -> EmptyResult;
+
+    /// URL of the logs uploaded by the last publish, if any.
+    fn get_logs_url(&self) -> Option<String> {
+        None
+    }
+}
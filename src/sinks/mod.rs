@@ -0,0 +1,2 @@
+pub mod iface;
+pub mod webhook;
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use tracing::{debug, error, info};
+
+use crate::{
+    models::{
+        app_state::Results,
+        config::{S3Config, WebhookConfig},
+    },
+    sinks::iface::ResultSink,
+    utils::{
+        errors::{EmptyResult, ResultTrait, ResultWithError},
+        http::HttpUtils,
+        s3::S3Utils,
+        variables::VariablesUtils,
+    },
+};
+
+/// Sink posting the results to a webhook, with the logs optionally uploaded to S3.
+pub struct WebhookSink {
+    config: WebhookConfig,
+    proxy: Option<String>,
+    logs_url: Mutex<Option<String>>,
+}
+
+impl ResultSink for WebhookSink {
+    fn get_name(&self) -> String {
+        format!("webhook {}", self.config.url)
+    }
+
+    fn publish(&self, results: &Results) -> EmptyResult {
+        self.call_webhook(results)
+    }
+
+    fn get_logs_url(&self) -> Option<String> {
+        self.logs_url.lock().ok()?.clone()
+    }
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig, proxy: Option<String>) -> Self {
+        WebhookSink {
+            config,
+            proxy,
+            logs_url: Mutex::new(None),
+        }
+    }
+
+    fn call_webhook(&self, results: &Results) -> EmptyResult {
+        if self.config.url.is_empty() {
+            info!("No webhook URL configured, skipping webhook call.");
+            return Ok(());
+        }
+
+        info!("Calling webhook {}...", self.config.url);
+
+        let client = HttpUtils::client(self.proxy.as_deref())?;
+        let logs_url = if let Some(s3_config) = self.config.s3_config.as_ref() {
+            match self.upload_logs_to_s3(results, s3_config) {
+                Ok(url) => url,
+                Err(e) => {
+                    error!("Failed to upload logs to S3: {}", e);
+                    "".to_owned()
+                }
+            }
+        } else {
+            "".to_owned()
+        };
+        if !logs_url.is_empty()
+            && let Ok(mut stored) = self.logs_url.lock()
+        {
+            *stored = Some(logs_url.clone());
+        }
+        self.send_message(&client, results, &logs_url)?;
+
+        info!("Webhook called successfully.");
+
+        Ok(())
+    }
+
+    fn send_message(
+        &self,
+        client: &reqwest::blocking::Client,
+        results: &Results,
+        logs_url: &str,
+    ) -> EmptyResult {
+        let message = self.get_message(results, logs_url);
+        debug!("Webhook message: {}", message);
+
+        let payload =
+            serde_json::json!({ "text": message, "results": results, "logs_url": logs_url });
+        let res = client
+            .post(&self.config.url)
+            .json(&payload)
+            .send()
+            .auto_err("Failed to send webhook message: {}")?;
+
+        if !res.status().is_success() {
+            if self.config.ignore_error {
+                info!(
+                    "Webhook API returned error status: {}, but ignoring as per configuration.",
+                    res.status()
+                );
+                return Ok(());
+            }
+
+            return Err(format!("Webhook API returned error status: {}", res.status()).into());
+        }
+
+        Ok(())
+    }
+
+    fn get_message(&self, results: &Results, logs_url: &str) -> String {
+        let errors = if results.error.is_empty() {
+            "".to_owned()
+        } else {
+            results
+                .error
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let failed = if results.setup_failed > 0 {
+            format!(
+                "{} ({} in before_each)",
+                results.failed, results.setup_failed
+            )
+        } else {
+            results.failed.to_string()
+        };
+
+        if !self.config.message_template.is_empty() {
+            let mut data = serde_json::to_value(results)
+                .unwrap_or_default()
+                .as_object()
+                .cloned()
+                .unwrap_or_default()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.as_str().unwrap_or_default().to_owned()))
+                .collect::<HashMap<_, _>>();
+            data.insert("logs_url".to_owned(), logs_url.to_owned());
+            data.insert("errors".to_owned(), errors);
+
+            data.insert("passed".to_owned(), results.passed.to_string());
+            data.insert("failed".to_owned(), results.failed.to_string());
+            data.insert("setup_failed".to_owned(), results.setup_failed.to_string());
+            data.insert("skipped".to_owned(), results.skipped.to_string());
+            data.insert("total".to_owned(), results.total.to_string());
+
+            let status = if results.is_success() {
+                "Passed"
+            } else {
+                "Failed"
+            };
+            let status_icon = if status == "Passed" { "✅" } else { "❌" };
+            data.insert("status".to_owned(), status.to_owned());
+            data.insert("status_icon".to_owned(), status_icon.to_owned());
+
+            debug!("Webhook message data: {:?}", data);
+
+            VariablesUtils::replace_vars(&self.config.message_template, &data, None)
+        } else {
+            format!(
+                "Test Run Completed:\n✅ Passed: {}\n❌ Failed: {}\n⏭️ Skipped: {}\n📋 Total: {}\nStart Time: {}\nEnd Time: {}\nLogs: {}\nErrors: {}",
+                results.passed,
+                failed,
+                results.skipped,
+                results.total,
+                results.start_time,
+                results.end_time,
+                logs_url,
+                errors
+            )
+        }
+    }
+
+    fn upload_logs_to_s3(
+        &self,
+        results: &Results,
+        s3_config: &S3Config,
+    ) -> ResultWithError<String> {
+        if results.full_log.is_empty() {
+            debug!("No logs to upload to S3.");
+            return Ok("".to_owned());
+        }
+
+        let key_prefix = s3_config.key_prefix.clone();
+
+        // Sinks run on the blocking thread pool, where blocking on the runtime is allowed
+        tokio::runtime::Handle::current().block_on(async {
+            let s3 = S3Utils::client(s3_config).await;
+
+            let key = format!("{}results_{}.txt", key_prefix, results.start_time).replace(" ", "_");
+            let s3_url =
+                S3Utils::upload(&s3, s3_config, &key, results.full_log.clone().into_bytes())
+                    .await?;
+            info!("Logs uploaded to S3 successfully: {}", s3_url);
+
+            Ok(s3_url)
+        })
+    }
+}
@@ -1,8 +1,10 @@
 use std::{
     io::Read as _,
+    os::unix::process::CommandExt as _,
     path::Path,
-    process::{Child, Command},
-    sync::Mutex,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{Mutex, RwLock},
     time::Duration,
 };
 
@@ -11,51 +13,149 @@ use regex::Regex;
 use tracing::{debug, error, info};
 
 use crate::{
-    models::app_state::{CommandOutput, RemoteInfo},
+    models::{
+        app_state::{CommandOutput, RemoteInfo},
+        config::ShellConfig,
+    },
     utils::{
         errors::{EmptyResult, ResultTrait, ResultWithError},
         file_logger::FileLogger,
+        logger::LoggerUtils,
+        shlex::ShlexUtils,
     },
 };
 
+/// A local process group started by playmaster, led by the process it spawned.
 struct CmdInfo {
     name: String,
-    child: Child,
+    pgid: i32,
+    /// Set when the child is owned here, rather than waited on by the caller
+    child: Option<Child>,
 }
 
+/// A remote command, whose process group id is written to `pid_file` on the remote.
 struct RemoteCmdInfo {
-    command: String,
+    name: String,
+    pid_file: String,
     remote: RemoteInfo,
 }
 
 lazy_static::lazy_static! {
     static ref RUNNING_CMDS: Mutex<Vec<CmdInfo>> = Mutex::new(Vec::new());
     static ref RUNNING_REMOTE_CMDS: Mutex<Vec<RemoteCmdInfo>> = Mutex::new(Vec::new());
+    static ref SHELL: RwLock<ShellConfig> = RwLock::new(ShellConfig::default());
 }
 
+/// Name of the env file kept under the playmaster root dir, every line added by
+/// playmaster (PATH entries, DISPLAY...) goes there and it is sourced before each command.
+pub const ENV_FILE: &str = ".bashrc";
+
 pub struct CommandUtils {}
 
 impl CommandUtils {
+    /// Sets the shell used by every command ran afterwards, should be called once the config is loaded.
+    pub fn set_shell(shell: ShellConfig) -> EmptyResult {
+        debug!("Using shell: {:?}", shell);
+
+        let mut curr = SHELL.write().auto_err("Failed to lock SHELL")?;
+        *curr = shell;
+        Ok(())
+    }
+
+    /// Shell binary commands are run through with `-c`.
+    pub fn shell_program() -> String {
+        SHELL
+            .read()
+            .map(|shell| shell.program.clone())
+            .unwrap_or_else(|_| ShellConfig::default().program)
+    }
+
+    pub fn env_file(root_dir: &str) -> PathBuf {
+        PathBuf::from(root_dir).join(ENV_FILE)
+    }
+
+    /// Makes the command lead its own process group, so it can be terminated along with
+    /// everything it spawns. Stdin is closed, as a background process group can't read the terminal.
+    pub fn in_own_process_group(command: &mut Command) -> &mut Command {
+        command.process_group(0).stdin(Stdio::null())
+    }
+
+    /// Tracks a child spawned with `in_own_process_group`, to terminate it on shutdown.
     pub fn track_cmd(name: &str, child: Child) -> EmptyResult {
-        debug!("Tracking local command: {}", name);
+        let pgid = child.id() as i32;
+        debug!("Tracking local command: {} (pgid {})", name, pgid);
+
+        let mut vec = RUNNING_CMDS
+            .lock()
+            .auto_err("Failed to lock RUNNING_CMDS")?;
+        vec.push(CmdInfo {
+            name: name.to_string(),
+            pgid,
+            child: Some(child),
+        });
+        Ok(())
+    }
+
+    /// Tracks the process group of a child the caller waits on itself,
+    /// `untrack_process_group` must be called once it exits so the id isn't signaled after being reused.
+    pub fn track_process_group(name: &str, pgid: u32) -> EmptyResult {
+        debug!("Tracking local process group: {} (pgid {})", name, pgid);
 
         let mut vec = RUNNING_CMDS
             .lock()
             .auto_err("Failed to lock RUNNING_CMDS")?;
         vec.push(CmdInfo {
             name: name.to_string(),
-            child,
+            pgid: pgid as i32,
+            child: None,
         });
         Ok(())
     }
 
-    pub fn track_remote_cmd(command: String, remote: RemoteInfo) -> EmptyResult {
-        debug!("Tracking remote command: {}", command);
+    pub fn untrack_process_group(pgid: u32) -> EmptyResult {
+        let mut vec = RUNNING_CMDS
+            .lock()
+            .auto_err("Failed to lock RUNNING_CMDS")?;
+        vec.retain(|cmd| cmd.child.is_some() || cmd.pgid != pgid as i32);
+        Ok(())
+    }
+
+    /// Path of a new file on the remote to record the process group id of a command in.
+    pub fn remote_pid_file(root_dir: &str) -> String {
+        format!("{}/pids/{}.pgid", root_dir, uuid::Uuid::new_v4())
+    }
+
+    /// Wraps a remote command so it records its process group id in `pid_file` while it runs.
+    pub fn with_remote_pid_file(cmd: &str, pid_file: &str) -> String {
+        let dir = Path::new(pid_file)
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default();
+        format!(
+            "mkdir -p {dir} && ps -o pgid= -p $$ | tr -d ' ' > {pid_file}; {cmd}; status=$?; rm -f {pid_file}; exit $status"
+        )
+    }
+
+    /// Tracks a remote command wrapped with `with_remote_pid_file`, to terminate it on shutdown.
+    pub fn track_remote_cmd(name: &str, pid_file: &str, remote: RemoteInfo) -> EmptyResult {
+        debug!("Tracking remote command: {} ({})", name, pid_file);
+
+        let mut vec = RUNNING_REMOTE_CMDS
+            .lock()
+            .auto_err("Failed to lock RUNNING_REMOTE_CMDS")?;
+        vec.push(RemoteCmdInfo {
+            name: name.to_owned(),
+            pid_file: pid_file.to_owned(),
+            remote,
+        });
+        Ok(())
+    }
 
+    pub fn untrack_remote_cmd(pid_file: &str) -> EmptyResult {
         let mut vec = RUNNING_REMOTE_CMDS
             .lock()
             .auto_err("Failed to lock RUNNING_REMOTE_CMDS")?;
-        vec.push(RemoteCmdInfo { command, remote });
+        vec.retain(|cmd| cmd.pid_file != pid_file);
         Ok(())
     }
 
@@ -70,12 +170,30 @@ impl CommandUtils {
             .lock()
             .auto_err("Failed to lock RUNNING_CMDS")?;
 
-        for mut cmd in vec.drain(..) {
-            println!("Terminating local command: {}", cmd.name);
-            let _ = cmd.child.kill();
-            let output = cmd.child.wait_with_output();
+        for cmd in vec.drain(..) {
+            info!(
+                "Terminating local command: {} (sending SIGTERM to pgid {})",
+                cmd.name, cmd.pgid
+            );
+            Self::signal_process_group(cmd.pgid, libc::SIGTERM);
+
+            let Some(mut child) = cmd.child else {
+                continue;
+            };
 
-            match output {
+            // Give the group a moment to exit cleanly before killing it
+            for _ in 0..30 {
+                if matches!(child.try_wait(), Ok(Some(_))) {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            if matches!(child.try_wait(), Ok(None)) {
+                info!("Sending SIGKILL to pgid {} ({})", cmd.pgid, cmd.name);
+                Self::signal_process_group(cmd.pgid, libc::SIGKILL);
+            }
+
+            match child.wait_with_output() {
                 Ok(output) => {
                     info!(
                         "Terminated command '{}' with status: {}",
@@ -99,18 +217,39 @@ impl CommandUtils {
         Ok(())
     }
 
+    fn signal_process_group(pgid: i32, signal: i32) {
+        // SAFETY: killpg only sends a signal, an invalid or exited group is reported through errno
+        if unsafe { libc::killpg(pgid, signal) } != 0 {
+            debug!(
+                "Failed to signal pgid {}: {}",
+                pgid,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
     fn terminate_remote_cmds(root_dir: &str) -> EmptyResult {
         let mut vec = RUNNING_REMOTE_CMDS
             .lock()
             .auto_err("Failed to lock RUNNING_REMOTE_CMDS")?;
 
         for command in vec.drain(..) {
-            info!("Terminating remote command: {}", command.command);
-            Self::run_command_str(
-                &format!("pkill -f \"{}\"", command.command),
+            let output = Self::run_command_str(
+                &format!(
+                    "pgid=$(cat {0} 2>/dev/null) && [ -n \"$pgid\" ] && kill -TERM -- -$pgid; echo $pgid; rm -f {0}",
+                    command.pid_file
+                ),
                 Some(&command.remote),
                 root_dir,
             )?;
+
+            match output.stdout.trim() {
+                "" => info!("Remote command {} already exited", command.name),
+                pgid => info!(
+                    "Terminating remote command: {} (sent SIGTERM to pgid {})",
+                    command.name, pgid
+                ),
+            }
         }
 
         Ok(())
@@ -130,7 +269,7 @@ impl CommandUtils {
                 .auto_err("Failed to execute remote command")?;
             Ok(res)
         } else {
-            let output = std::process::Command::new("bash")
+            let output = std::process::Command::new(Self::shell_program())
                 .arg("-c")
                 .arg(cmd)
                 .stdout(std::process::Stdio::piped())
@@ -212,7 +351,7 @@ impl CommandUtils {
         )?;
 
         let ssh_target = format!("{}@{}", remote.user, remote.host);
-        let ssh_cmd = format!("ssh -p {}", remote.port);
+        let ssh_cmd = remote.ssh_command();
 
         let mut command = Command::new("sshpass");
         command.args([
@@ -248,6 +387,153 @@ impl CommandUtils {
         Ok(())
     }
 
+    /// Checks every file of a local directory made it to the remote with the same size,
+    /// returning the differences, empty when the copies match.
+    pub fn diff_synced_dir(
+        remote: &RemoteInfo,
+        root_dir: &str,
+        local_path: &str,
+        remote_path: &str,
+    ) -> ResultWithError<Vec<String>> {
+        let mut local = std::collections::BTreeMap::new();
+        CommandUtils::collect_file_sizes(Path::new(local_path), Path::new(local_path), &mut local)?;
+
+        let output = CommandUtils::run_command_str(
+            &format!(
+                "find {} -type f -printf '%P\\t%s\\n'",
+                ShlexUtils::quote(remote_path.trim_end_matches('/'))?
+            ),
+            Some(remote),
+            root_dir,
+        )?;
+        if output.status != 0 {
+            return Err(format!(
+                "Failed to list remote directory '{}': {}",
+                remote_path,
+                output.stderr.trim()
+            )
+            .into());
+        }
+        Ok(CommandUtils::compare_synced_files(&local, &output.stdout))
+    }
+
+    /// Differences between the local file sizes and the `find -printf '%P\t%s\n'` listing of
+    /// the remote copy, skipping the remote files under the rsync excludes.
+    fn compare_synced_files(
+        local: &std::collections::BTreeMap<String, u64>,
+        remote_listing: &str,
+    ) -> Vec<String> {
+        let remote_sizes = remote_listing
+            .lines()
+            .filter_map(|line| line.rsplit_once('\t'))
+            .filter(|(path, _)| !CommandUtils::is_sync_excluded(path))
+            .map(|(path, size)| (path.to_owned(), size.trim().parse::<u64>().ok()))
+            .collect::<std::collections::BTreeMap<_, _>>();
+
+        let mut diffs = vec![];
+        for (path, size) in local {
+            match remote_sizes.get(path) {
+                None => diffs.push(format!("{} is missing", path)),
+                Some(remote_size) if *remote_size != Some(*size) => diffs.push(format!(
+                    "{} has {} bytes instead of {}",
+                    path,
+                    remote_size.map_or("?".to_owned(), |s| s.to_string()),
+                    size
+                )),
+                _ => {}
+            }
+        }
+        for path in remote_sizes.keys().filter(|p| !local.contains_key(*p)) {
+            diffs.push(format!("{} is not in the local directory", path));
+        }
+
+        diffs
+    }
+
+    /// Relative path to size of the regular files under `dir`, skipping what rsync excludes.
+    fn collect_file_sizes(
+        base: &Path,
+        dir: &Path,
+        sizes: &mut std::collections::BTreeMap<String, u64>,
+    ) -> EmptyResult {
+        let entries = std::fs::read_dir(dir)
+            .auto_err(&format!("Failed to read directory {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let relative = path
+                .strip_prefix(base)
+                .auto_err("Synced file outside of its directory")?
+                .to_string_lossy()
+                .into_owned();
+            if CommandUtils::is_sync_excluded(&relative) {
+                continue;
+            }
+
+            // Symlinks are copied as links by rsync and not listed on the remote
+            let metadata = std::fs::symlink_metadata(&path)?;
+            if metadata.is_dir() {
+                CommandUtils::collect_file_sizes(base, &path, sizes)?;
+            } else if metadata.is_file() {
+                sizes.insert(relative, metadata.len());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a path relative to a synced directory is under one of the rsync excludes.
+    fn is_sync_excluded(relative: &str) -> bool {
+        let mut components = relative.split('/').collect::<Vec<_>>();
+        // The last component is a file, only directories are excluded
+        components.pop();
+        components
+            .iter()
+            .any(|c| matches!(*c, "build" | ".dart_tool" | ".git"))
+    }
+
+    /// Copies a remote directory into a local one, keeping local files missing on the remote.
+    pub fn sync_dir_from_remote(
+        remote: &RemoteInfo,
+        remote_path: &str,
+        local_path: &str,
+    ) -> EmptyResult {
+        debug!(
+            "Syncing remote path '{}' to local directory '{}'",
+            remote_path, local_path
+        );
+
+        std::fs::create_dir_all(local_path).auto_err("Failed to create local directory")?;
+
+        let ssh_target = format!("{}@{}", remote.user, remote.host);
+        let ssh_cmd = remote.ssh_command();
+
+        let mut command = Command::new("sshpass");
+        command.args([
+            "-p",
+            &remote.password,
+            "rsync",
+            "-azP",
+            "-e",
+            &ssh_cmd,
+            &format!("{}:{}/", ssh_target, remote_path.trim_end_matches('/')),
+            &format!("{}/", local_path.trim_end_matches('/')),
+        ]);
+        debug!("Running rsync command: {:?}", command);
+
+        let output = command
+            .output()
+            .auto_err("Failed to execute rsync command")?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        debug!("rsync stdout: {}", String::from_utf8_lossy(&output.stdout));
+        debug!("rsync stderr: {}", stderr);
+
+        if !output.status.success() {
+            return Err(format!("rsync failed: {}", stderr).into());
+        }
+
+        Ok(())
+    }
+
     /// Fetches the contents of a file from a remote host over SSH
     #[allow(dead_code)]
     pub fn fetch_remote_file(remote: &RemoteInfo, remote_path: &str) -> ResultWithError<String> {
@@ -277,7 +563,12 @@ impl CommandUtils {
         Ok(contents)
     }
 
+    /// Spinner shown while a test runs, hidden with JSON logs as it redraws the terminal line.
     pub fn display_loader(msg: String) -> ProgressBar {
+        if LoggerUtils::is_json() {
+            return ProgressBar::hidden();
+        }
+
         let spinner = ProgressBar::new_spinner();
         spinner.set_style(
             ProgressStyle::with_template("{spinner:.green} {msg}")
@@ -317,10 +608,86 @@ impl CommandUtils {
         .into_owned()
     }
 
+    /// Prefixes the command with the sourcing of the configured user profile, if any, then
+    /// the playmaster env file. Uses `.` rather than `source` so it also works with `sh`.
     pub fn with_env_source(root_dir: &str, str: &str) -> ResultWithError<String> {
-        Ok(format!(
-            "source {}/.bashrc > /dev/null 2>&1 || true; {}",
-            root_dir, str
-        ))
+        let profile = SHELL
+            .read()
+            .auto_err("Failed to lock SHELL")?
+            .profile
+            .clone();
+
+        let mut cmd = String::new();
+        if let Some(profile) = profile {
+            cmd.push_str(&format!(". {} > /dev/null 2>&1 || true; ", profile));
+        }
+        cmd.push_str(&format!(
+            ". {} > /dev/null 2>&1 || true; {}",
+            Self::env_file(root_dir).to_string_lossy(),
+            str
+        ));
+
+        Ok(cmd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn local_sizes(files: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        files
+            .iter()
+            .map(|(path, size)| (path.to_string(), *size))
+            .collect()
+    }
+
+    #[test]
+    fn matching_copies_have_no_differences() {
+        let local = local_sizes(&[("pubspec.yaml", 120), ("lib/main.dart", 4_096)]);
+        let remote = "pubspec.yaml\t120\nlib/main.dart\t4096\n";
+
+        assert!(CommandUtils::compare_synced_files(&local, remote).is_empty());
+    }
+
+    #[test]
+    fn synced_files_report_missing_resized_and_extra_files() {
+        let local = local_sizes(&[
+            ("pubspec.yaml", 120),
+            ("lib/main.dart", 4_096),
+            ("lib/login.dart", 800),
+        ]);
+        let remote = "pubspec.yaml\t120\n\
+                      lib/main.dart\t1024\n\
+                      lib/old.dart\t300\n\
+                      lib/broken.dart\tunknown\n";
+
+        assert_eq!(
+            CommandUtils::compare_synced_files(&local, remote),
+            [
+                "lib/login.dart is missing",
+                "lib/main.dart has 1024 bytes instead of 4096",
+                "lib/broken.dart is not in the local directory",
+                "lib/old.dart is not in the local directory",
+            ]
+        );
+    }
+
+    #[test]
+    fn excluded_remote_dirs_are_ignored() {
+        let local = local_sizes(&[("pubspec.yaml", 120)]);
+        let remote = "pubspec.yaml\t120\n\
+                      build/app.so\t9000\n\
+                      .dart_tool/package_config.json\t500\n\
+                      packages/ui/.git/HEAD\t21\n";
+
+        assert!(CommandUtils::compare_synced_files(&local, remote).is_empty());
+        // Only directories are excluded, not files named like them
+        assert_eq!(
+            CommandUtils::compare_synced_files(&local, "pubspec.yaml\t120\nbuild\t3\n"),
+            ["build is not in the local directory"]
+        );
     }
 }
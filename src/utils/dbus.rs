@@ -3,6 +3,8 @@ use regex::Regex;
 const DBUS_PATH: &str = "/one/playmaster/E2E";
 const DBUS_INTERFACE: &str = "one.playmaster.E2E";
 const DBUS_METHOD_CONTINUE: &str = "Continue";
+/// Prefix of the input names requested before each step in interactive mode.
+const DBUS_STEP_PAUSE_PREFIX: &str = "Step ";
 
 lazy_static::lazy_static! {
     static ref CONTINUE_REGEX: Regex = Regex::new(
@@ -25,6 +27,15 @@ impl DbusUtils {
         DBUS_METHOD_CONTINUE
     }
 
+    pub fn get_dbus_step_pause_prefix() -> &'static str {
+        DBUS_STEP_PAUSE_PREFIX
+    }
+
+    /// Label of the step about to run when the input name is an interactive mode pause.
+    pub fn identify_step_pause(input_name: &str) -> Option<&str> {
+        input_name.strip_prefix(DBUS_STEP_PAUSE_PREFIX)
+    }
+
     pub fn dbus_method_continue_cmd(input: &str) -> String {
         format!(
             "busctl --user call {} {} {} {} s \"{}\"",
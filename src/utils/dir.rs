@@ -4,7 +4,7 @@ use std::{
 };
 
 use serde::de::DeserializeOwned;
-use tracing::debug;
+use tracing::{debug, error, warn};
 
 use crate::{
     models::app_state::RemoteInfo,
@@ -16,9 +16,10 @@ pub enum YamlType {
     Vars,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct YamlResult<T> {
     pub file_name: String,
+    pub path: PathBuf,
     pub content: T,
 }
 
@@ -29,6 +30,8 @@ impl DirUtils {
         std::env::current_dir().auto_err("Could not read current directory")
     }
 
+    /// Computes the playmaster root dir, which runs `pwd` over SSH for remotes.
+    /// During a run prefer the cached value from `HookContext::get_root_dir`.
     pub fn root_dir(remote: Option<&RemoteInfo>) -> ResultWithError<std::path::PathBuf> {
         let home = if let Some(remote) = remote {
             remote
@@ -49,29 +52,54 @@ impl DirUtils {
             .join(".local/share/playmaster"))
     }
 
-    pub fn parse_all_from_curr_dir<T>(yaml_type: YamlType) -> ResultWithError<Vec<YamlResult<T>>>
+    /// Parses the YAML files of the given type found in each of the `test_dirs`,
+    /// relative to the current directory.
+    pub fn parse_all_from_curr_dir<T>(
+        yaml_type: YamlType,
+        test_dirs: &[String],
+    ) -> ResultWithError<Vec<YamlResult<T>>>
     where
         T: DeserializeOwned,
     {
-        let config_path = DirUtils::curr_dir()?.join("feature_test");
+        let curr_dir = DirUtils::curr_dir()?;
+        let mut res = Vec::new();
+        let mut failures = Vec::new();
+
+        for test_dir in test_dirs {
+            let config_path = curr_dir.join(test_dir);
+
+            if !config_path.exists() {
+                return Err(format!("{} directory not found", test_dir).into());
+            }
+
+            debug!("Searching for YAML files in {:?}", config_path);
+            let (parsed, failed) = Self::find_all_yaml(&config_path, &yaml_type)?;
+            res.extend(parsed);
+            failures.extend(failed);
+        }
 
-        if !config_path.exists() {
-            return Err("feature_test directory not found".into());
+        // Every broken file is listed, so they can all be fixed before the next run
+        if !failures.is_empty() {
+            for failure in &failures {
+                error!("{}", failure);
+            }
+            return Err(format!("{} YAML file(s) could not be parsed", failures.len()).into());
         }
 
-        debug!("Searching for YAML files in {:?}", config_path);
-        let res = Self::find_all_yaml(&config_path, yaml_type)?;
         Ok(res)
     }
 
+    /// Parses the YAML files of the given type under `config_path`, returning the parsed files
+    /// and the errors of those that couldn't be read or parsed. Empty files are skipped.
     fn find_all_yaml<T>(
         config_path: &Path,
-        yaml_type: YamlType,
-    ) -> ResultWithError<Vec<YamlResult<T>>>
+        yaml_type: &YamlType,
+    ) -> ResultWithError<(Vec<YamlResult<T>>, Vec<String>)>
     where
         T: DeserializeOwned,
     {
         let mut features = Vec::new();
+        let mut failures = Vec::new();
         let ends_with = match yaml_type {
             YamlType::FeatureTest => vec![".test.yaml", ".test.yml"],
             YamlType::Vars => vec![".vars.yaml", ".vars.yml"],
@@ -106,20 +134,81 @@ impl DirUtils {
                         continue;
                     };
 
-                    let content = fs::read_to_string(&path)
-                        .auto_err(&format!("Failed to read file: {:?}", path))?;
+                    let content = match fs::read_to_string(&path) {
+                        Ok(content) => content,
+                        Err(err) => {
+                            failures.push(format!("Failed to read file {:?}: {}", path, err));
+                            continue;
+                        }
+                    };
 
-                    let feature: T = serde_yaml::from_str(&content)
-                        .auto_err(&format!("Failed to parse YAML: {:?}", path))?;
+                    if content.trim().is_empty() {
+                        warn!("Skipping empty YAML file {:?}", path);
+                        continue;
+                    }
+
+                    let feature: T = match serde_yaml::from_str(&content) {
+                        Ok(feature) => feature,
+                        Err(err) => {
+                            failures.push(format!("Failed to parse YAML {:?}: {}", path, err));
+                            continue;
+                        }
+                    };
 
                     features.push(YamlResult {
                         file_name: file_name.to_string(),
+                        path,
                         content: feature,
                     });
                 }
             }
         }
 
-        Ok(features)
+        failures.sort();
+        Ok((features, failures))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::feature_test::FeatureTest;
+
+    #[test]
+    fn find_all_yaml_lists_every_broken_file_and_skips_empty_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("auth");
+        fs::create_dir(&nested).unwrap();
+        fs::write(
+            nested.join("login.test.yaml"),
+            "name: Login\ntests:\n  - name: Valid\n    steps: []\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("broken.test.yaml"), "name: [unclosed\n").unwrap();
+        fs::write(dir.path().join("missing_name.test.yml"), "tests: []\n").unwrap();
+        fs::write(dir.path().join("empty.test.yaml"), "  \n").unwrap();
+        fs::write(dir.path().join("common.vars.yaml"), "key: value\n").unwrap();
+
+        let (parsed, failures) =
+            DirUtils::find_all_yaml::<FeatureTest>(dir.path(), &YamlType::FeatureTest).unwrap();
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].file_name, "login");
+        assert_eq!(parsed[0].path, nested.join("login.test.yaml"));
+        assert_eq!(parsed[0].content.name, "Login");
+
+        // Sorted, so the files are listed in a stable order
+        assert_eq!(failures.len(), 2);
+        assert!(
+            failures[0].starts_with("Failed to parse YAML")
+                && failures[0].contains("broken.test.yaml"),
+            "{}",
+            failures[0]
+        );
+        assert!(
+            failures[1].contains("missing_name.test.yml") && failures[1].contains("name"),
+            "{}",
+            failures[1]
+        );
     }
 }
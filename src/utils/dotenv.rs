@@ -0,0 +1,104 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::errors::{ResultTrait as _, ResultWithError};
+
+const DEFAULT_ENV_FILE: &str = ".env";
+
+pub struct DotEnvUtils {}
+
+/// What `DotEnvUtils::load` did, to be logged once the logger is set up.
+#[derive(Debug)]
+pub struct EnvFileSummary {
+    pub path: PathBuf,
+    pub loaded: usize,
+    /// Keys already set in the environment, left untouched
+    pub kept: Vec<String>,
+}
+
+impl DotEnvUtils {
+    /// Loads `KEY=value` lines from `path`, or from `./.env` when it exists, into the process environment.
+    /// Variables already set are kept unless `override_existing` is set.
+    ///
+    /// Must run at startup, before the logger and the async runtime, so it returns what to log
+    /// instead of logging it. Returns `None` when there is no env file to load.
+    pub fn load(
+        path: Option<&Path>,
+        override_existing: bool,
+    ) -> ResultWithError<Option<EnvFileSummary>> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_ENV_FILE).is_file() => Path::new(DEFAULT_ENV_FILE),
+            None => return Ok(None),
+        };
+
+        let entries = dotenvy::from_path_iter(path)
+            .auto_err(&format!("Failed to read env file {}", path.display()))?;
+
+        let mut loaded = 0;
+        let mut kept = vec![];
+        for entry in entries {
+            let (key, value) =
+                entry.auto_err(&format!("Failed to parse env file {}", path.display()))?;
+
+            if !override_existing && env::var_os(&key).is_some() {
+                kept.push(key);
+                continue;
+            }
+
+            // SAFETY: called from a plain `main` before the logger, the tokio runtime and any other
+            // thread exist, so nothing else can read or write the environment concurrently.
+            unsafe { env::set_var(&key, value) };
+            loaded += 1;
+        }
+
+        Ok(Some(EnvFileSummary {
+            path: path.to_path_buf(),
+            loaded,
+            kept,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::*;
+    use crate::models::config::Config;
+
+    #[test]
+    fn env_file_value_expands_in_config() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "PLAYMASTER_DOTENV_TEST_DEVICE=pixel-7").unwrap();
+        writeln!(file, "# comment").unwrap();
+        writeln!(file, "PLAYMASTER_DOTENV_TEST_KEPT=from-file").unwrap();
+        // SAFETY: these variables are unique to this test, no other thread reads them.
+        unsafe { env::set_var("PLAYMASTER_DOTENV_TEST_KEPT", "from-env") };
+
+        let summary = DotEnvUtils::load(Some(file.path()), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.loaded, 1);
+        assert_eq!(summary.kept, vec!["PLAYMASTER_DOTENV_TEST_KEPT"]);
+
+        let config = Config::from_content(
+            "project_type: flutter\n\
+             device: \"{{ env.PLAYMASTER_DOTENV_TEST_DEVICE }}\"\n\
+             test_dirs: [\"{{ env.PLAYMASTER_DOTENV_TEST_KEPT }}\"]\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(config.device.as_deref(), Some("pixel-7"));
+        assert_eq!(config.test_dirs, vec!["from-env"]);
+    }
+
+    #[test]
+    fn missing_explicit_env_file_is_an_error() {
+        let err =
+            DotEnvUtils::load(Some(Path::new("/nonexistent/playmaster.env")), false).unwrap_err();
+        assert!(err.to_string().contains("Failed to read env file"));
+    }
+}
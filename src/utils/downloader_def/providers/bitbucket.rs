@@ -7,6 +7,7 @@ use std::path::Path;
 
 use crate::utils::downloader_def::r#trait::{ArtifactInfo, SourceProvider};
 use crate::utils::errors::ResultWithError;
+use crate::utils::http::HttpUtils;
 
 #[derive(Debug)]
 pub struct BitbucketSourceProvider {
@@ -17,16 +18,19 @@ pub struct BitbucketSourceProvider {
 }
 
 impl BitbucketSourceProvider {
-    pub fn new<S: Into<String>>(workspace: S, repo: S, token: Option<String>) -> Self {
-        let client = Client::builder()
-            .build()
-            .expect("Failed to create HTTP client");
-        Self {
+    pub fn new<S: Into<String>>(
+        workspace: S,
+        repo: S,
+        token: Option<String>,
+        proxy: Option<&str>,
+    ) -> ResultWithError<Self> {
+        let client = HttpUtils::client(proxy)?;
+        Ok(Self {
             client,
             workspace: workspace.into(),
             repo: repo.into(),
             token,
-        }
+        })
     }
 
     fn apply_auth(
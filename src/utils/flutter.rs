@@ -5,6 +5,9 @@ use crate::utils::{
     errors::{ResultTrait as _, ResultWithError},
 };
 
+/// Prefixes the failures thrown by the `before_each` steps, to tell them apart from assertions.
+pub const SETUP_FAILURE_PREFIX: &str = "[before_each]";
+
 pub struct FlutterUtils;
 
 impl FlutterUtils {
@@ -39,4 +42,111 @@ impl FlutterUtils {
             fs::read_to_string(main_dart_path).auto_err("Could not read lib/main.dart file")?;
         Ok(content.contains("main(List<String>"))
     }
+
+    /// Whether the test failed in its `before_each` steps rather than in its own steps.
+    pub fn is_setup_failure(output: &str) -> bool {
+        output.contains(SETUP_FAILURE_PREFIX)
+    }
+
+    /// Extracts the `EXCEPTION CAUGHT BY ...` block of a test output, with the Flutter
+    /// and Dart SDK frames dropped from its stack trace.
+    pub fn extract_exception(output: &str) -> Option<String> {
+        let mut lines = output
+            .lines()
+            .skip_while(|line| !line.contains("EXCEPTION CAUGHT BY"));
+        let header = lines.next()?;
+
+        let mut exception = vec![header.trim_matches(['═', '╡', '╞', ' '])];
+        for line in lines {
+            // The block ends with a line made of `═`
+            if line.starts_with('═') && line.chars().all(|c| c == '═') {
+                break;
+            }
+
+            let is_frame = line.starts_with('#');
+            let is_framework_frame = is_frame
+                // `(dart:` as the `.dart:` of every file path would match a bare `dart:`
+                && ["package:flutter/", "package:flutter_test/", "(dart:"]
+                    .iter()
+                    .any(|pkg| line.contains(pkg));
+            if is_framework_frame
+                || line.contains("<asynchronous suspension>")
+                || line.starts_with("(elided ")
+            {
+                continue;
+            }
+
+            exception.push(line);
+        }
+
+        Some(exception.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output collected for a test failing in `flutter drive`, trimmed like `process_lines` does.
+    const FAILED_TEST_OUTPUT: &str = "\
+══╡ EXCEPTION CAUGHT BY FLUTTER TEST FRAMEWORK ╞════════════════════════════════════════════════════
+The following TestFailure was thrown running a test:
+Expected: exactly one matching candidate
+Actual: _KeyWidgetFinder:<Found 0 widgets with key [<'home_title'>]: []>
+Which: means none were found but one was expected
+
+When the exception was thrown, this was the stack:
+#0      fail (package:matcher/src/expect/util.dart:149:31)
+#1      _expect (package:matcher/src/expect/expect.dart:125:3)
+#2      expect (package:flutter_test/src/widget_tester.dart:480:18)
+#3      main.<anonymous closure>.<anonymous closure> (file:///app/integration_test/generated/login_test.dart:42:7)
+<asynchronous suspension>
+#4      testWidgets.<anonymous closure>.<anonymous closure> (package:flutter_test/src/widget_tester.dart:189:15)
+<asynchronous suspension>
+#5      _rootRun (dart:async/zone.dart:1399:13)
+(elided one frame from package:stack_trace)
+
+This was caught by the test expectation on the following line:
+file:///app/integration_test/generated/login_test.dart line 42
+The test description was:
+Login - Valid credentials
+════════════════════════════════════════════════════════════════════════════════════════════════════
+00:13 +1 -1: Login - Valid credentials [E]
+Test failed. See exception logs above.
+";
+
+    #[test]
+    fn extracts_the_exception_without_framework_frames() {
+        let exception =
+            FlutterUtils::extract_exception(&format!("Logging in...\n\n{}", FAILED_TEST_OUTPUT))
+                .expect("Should find the exception");
+
+        assert_eq!(
+            exception,
+            "\
+EXCEPTION CAUGHT BY FLUTTER TEST FRAMEWORK
+The following TestFailure was thrown running a test:
+Expected: exactly one matching candidate
+Actual: _KeyWidgetFinder:<Found 0 widgets with key [<'home_title'>]: []>
+Which: means none were found but one was expected
+
+When the exception was thrown, this was the stack:
+#0      fail (package:matcher/src/expect/util.dart:149:31)
+#1      _expect (package:matcher/src/expect/expect.dart:125:3)
+#3      main.<anonymous closure>.<anonymous closure> (file:///app/integration_test/generated/login_test.dart:42:7)
+
+This was caught by the test expectation on the following line:
+file:///app/integration_test/generated/login_test.dart line 42
+The test description was:
+Login - Valid credentials"
+        );
+    }
+
+    #[test]
+    fn no_exception_without_the_flutter_block() {
+        assert_eq!(
+            FlutterUtils::extract_exception("00:05 +1: Login - Valid credentials\nAll good\n"),
+            None
+        );
+    }
 }
@@ -0,0 +1,70 @@
+use reqwest::blocking::Client;
+use tracing::debug;
+
+use crate::utils::errors::{ResultTrait as _, ResultWithError};
+
+pub struct HttpUtils {}
+
+impl HttpUtils {
+    /// Builds a blocking HTTP client.
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` are honored, unless `proxy` overrides them for every request.
+    pub fn client(proxy: Option<&str>) -> ResultWithError<Client> {
+        let mut builder = Client::builder();
+
+        if let Some(proxy) = proxy {
+            debug!("Using proxy from config for HTTP requests");
+            builder = builder.proxy(reqwest::Proxy::all(proxy).auto_err("Invalid proxy URL")?);
+        }
+
+        builder.build().auto_err("Failed to create HTTP client")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn requests_go_through_the_configured_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let proxy_thread = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .unwrap();
+            request_line
+        });
+
+        let client = HttpUtils::client(Some(&proxy)).unwrap();
+        let body = client
+            .get("http://downloads.example.invalid/flutter.tar.xz")
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+
+        assert_eq!(body, "ok");
+        assert_eq!(
+            proxy_thread.join().unwrap(),
+            "GET http://downloads.example.invalid/flutter.tar.xz HTTP/1.1\r\n"
+        );
+    }
+
+    #[test]
+    fn invalid_proxy_is_rejected() {
+        let err = HttpUtils::client(Some("not a url")).unwrap_err();
+
+        assert!(err.to_string().starts_with("Invalid proxy URL"), "{err}");
+    }
+}
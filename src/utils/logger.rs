@@ -1,16 +1,51 @@
-use tracing_subscriber::{EnvFilter, fmt};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing_subscriber::{EnvFilter, fmt, fmt::writer::BoxMakeWriter};
+
+use crate::models::args::LogFormat;
+
+/// Whether the logs are JSON, in which case nothing else may be written to the terminal
+static JSON_LOGS: AtomicBool = AtomicBool::new(false);
 
 pub struct LoggerUtils {}
 
 impl LoggerUtils {
-    pub fn init() {
-        fmt()
-            .with_env_filter(
-                EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-            )
-            .with_target(false) // Optional: hide module names
-            .with_level(true) // Show level (INFO, DEBUG, etc.)
-            .compact() // Compact single-line format for CLI tools
-            .init();
+    /// Whether `--log-format json` is active, so raw output must go through `tracing` or be
+    /// left out, keeping one JSON object per line.
+    pub fn is_json() -> bool {
+        JSON_LOGS.load(Ordering::Relaxed)
+    }
+
+    /// Logs to stdout, or to stderr when stdout is kept for a machine readable output.
+    pub fn init(format: &LogFormat, to_stderr: bool) {
+        JSON_LOGS.store(matches!(format, LogFormat::Json), Ordering::Relaxed);
+
+        let env_filter =
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+        let writer = if to_stderr {
+            BoxMakeWriter::new(std::io::stderr)
+        } else {
+            BoxMakeWriter::new(std::io::stdout)
+        };
+
+        match format {
+            LogFormat::Pretty => fmt()
+                .with_env_filter(env_filter)
+                .with_writer(writer)
+                .with_target(false) // Optional: hide module names
+                .with_level(true) // Show level (INFO, DEBUG, etc.)
+                .compact() // Compact single-line format for CLI tools
+                .init(),
+            // One JSON object per event, with the thread name so worker and signal events can be told apart
+            LogFormat::Json => fmt()
+                .with_env_filter(env_filter)
+                .with_writer(writer)
+                .with_target(true)
+                .with_level(true)
+                .with_thread_names(true)
+                .with_ansi(false)
+                .json()
+                .init(),
+        }
     }
 }
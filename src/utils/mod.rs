@@ -1,15 +1,20 @@
 pub mod command;
 pub mod dbus;
 pub mod dir;
+pub mod dotenv;
 pub mod downloader_def;
 pub mod errors;
 pub mod execution;
 pub mod file_logger;
 pub mod flutter;
+pub mod http;
 pub mod logger;
 pub mod os;
+pub mod s3;
 pub mod semver;
 pub mod serializers;
+pub mod setup_marker;
 pub mod shlex;
 pub mod string;
+pub mod timing;
 pub mod variables;
@@ -9,12 +9,15 @@ use rand::{Rng as _, distr::Alphanumeric};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 use crate::{
     hooks::iface::HookContext,
     models::app_state::{AppState, RemoteInfo},
-    utils::errors::{EmptyResult, ResultTrait, ResultWithError},
+    utils::{
+        errors::{EmptyResult, ResultTrait, ResultWithError},
+        logger::LoggerUtils,
+    },
 };
 
 #[derive(Debug)]
@@ -168,6 +171,11 @@ impl OsUtils {
     }
 
     pub fn ask(prompt: &str) -> ResultWithError<String> {
+        Self::ask_with_timeout(prompt, Some(Duration::from_secs(30)))
+    }
+
+    /// Reads a line from stdin, empty when nothing was entered before the timeout, if any.
+    pub fn ask_with_timeout(prompt: &str, timeout: Option<Duration>) -> ResultWithError<String> {
         print!("{}", prompt);
         io::stdout()
             .flush()
@@ -182,12 +190,25 @@ impl OsUtils {
             }
         });
 
-        let res = match rx.recv_timeout(Duration::from_secs(30)) {
-            Ok(input) => input,
-            Err(_) => {
-                println!("\nTimeout: no input received within 30 seconds");
-                String::new()
-            }
+        let res = match timeout {
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(input) => input,
+                Err(_) if LoggerUtils::is_json() => {
+                    warn!(
+                        "Timeout: no input received within {} seconds",
+                        timeout.as_secs()
+                    );
+                    String::new()
+                }
+                Err(_) => {
+                    println!(
+                        "\nTimeout: no input received within {} seconds",
+                        timeout.as_secs()
+                    );
+                    String::new()
+                }
+            },
+            None => rx.recv().unwrap_or_default(),
         };
 
         Ok(res)
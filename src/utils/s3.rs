@@ -0,0 +1,247 @@
+use std::{fmt::Display, time::Duration};
+
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::{
+    config::ProvideCredentials as _, presigning::PresigningConfig, primitives::ByteStream,
+    types::ObjectCannedAcl,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    models::config::{S3Acl, S3Config},
+    utils::errors::{ResultTrait, ResultWithError},
+};
+
+const UPLOAD_ATTEMPTS: u32 = 3;
+const UPLOAD_BACKOFF: Duration = Duration::from_secs(1);
+
+pub struct S3Utils;
+
+impl S3Utils {
+    /// Builds a client from the AWS environment, with the region and endpoint overrides of the config.
+    pub async fn client(s3_config: &S3Config) -> aws_sdk_s3::Client {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = &s3_config.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+        let config = loader.load().await;
+
+        let mut builder = aws_sdk_s3::config::Builder::from(&config);
+        if let Some(endpoint_url) = &s3_config.endpoint_url {
+            debug!("Using custom S3 endpoint: {}", endpoint_url);
+            builder = builder.endpoint_url(endpoint_url).force_path_style(true);
+        }
+
+        aws_sdk_s3::Client::from_conf(builder.build())
+    }
+
+    /// Resolves credentials from the AWS environment, as the client would, without calling S3.
+    pub async fn check_credentials(s3_config: &S3Config) -> ResultWithError<String> {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = &s3_config.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+        let config = loader.load().await;
+
+        let provider = config
+            .credentials_provider()
+            .ok_or("No AWS credentials provider found")?;
+        let credentials = provider
+            .provide_credentials()
+            .await
+            .map_err(|e| format!("No AWS credentials found: {}", e))?;
+
+        Ok(format!(
+            "access key {}…, region {}",
+            credentials
+                .access_key_id()
+                .chars()
+                .take(4)
+                .collect::<String>(),
+            config
+                .region()
+                .map(|r| r.to_string())
+                .unwrap_or_else(|| "unset".to_owned())
+        ))
+    }
+
+    /// Uploads `body` to `key` in the configured bucket and returns a URL to it,
+    /// presigned unless disabled in the config.
+    pub async fn upload(
+        s3: &aws_sdk_s3::Client,
+        s3_config: &S3Config,
+        key: &str,
+        body: Vec<u8>,
+    ) -> ResultWithError<String> {
+        info!("Uploading to S3 path: s3://{}/{}", s3_config.bucket, key);
+
+        let acl = match s3_config.acl {
+            S3Acl::Private => ObjectCannedAcl::Private,
+            S3Acl::PublicRead => ObjectCannedAcl::PublicRead,
+        };
+
+        // Retry with exponential backoff, so a transient error doesn't drop the URL
+        Self::with_retries("upload to S3", UPLOAD_BACKOFF, || {
+            s3.put_object()
+                .bucket(&s3_config.bucket)
+                .key(key)
+                .acl(acl.clone())
+                .body(ByteStream::from(body.clone()))
+                .send()
+        })
+        .await?;
+
+        Self::object_url(s3, s3_config, key).await
+    }
+
+    /// Runs `run` up to `UPLOAD_ATTEMPTS` times, waiting `backoff`, then twice as long,
+    /// between the failed attempts.
+    async fn with_retries<T, E, F, Fut>(
+        action: &str,
+        backoff: Duration,
+        mut run: F,
+    ) -> ResultWithError<T>
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            info!(
+                "Trying to {}, attempt {}/{}",
+                action, attempt, UPLOAD_ATTEMPTS
+            );
+
+            match run().await {
+                Ok(res) => return Ok(res),
+                Err(err) if attempt < UPLOAD_ATTEMPTS => {
+                    let backoff = backoff * 2u32.pow(attempt - 1);
+                    warn!(
+                        "Failed to {} (attempt {}/{}): {}, retrying in {:?}",
+                        action, attempt, UPLOAD_ATTEMPTS, err, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(format!(
+                        "Failed to {} after {} attempts: {}",
+                        action, UPLOAD_ATTEMPTS, err
+                    )
+                    .into());
+                }
+            }
+        }
+    }
+
+    async fn object_url(
+        s3: &aws_sdk_s3::Client,
+        s3_config: &S3Config,
+        key: &str,
+    ) -> ResultWithError<String> {
+        if !s3_config.presign {
+            return Ok(match &s3_config.endpoint_url {
+                Some(endpoint_url) => format!(
+                    "{}/{}/{}",
+                    endpoint_url.trim_end_matches('/'),
+                    s3_config.bucket,
+                    key
+                ),
+                None => format!("https://{}.s3.amazonaws.com/{}", s3_config.bucket, key),
+            });
+        }
+
+        let presigning =
+            PresigningConfig::expires_in(Duration::from_secs(s3_config.presign_expiry_secs))
+                .auto_err("Invalid S3 presign expiry")?;
+        let request = s3
+            .get_object()
+            .bucket(&s3_config.bucket)
+            .key(key)
+            .presigned(presigning)
+            .await
+            .auto_err("Failed to presign S3 URL")?;
+
+        Ok(request.uri().to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Runs `with_retries` over an operation failing its first `failures` attempts.
+    fn run_failing(failures: u32) -> (ResultWithError<u32>, u32) {
+        let attempts = Cell::new(0);
+        let res = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(S3Utils::with_retries(
+                "upload to S3",
+                Duration::from_millis(1),
+                || {
+                    attempts.set(attempts.get() + 1);
+                    let attempt = attempts.get();
+                    async move {
+                        if attempt <= failures {
+                            Err(format!("503 Slow Down #{}", attempt))
+                        } else {
+                            Ok(attempt)
+                        }
+                    }
+                },
+            ));
+        (res, attempts.get())
+    }
+
+    #[test]
+    fn retries_until_the_upload_succeeds() {
+        let (res, attempts) = run_failing(2);
+
+        assert_eq!(res.unwrap(), 3);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let (res, attempts) = run_failing(5);
+
+        assert_eq!(attempts, UPLOAD_ATTEMPTS);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Failed to upload to S3 after 3 attempts: 503 Slow Down #3"
+        );
+    }
+
+    #[test]
+    fn does_not_retry_a_successful_upload() {
+        let (res, attempts) = run_failing(0);
+
+        assert_eq!(res.unwrap(), 1);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn custom_endpoint_uses_path_style_urls() {
+        let s3_config: S3Config = serde_yaml::from_str(
+            "{ bucket: logs, presign: false, endpoint_url: \"http://localhost:9000/\", region: eu-west-3 }",
+        )
+        .unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        let s3 = runtime.block_on(S3Utils::client(&s3_config));
+        let url = runtime
+            .block_on(S3Utils::object_url(&s3, &s3_config, "run/flutter.log"))
+            .unwrap();
+
+        assert_eq!(s3.config().region().unwrap().as_ref(), "eu-west-3");
+        assert_eq!(url, "http://localhost:9000/logs/run/flutter.log");
+    }
+}
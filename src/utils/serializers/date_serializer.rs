@@ -26,7 +26,6 @@ where
 //        D: Deserializer<'de>
 //
 // although it may also be generic over the output types T.
-#[allow(dead_code)]
 pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
 where
     D: Deserializer<'de>,
@@ -35,7 +34,6 @@ where
     deserialize_date::<'de, D>(s)
 }
 
-#[allow(dead_code)]
 fn deserialize_date<'de, D>(s: String) -> Result<DateTime<Utc>, D::Error>
 where
     D: Deserializer<'de>,
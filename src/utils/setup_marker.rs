@@ -0,0 +1,94 @@
+use std::{fs, path::PathBuf};
+
+use tracing::{debug, info};
+
+use crate::{
+    models::app_state::RemoteInfo,
+    utils::{
+        command::CommandUtils,
+        errors::{EmptyResult, ResultWithError},
+        shlex::ShlexUtils,
+    },
+};
+
+const MARKER_FILE: &str = ".setup-complete";
+
+/// Marker file written in the root dir once setup succeeded, holding the hash of the config
+/// it succeeded with, so `--once` can skip setup until the config changes.
+pub struct SetupMarker;
+
+impl SetupMarker {
+    fn path(root_dir: &str) -> PathBuf {
+        PathBuf::from(root_dir).join(MARKER_FILE)
+    }
+
+    /// Whether setup already succeeded with the config of the given hash.
+    pub fn matches(
+        remote: Option<&RemoteInfo>,
+        root_dir: &str,
+        config_hash: &str,
+    ) -> ResultWithError<bool> {
+        let path = Self::path(root_dir);
+        let content = if remote.is_some() {
+            let cmd = format!(
+                "cat {} 2>/dev/null || true",
+                ShlexUtils::quote(&path.to_string_lossy())?
+            );
+            CommandUtils::run_command_str(&cmd, remote, root_dir)?.stdout
+        } else {
+            fs::read_to_string(&path).unwrap_or_default()
+        };
+
+        debug!("Setup marker {}: {}", path.display(), content.trim());
+        Ok(!config_hash.is_empty() && content.trim() == config_hash)
+    }
+
+    pub fn write(remote: Option<&RemoteInfo>, root_dir: &str, config_hash: &str) -> EmptyResult {
+        let path = Self::path(root_dir);
+        if remote.is_some() {
+            let cmd = format!(
+                "printf '%s\\n' {} > {}",
+                ShlexUtils::quote(config_hash)?,
+                ShlexUtils::quote(&path.to_string_lossy())?
+            );
+            let res = CommandUtils::run_command_str(&cmd, remote, root_dir)?;
+            if res.status != 0 {
+                return Err(format!("Failed to write setup marker: {}", res.stderr).into());
+            }
+        } else {
+            fs::write(&path, format!("{}\n", config_hash))?;
+        }
+
+        info!("Setup marked as complete in {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marker_matches_the_hash_it_was_written_with() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_dir = dir.path().to_string_lossy();
+
+        // No marker yet
+        assert!(!SetupMarker::matches(None, &root_dir, "abc123").unwrap());
+
+        SetupMarker::write(None, &root_dir, "abc123").unwrap();
+        assert!(SetupMarker::matches(None, &root_dir, "abc123").unwrap());
+    }
+
+    #[test]
+    fn marker_does_not_match_another_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_dir = dir.path().to_string_lossy();
+        SetupMarker::write(None, &root_dir, "abc123").unwrap();
+
+        // The config changed since setup succeeded
+        assert!(!SetupMarker::matches(None, &root_dir, "def456").unwrap());
+        // A config without hash never skips setup
+        assert!(!SetupMarker::matches(None, &root_dir, "").unwrap());
+    }
+}
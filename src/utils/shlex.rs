@@ -4,7 +4,6 @@ use shlex::Shlex;
 
 use crate::utils::errors::{OptionResultTrait as _, ResultWithError};
 
-#[allow(dead_code)]
 pub struct ShlexUtils {}
 
 impl ShlexUtils {
@@ -19,4 +18,91 @@ impl ShlexUtils {
         command.args(args);
         Ok(command)
     }
+
+    /// Quotes a string so a POSIX shell reads it back as a single word, whatever quotes,
+    /// `$`, backticks, backslashes or newlines it contains.
+    pub fn quote(input: &str) -> ResultWithError<String> {
+        shlex::try_quote(input)
+            .map(|quoted| quoted.into_owned())
+            .map_err(|e| format!("Failed to quote '{}': {}", input, e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Values the run commands embed, e.g. `--env` values and remote paths.
+    const CASES: &[&str] = &[
+        "plain",
+        "",
+        "two words",
+        "it's",
+        "say \"hi\"",
+        "$HOME and ${PATH}",
+        "`whoami` and $(id)",
+        "back\\slash",
+        "first line\nsecond line",
+        "'\"$`\\\n; rm -rf /tmp/x &",
+    ];
+
+    /// Output of `printf %s <arg>` run by `sh -c`, the arg being shell code.
+    fn sh_printf(arg: &str) -> String {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf %s {}", arg))
+            .output()
+            .expect("Should run sh");
+        assert!(output.status.success(), "sh failed for {}", arg);
+        String::from_utf8(output.stdout).expect("Should be UTF-8")
+    }
+
+    #[test]
+    fn quote_splits_back_into_the_same_word() {
+        for case in CASES {
+            let quoted = ShlexUtils::quote(case).expect("Should quote");
+            assert_eq!(
+                shlex::split(&quoted),
+                Some(vec![case.to_string()]),
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn quote_reaches_the_shell_unchanged() {
+        for case in CASES {
+            let quoted = ShlexUtils::quote(case).expect("Should quote");
+            assert_eq!(sh_printf(&quoted), *case);
+        }
+    }
+
+    #[test]
+    fn quote_survives_being_wrapped_in_another_shell() {
+        // As remote commands are run with `<shell> -c <quoted command>`
+        for case in CASES {
+            let inner = format!(
+                "printf %s {}",
+                ShlexUtils::quote(case).expect("Should quote")
+            );
+            let wrapped = format!("sh -c {}", ShlexUtils::quote(&inner).expect("Should quote"));
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(&wrapped)
+                .output()
+                .expect("Should run sh");
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                *case,
+                "{}",
+                wrapped
+            );
+        }
+    }
+
+    #[test]
+    fn quote_rejects_nul_bytes() {
+        assert!(ShlexUtils::quote("a\0b").is_err());
+    }
 }
@@ -1,6 +1,15 @@
 pub struct StringUtils;
 
 impl StringUtils {
+    /// Hides a secret in logs, keeping its first characters to tell secrets apart.
+    pub fn redact(secret: &str) -> String {
+        if secret.chars().count() <= 8 {
+            return "***".to_owned();
+        }
+
+        format!("{}***", secret.chars().take(4).collect::<String>())
+    }
+
     pub fn to_pascal_case_with_dots(name: &str) -> String {
         if !name.contains(".") {
             return name.to_owned();
@@ -0,0 +1,158 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    hooks::iface::HookContext,
+    models::app_state::{AppState, PhaseTiming},
+};
+
+/// Times a phase of the run until dropped, recording it in the results with `--profile-build`.
+pub struct TimingSpan {
+    name: String,
+    start: Instant,
+    state: Option<Arc<RwLock<AppState>>>,
+}
+
+impl TimingSpan {
+    pub fn start(ctx: &HookContext<'_, AppState>, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            start: Instant::now(),
+            state: ctx.args.is_profile_build().then(|| Arc::clone(&ctx.state)),
+        }
+    }
+
+    /// Logs the duration of each recorded phase and its share of their total.
+    pub fn print_breakdown(timings: &[PhaseTiming]) {
+        if timings.is_empty() {
+            return;
+        }
+
+        let total = timings.iter().map(|t| t.duration_ms).sum::<u64>().max(1);
+        let width = timings.iter().map(|t| t.name.len()).max().unwrap_or(0);
+
+        info!("⏱️ Timing breakdown:");
+        for timing in timings {
+            info!(
+                "  {:<width$}  {:>8.1}s  {:>5.1}%",
+                timing.name,
+                timing.duration_ms as f64 / 1000.0,
+                timing.duration_ms as f64 * 100.0 / total as f64,
+            );
+        }
+        info!("  {:<width$}  {:>8.1}s", "total", total as f64 / 1000.0);
+    }
+}
+
+impl Drop for TimingSpan {
+    fn drop(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let timing = PhaseTiming {
+            name: std::mem::take(&mut self.name),
+            duration_ms: self.start.elapsed().as_millis() as u64,
+        };
+        match state.write() {
+            Ok(mut state) => state.results.phase_timings.push(timing),
+            Err(_) => warn!("Failed to record the duration of phase {}", timing.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use clap::Parser as _;
+
+    use super::*;
+    use crate::{
+        hooks::iface::HookType,
+        models::{args::AppArgs, config::Config, vars::Vars},
+    };
+
+    /// Phase names recorded by a run spanning the same phases as `CodeRun::execute`,
+    /// along with the saved results.
+    fn recorded_phases(args: &[&str]) -> (Vec<String>, serde_json::Value) {
+        let args = AppArgs::parse_from(args);
+        let config: Config =
+            serde_yaml::from_str("project_type: flutter").expect("Should parse config");
+        let vars = Vars {
+            data: vec![],
+            all_vars: HashMap::new(),
+        };
+        let ctx = HookContext {
+            args: &args,
+            config: &config,
+            vars: &vars,
+            state: Arc::new(RwLock::new(AppState::default())),
+        };
+
+        let mut phases = vec!["generate".to_owned()];
+        phases.extend(
+            HookType::pre_hooks()
+                .iter()
+                .map(|h| h.error_phase().as_str().to_owned()),
+        );
+        phases
+            .extend(["build", "sync integration tests", "sync pubspec", "drive"].map(String::from));
+        phases.extend(
+            HookType::post_hooks()
+                .iter()
+                .map(|h| h.error_phase().as_str().to_owned()),
+        );
+        for phase in phases {
+            let _span = TimingSpan::start(&ctx, phase);
+        }
+
+        let results = ctx.read_state().unwrap().results.clone();
+        let names = results
+            .phase_timings
+            .iter()
+            .map(|t| t.name.clone())
+            .collect();
+        (names, serde_json::to_value(&results).unwrap())
+    }
+
+    #[test]
+    fn profile_build_records_every_phase_in_order() {
+        let (names, saved) = recorded_phases(&["playmaster", "run", "--profile-build"]);
+
+        assert_eq!(
+            names,
+            [
+                "generate",
+                "connect",
+                "verify",
+                "prepare",
+                "build",
+                "sync integration tests",
+                "sync pubspec",
+                "drive",
+                "report",
+            ]
+        );
+        let saved_names = saved["phase_timings"]
+            .as_array()
+            .expect("Should save the phase timings")
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(saved_names, names);
+        assert!(saved["phase_timings"][0]["duration_ms"].is_u64());
+    }
+
+    #[test]
+    fn phases_are_not_recorded_without_profile_build() {
+        let (names, saved) = recorded_phases(&["playmaster", "run"]);
+
+        assert!(names.is_empty());
+        assert!(saved.get("phase_timings").is_none());
+    }
+}
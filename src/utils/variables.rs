@@ -1,5 +1,9 @@
 use regex::Regex;
-use std::{collections::HashMap, env};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::Mutex,
+};
 
 use crate::utils::string::StringUtils;
 
@@ -10,8 +14,14 @@ lazy_static::lazy_static! {
     static ref ENV_VAR_RE: Regex =
         Regex::new(r"\{\{\s*env\.([A-Za-z_][A-Za-z0-9_]*)\s*\}\}")
             .expect("Failed to compile environment variable regex");
+
+    /// `vars.<file>.<key>` keys resolved so far, for the `--warn-unused-vars` report
+    static ref USED_VARS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
 }
 
+/// Dart variable holding the index of the innermost `repeat` step, read with `{{ loop.index }}`.
+pub const LOOP_INDEX_VAR: &str = "loopIndex";
+
 pub struct VariablesUtils {}
 
 impl VariablesUtils {
@@ -34,6 +44,12 @@ impl VariablesUtils {
                 };
 
                 let key = cap.as_str().trim();
+                if key == "loop.index" {
+                    return format!("${{{}}}", LOOP_INDEX_VAR);
+                }
+                if key.starts_with("vars.") {
+                    Self::mark_used(key);
+                }
                 format!(
                     "${{{}}}",
                     StringUtils::to_pascal_case_with_dots(&key.replace("vars.", ""))
@@ -42,6 +58,14 @@ impl VariablesUtils {
             .to_string()
     }
 
+    /// Whether `input` reads the `repeat` index with `{{ loop.index }}`.
+    pub fn uses_loop_index(input: &str) -> bool {
+        VERSION_RE.captures_iter(input).any(|caps| {
+            caps.get(1)
+                .is_some_and(|cap| cap.as_str().trim() == "loop.index")
+        })
+    }
+
     pub fn replace_vars(
         input: &str,
         vars: &HashMap<String, String>,
@@ -61,10 +85,39 @@ impl VariablesUtils {
                     return val.clone();
                 }
 
-                vars.get(key)
+                match vars.get(key) {
+                    Some(val) => {
+                        Self::mark_used(key);
+                        val.clone()
+                    }
+                    None => format!("${{{{{}}}}}", key),
+                }
+            })
+            .to_string()
+    }
+
+    /// Replaces the `{{ name }}` usages of the given arguments, leaving the other usages as written.
+    pub fn replace_args(input: &str, args: &HashMap<String, String>) -> String {
+        VERSION_RE
+            .replace_all(input, |caps: &regex::Captures| {
+                args.get(caps[1].trim())
                     .cloned()
-                    .unwrap_or_else(|| format!("${{{{{}}}}}", key))
+                    .unwrap_or_else(|| caps[0].to_owned())
             })
             .to_string()
     }
+
+    fn mark_used(key: &str) {
+        if let Ok(mut used) = USED_VARS.lock() {
+            used.insert(key.to_owned());
+        }
+    }
+
+    /// Keys of the vars resolved by `replace_var_usage` and `replace_vars` so far.
+    pub fn used_vars() -> HashSet<String> {
+        USED_VARS
+            .lock()
+            .map(|used| used.clone())
+            .unwrap_or_default()
+    }
 }